@@ -1,11 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::path::Path as FsPath;
+use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::routing::{get, post};
-use axum::{Json, Router};
+use axum::extract::{Path, Query, RawForm, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Json, Router};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
@@ -14,6 +19,7 @@ use tokio::sync::Mutex;
 pub enum VmStatus {
     Running,
     Stopped,
+    Paused,
 }
 
 impl VmStatus {
@@ -21,6 +27,7 @@ impl VmStatus {
         match self {
             VmStatus::Running => "running",
             VmStatus::Stopped => "stopped",
+            VmStatus::Paused => "paused",
         }
     }
 }
@@ -34,36 +41,470 @@ pub struct VmEntry {
     pub notes: Option<String>,
 }
 
-#[derive(Debug, Default)]
+impl VmEntry {
+    /// A stopped VM without tags or notes.
+    pub fn builder(vmid: u64, name: &str) -> VmEntryBuilder {
+        VmEntryBuilder {
+            vm: VmEntry {
+                vmid,
+                name: name.to_string(),
+                tags: Vec::new(),
+                status: VmStatus::Stopped,
+                notes: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VmEntryBuilder {
+    vm: VmEntry,
+}
+
+impl VmEntryBuilder {
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.vm.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    pub fn status(mut self, status: VmStatus) -> Self {
+        self.vm.status = status;
+        self
+    }
+
+    pub fn notes(mut self, notes: Option<&str>) -> Self {
+        self.vm.notes = notes.map(String::from);
+        self
+    }
+
+    pub fn build(self) -> VmEntry {
+        self.vm
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct VmResources {
+    pub maxmem: Option<u64>,
+    pub maxcpu: Option<f64>,
+    pub disk: Option<u64>,
+    pub uptime: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub description: Option<String>,
+    pub snaptime: Option<u64>,
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageEntry {
+    pub storage: String,
+    #[serde(rename = "type")]
+    pub storage_type: String,
+    pub content: String,
+    pub avail: u64,
+    pub total: u64,
+    pub used: u64,
+    pub enabled: u8,
+    pub active: u8,
+    pub shared: u8,
+}
+
+/// Parameters of a clone request accepted by the dummy server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloneRecord {
+    pub source_vmid: u64,
+    pub newid: u64,
+    pub name: Option<String>,
+    pub full: Option<u8>,
+    pub snapname: Option<String>,
+    pub storage: Option<String>,
+    pub target: Option<String>,
+    pub pool: Option<String>,
+}
+
+/// A backup archive stored by the dummy's `vzdump` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub volid: String,
+    pub storage: String,
+    pub vmid: u64,
+    pub size: u64,
+    pub ctime: u64,
+    pub format: String,
+    pub notes: Option<String>,
+}
+
+/// A command run through the dummy guest agent. `echo` prints its arguments;
+/// any other command exits with 127.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentExecRecord {
+    pub pid: u64,
+    pub vmid: u64,
+    pub command: Vec<String>,
+}
+
+/// CPU and memory settings from a VM's config; memory is in MiB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmHardware {
+    pub sockets: u32,
+    pub cores: u32,
+    pub memory: u64,
+}
+
+impl Default for VmHardware {
+    /// What Proxmox assumes when the config leaves the keys unset.
+    fn default() -> Self {
+        Self {
+            sockets: 1,
+            cores: 1,
+            memory: 512,
+        }
+    }
+}
+
+/// A disk resize accepted by the dummy server; disk sizes are not tracked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskResizeRecord {
+    pub vmid: u64,
+    pub disk: String,
+    pub size: String,
+}
+
+/// A file fetched into storage through `download-url`. Nothing is downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadRecord {
+    pub storage: String,
+    pub content: String,
+    pub filename: String,
+    pub url: String,
+}
+
+/// Status changes kept per VM; older ones are dropped.
+pub const MAX_STATUS_TRANSITIONS: usize = 10;
+
+/// A VM status change; `transitioned_at` is in Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmStatusTransition {
+    pub from: VmStatus,
+    pub to: VmStatus,
+    pub transitioned_at: u64,
+}
+
+/// A key press sent to a running VM through `sendkey`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentKeyRecord {
+    pub vmid: u64,
+    pub key: String,
+}
+
+/// A VM firewall rule. Like Proxmox, rules are addressed by their index in the
+/// VM's rule list, which is reported as `pos`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRuleEntry {
+    pub action: String,
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub enable: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest: Option<String>,
+}
+
+/// A finished task; dummy tasks complete as soon as they are created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskEntry {
+    pub upid: String,
+    pub kind: String,
+    pub vmid: u64,
+    pub starttime: u64,
+    pub exit_status: String,
+    pub log: Vec<String>,
+}
+
+/// A request received by the dummy server, recorded in arrival order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 struct DummyState {
     node: String,
     vms: HashMap<u64, VmEntry>,
+    resources: HashMap<u64, VmResources>,
+    snapshots: HashMap<u64, Vec<SnapshotEntry>>,
+    storages: Vec<StorageEntry>,
+    clones: Vec<CloneRecord>,
+    backups: Vec<BackupEntry>,
+    tasks: Vec<TaskEntry>,
+    agent_execs: Vec<AgentExecRecord>,
+    disk_resizes: Vec<DiskResizeRecord>,
+    downloads: Vec<DownloadRecord>,
+    sent_keys: Vec<SentKeyRecord>,
+    firewall_rules: HashMap<u64, Vec<FirewallRuleEntry>>,
+    /// Cloud-init drive and settings (`ide2`, `ciuser`, `ipconfig0`, ...) as sent.
+    cloudinit: HashMap<u64, BTreeMap<String, String>>,
+    /// Disks attached through config updates (`scsi0`, ...) as sent.
+    disks: HashMap<u64, BTreeMap<String, String>>,
+    /// Last status changes per VM, oldest first.
+    transitions: HashMap<u64, VecDeque<VmStatusTransition>>,
+    /// Only VMs whose CPU or memory was changed; others use the defaults.
+    hardware: HashMap<u64, VmHardware>,
+    /// Exit status for the next task instead of `OK`.
+    next_task_failure: Option<String>,
+    requests: Vec<RecordedRequest>,
+    /// When set, clone requests succeed but the new VM never appears.
+    drop_clones: bool,
+    /// When set, shutdown requests are accepted but the guest keeps running.
+    ignore_shutdown: bool,
+    /// When set, start requests fail with 500 and the VM stays stopped.
+    fail_starts: bool,
+    /// Unix time of each VM's last start request, for `uptime`.
+    started_at: HashMap<u64, u64>,
+    /// Per-VM time a start or shutdown takes to change the VM's status.
+    power_delays: HashMap<u64, Duration>,
+    /// Expected `Authorization` header value, e.g. `PVEAPIToken=id=secret`.
+    auth_token: Option<String>,
+    auth_required: bool,
+    pools: HashMap<String, PoolEntry>,
+    acl: Vec<AclEntry>,
+    /// Privileges of the calling token by path; [`ROOT_PRIVILEGES`] on `/` when unset.
+    permissions: Option<BTreeMap<String, Vec<String>>>,
+    /// Failures injected per request path, checked before authentication.
+    #[serde(skip)]
+    route_errors: HashMap<String, InjectedError>,
+    /// Until then every Proxmox route answers 503, as if the host were unreachable.
+    #[serde(skip)]
+    partition_until: Option<Instant>,
+    /// Bearer secret for `/admin/state`; the endpoint is disabled without it.
+    #[serde(skip)]
+    admin_secret: Option<String>,
+}
+
+/// A resource pool; `members` are vmids in the order they joined.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolEntry {
+    pub comment: Option<String>,
+    pub members: Vec<u64>,
+}
+
+/// An ACL entry as listed by `/access/acl`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub ugid: String,
+    pub roleid: String,
+    pub propagate: u8,
+}
+
+/// Privileges reported on `/` until [`DummyHandle::set_permissions`] is used,
+/// as for a token of `root@pam` without privilege separation.
+pub const ROOT_PRIVILEGES: &[&str] = &[
+    "Datastore.Allocate",
+    "Datastore.AllocateSpace",
+    "Datastore.Audit",
+    "Pool.Allocate",
+    "Pool.Audit",
+    "Sys.Audit",
+    "VM.Allocate",
+    "VM.Audit",
+    "VM.Backup",
+    "VM.Clone",
+    "VM.Config.Disk",
+    "VM.Console",
+    "VM.PowerMgmt",
+    "VM.Snapshot",
+    "VM.Snapshot.Rollback",
+];
+
+/// A failure the dummy produces for a route instead of answering normally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectedError {
+    /// Responds with this status; a 429 also carries `Retry-After`.
+    Status(StatusCode),
+    /// Stalls the response this long before handling the request, e.g. to
+    /// trip the client's request timeout.
+    Delay(Duration),
+}
+
+impl DummyState {
+    /// Moves `vmid` to `status`, remembering the change in its transitions.
+    fn set_vm_status(&mut self, vmid: u64, status: VmStatus) {
+        let Some(vm) = self.vms.get_mut(&vmid) else {
+            return;
+        };
+        let from = std::mem::replace(&mut vm.status, status);
+        if from == status {
+            return;
+        }
+        let history = self.transitions.entry(vmid).or_default();
+        if history.len() == MAX_STATUS_TRANSITIONS {
+            history.pop_front();
+        }
+        history.push_back(VmStatusTransition {
+            from,
+            to: status,
+            transitioned_at: unix_now_ms(),
+        });
+    }
+
+    /// Records a finished task and returns its UPID. Consumes any pending failure.
+    fn record_task(&mut self, kind: &str, vmid: u64) -> TaskEntry {
+        let starttime = unix_now();
+        let exit_status = self
+            .next_task_failure
+            .take()
+            .unwrap_or_else(|| "OK".to_string());
+        let log = if exit_status == "OK" {
+            vec!["TASK OK".to_string()]
+        } else {
+            vec![format!("TASK ERROR: {exit_status}")]
+        };
+        let task = TaskEntry {
+            upid: format!(
+                "UPID:{}:{:08X}:00000000:{starttime:08X}:{kind}:{vmid}:root@pam:",
+                self.node,
+                self.tasks.len() + 1
+            ),
+            kind: kind.to_string(),
+            vmid,
+            starttime,
+            exit_status,
+            log,
+        };
+        self.tasks.push(task.clone());
+        task
+    }
+
+    fn resource_vm(&self, vm: &VmEntry) -> ResourceVm {
+        let resources = self.resources.get(&vm.vmid).copied().unwrap_or_default();
+        ResourceVm {
+            vmid: vm.vmid,
+            name: Some(vm.name.clone()),
+            tags: Some(vm.tags.join(";")),
+            status: Some(vm.status.as_str().to_string()),
+            node: Some(self.node.clone()),
+            description: vm.notes.clone(),
+            maxmem: resources.maxmem,
+            maxcpu: resources.maxcpu,
+            disk: resources.disk,
+            uptime: resources.uptime,
+        }
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct DummyHandle {
     state: Arc<Mutex<DummyState>>,
+    /// Port of the listener passed to [`DummyHandle::serve`]; zero until then.
+    port: Arc<AtomicU16>,
 }
 
 impl DummyHandle {
     pub fn new(node: impl Into<String>) -> Self {
-        let mut state = DummyState::default();
-        state.node = node.into();
+        let state = DummyState {
+            node: node.into(),
+            ..Default::default()
+        };
         Self {
             state: Arc::new(Mutex::new(state)),
+            port: Arc::default(),
         }
     }
 
+    /// A handle for `node` holding the VMs in `json`, a JSON array of [`VmEntry`].
+    pub fn from_json(node: impl Into<String>, json: &str) -> Result<Self, serde_json::Error> {
+        let vms: Vec<VmEntry> = serde_json::from_str(json)?;
+        let state = DummyState {
+            node: node.into(),
+            vms: vms.into_iter().map(|vm| (vm.vmid, vm)).collect(),
+            ..Default::default()
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            port: Arc::default(),
+        })
+    }
+
+    /// Restores a handle from a file written by [`DummyHandle::save_to_file`].
+    pub async fn load_from_file(path: &FsPath) -> Result<Self, std::io::Error> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let state: DummyState = serde_json::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            port: Arc::default(),
+        })
+    }
+
+    /// Writes the full dummy state (VMs, snapshots, tasks, request log and
+    /// injected task failures) as JSON. Route errors are not saved.
+    pub async fn save_to_file(&self, path: &FsPath) -> Result<(), std::io::Error> {
+        let contents = {
+            let state = self.state.lock().await;
+            serde_json::to_vec_pretty(&*state)?
+        };
+        tokio::fs::write(path, contents).await
+    }
+
     pub async fn insert_vm(&self, vm: VmEntry) {
         let mut state = self.state.lock().await;
         state.vms.insert(vm.vmid, vm);
     }
 
+    /// Inserts every VM of a JSON array of [`VmEntry`]; nothing is inserted if
+    /// any entry fails to parse.
+    pub async fn insert_vms_from_json(&self, json: &str) -> Result<(), serde_json::Error> {
+        let vms: Vec<VmEntry> = serde_json::from_str(json)?;
+        let mut state = self.state.lock().await;
+        state.vms.extend(vms.into_iter().map(|vm| (vm.vmid, vm)));
+        Ok(())
+    }
+
+    /// Removes every VM, keeping the rest of the state.
+    pub async fn clear_vms(&self) {
+        let mut state = self.state.lock().await;
+        state.vms.clear();
+    }
+
     pub async fn set_status(&self, vmid: u64, status: VmStatus) {
         let mut state = self.state.lock().await;
-        if let Some(vm) = state.vms.get_mut(&vmid) {
-            vm.status = status;
-        }
+        state.set_vm_status(vmid, status);
+    }
+
+    /// Status changes of `vmid`, oldest first; at most [`MAX_STATUS_TRANSITIONS`].
+    pub async fn transitions(&self, vmid: u64) -> Vec<VmStatusTransition> {
+        let state = self.state.lock().await;
+        state
+            .transitions
+            .get(&vmid)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn set_vm_resources(&self, vmid: u64, resources: VmResources) {
+        let mut state = self.state.lock().await;
+        state.resources.insert(vmid, resources);
+    }
+
+    pub async fn snapshots(&self, vmid: u64) -> Vec<SnapshotEntry> {
+        let state = self.state.lock().await;
+        state.snapshots.get(&vmid).cloned().unwrap_or_default()
+    }
+
+    pub async fn add_storage(&self, storage: StorageEntry) {
+        let mut state = self.state.lock().await;
+        state.storages.push(storage);
     }
 
     pub async fn status(&self, vmid: u64) -> Option<VmStatus> {
@@ -71,8 +512,216 @@ impl DummyHandle {
         state.vms.get(&vmid).map(|vm| vm.status)
     }
 
+    pub async fn vm(&self, vmid: u64) -> Option<VmEntry> {
+        let state = self.state.lock().await;
+        state.vms.get(&vmid).cloned()
+    }
+
+    pub async fn clones(&self) -> Vec<CloneRecord> {
+        let state = self.state.lock().await;
+        state.clones.clone()
+    }
+
+    pub async fn backups(&self) -> Vec<BackupEntry> {
+        self.state.lock().await.backups.clone()
+    }
+
+    pub async fn agent_execs(&self) -> Vec<AgentExecRecord> {
+        self.state.lock().await.agent_execs.clone()
+    }
+
+    /// `None` for unknown VMs.
+    pub async fn hardware(&self, vmid: u64) -> Option<VmHardware> {
+        let state = self.state.lock().await;
+        state
+            .vms
+            .contains_key(&vmid)
+            .then(|| state.hardware.get(&vmid).copied().unwrap_or_default())
+    }
+
+    pub async fn disk_resizes(&self) -> Vec<DiskResizeRecord> {
+        self.state.lock().await.disk_resizes.clone()
+    }
+
+    pub async fn downloads(&self) -> Vec<DownloadRecord> {
+        self.state.lock().await.downloads.clone()
+    }
+
+    pub async fn sent_keys(&self) -> Vec<SentKeyRecord> {
+        self.state.lock().await.sent_keys.clone()
+    }
+
+    pub async fn add_firewall_rule(&self, vmid: u64, rule: FirewallRuleEntry) {
+        let mut state = self.state.lock().await;
+        state.firewall_rules.entry(vmid).or_default().push(rule);
+    }
+
+    pub async fn firewall_rules(&self, vmid: u64) -> Vec<FirewallRuleEntry> {
+        let state = self.state.lock().await;
+        state.firewall_rules.get(&vmid).cloned().unwrap_or_default()
+    }
+
+    pub async fn cloudinit(&self, vmid: u64) -> BTreeMap<String, String> {
+        let state = self.state.lock().await;
+        state.cloudinit.get(&vmid).cloned().unwrap_or_default()
+    }
+
+    pub async fn disks(&self, vmid: u64) -> BTreeMap<String, String> {
+        let state = self.state.lock().await;
+        state.disks.get(&vmid).cloned().unwrap_or_default()
+    }
+
+    pub async fn tasks(&self) -> Vec<TaskEntry> {
+        let state = self.state.lock().await;
+        state.tasks.clone()
+    }
+
+    pub async fn set_task_log(&self, upid: &str, lines: Vec<String>) {
+        let mut state = self.state.lock().await;
+        if let Some(task) = state.tasks.iter_mut().find(|task| task.upid == upid) {
+            task.log = lines;
+        }
+    }
+
+    /// Makes the next task finish with `exit_status`; for clones no VM is created.
+    pub async fn fail_next_task(&self, exit_status: impl Into<String>) {
+        let mut state = self.state.lock().await;
+        state.next_task_failure = Some(exit_status.into());
+    }
+
+    pub async fn requests(&self) -> Vec<RecordedRequest> {
+        let state = self.state.lock().await;
+        state.requests.clone()
+    }
+
+    /// Panics unless the VM power actions received so far (POSTs to
+    /// `.../qemu/{vmid}/status/{action}`) are exactly `expected`, in order.
+    pub async fn assert_action_sequence(&self, expected: &[(u64, &str)]) {
+        let requests = self.requests().await;
+        let actual: Vec<(u64, &str)> = requests
+            .iter()
+            .filter(|request| request.method == "POST")
+            .filter_map(|request| status_action(&request.path))
+            .collect();
+        assert_eq!(
+            actual, expected,
+            "VM action sequence differs\n  actual:   {actual:?}\n  expected: {expected:?}"
+        );
+    }
+
+    /// Makes start and shutdown requests for `vmid` return a UPID right away
+    /// and change the VM's status only after `delay`, like a real guest.
+    pub async fn set_vm_power_transition_delay(&self, vmid: u64, delay: Duration) {
+        let mut state = self.state.lock().await;
+        state.power_delays.insert(vmid, delay);
+    }
+
+    pub async fn set_drop_clones(&self, drop_clones: bool) {
+        let mut state = self.state.lock().await;
+        state.drop_clones = drop_clones;
+    }
+
+    /// Rejects requests with 401 unless they carry this API token.
+    pub async fn require_auth(&self, token_id: &str, token_secret: &str) {
+        let mut state = self.state.lock().await;
+        state.auth_token = Some(format!("PVEAPIToken={token_id}={token_secret}"));
+        state.auth_required = true;
+    }
+
+    pub async fn set_auth_required(&self, required: bool) {
+        let mut state = self.state.lock().await;
+        state.auth_required = required;
+    }
+
+    pub async fn set_ignore_shutdown(&self, ignore_shutdown: bool) {
+        let mut state = self.state.lock().await;
+        state.ignore_shutdown = ignore_shutdown;
+    }
+
+    pub async fn set_fail_starts(&self, fail_starts: bool) {
+        let mut state = self.state.lock().await;
+        state.fail_starts = fail_starts;
+    }
+
+    pub async fn add_pool(&self, poolid: impl Into<String>, comment: Option<&str>) {
+        let mut state = self.state.lock().await;
+        state.pools.insert(
+            poolid.into(),
+            PoolEntry {
+                comment: comment.map(str::to_string),
+                members: Vec::new(),
+            },
+        );
+    }
+
+    pub async fn pool(&self, poolid: &str) -> Option<PoolEntry> {
+        self.state.lock().await.pools.get(poolid).cloned()
+    }
+
+    pub async fn add_acl_entry(&self, entry: AclEntry) {
+        self.state.lock().await.acl.push(entry);
+    }
+
+    /// Grants the calling token `privileges` on `path`. The first call drops
+    /// the default [`ROOT_PRIVILEGES`].
+    pub async fn set_permissions(&self, path: &str, privileges: &[&str]) {
+        let mut state = self.state.lock().await;
+        state.permissions.get_or_insert_with(BTreeMap::new).insert(
+            path.to_string(),
+            privileges
+                .iter()
+                .map(|privilege| privilege.to_string())
+                .collect(),
+        );
+    }
+
+    /// Makes every request to `path` (e.g. `/api2/json/cluster/resources`,
+    /// query excluded) fail with `error` until cleared.
+    pub async fn set_error_on_route(&self, path: impl Into<String>, error: InjectedError) {
+        let mut state = self.state.lock().await;
+        state.route_errors.insert(path.into(), error);
+    }
+
+    /// Enables `GET`/`POST /admin/state` for requests sending
+    /// `Authorization: Bearer <secret>`.
+    pub async fn set_admin_secret(&self, secret: impl Into<String>) {
+        self.state.lock().await.admin_secret = Some(secret.into());
+    }
+
+    /// Port the server is listening on, once [`DummyHandle::serve`] has been
+    /// given a bound listener.
+    pub fn port(&self) -> Option<u16> {
+        match self.port.load(Ordering::Relaxed) {
+            0 => None,
+            port => Some(port),
+        }
+    }
+
+    pub async fn clear_route_errors(&self) {
+        let mut state = self.state.lock().await;
+        state.route_errors.clear();
+    }
+
+    /// Answers every Proxmox route with 503 for `duration`, then recovers on
+    /// its own. `/admin/state` stays reachable.
+    pub async fn simulate_network_partition(&self, duration: Duration) {
+        self.state.lock().await.partition_until = Some(Instant::now() + duration);
+    }
+
+    /// Like [`DummyHandle::simulate_network_partition`], lasting until
+    /// [`DummyHandle::end_partition`].
+    pub async fn simulate_permanent_partition(&self) {
+        self.simulate_network_partition(PERMANENT_PARTITION).await;
+    }
+
+    pub async fn end_partition(&self) {
+        self.state.lock().await.partition_until = None;
+    }
+
     pub fn router(&self) -> Router {
         Router::new()
+            .route("/api2/json/nodes", get(list_nodes))
+            .route("/api2/json/nodes/:node/status", get(node_status))
             .route("/api2/json/nodes/:node/qemu", get(list_vms))
             .route(
                 "/api2/json/nodes/:node/qemu/:vmid/status/current",
@@ -90,12 +739,118 @@ impl DummyHandle {
                 "/api2/json/nodes/:node/qemu/:vmid/status/stop",
                 post(stop_vm),
             )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/status/suspend",
+                post(suspend_vm),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/status/resume",
+                post(resume_vm),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/status/reboot",
+                post(reboot_vm),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/status/reset",
+                post(reset_vm),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/config",
+                get(vm_config).put(update_config),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/snapshot",
+                get(list_snapshots).post(create_snapshot),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/snapshot/:snapname",
+                delete(delete_snapshot),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/snapshot/:snapname/rollback",
+                post(rollback_snapshot),
+            )
+            .route("/api2/json/nodes/:node/qemu/:vmid", delete(destroy_vm))
+            .route("/api2/json/nodes/:node/qemu/:vmid/clone", post(clone_vm))
+            .route("/api2/json/nodes/:node/qemu/:vmid/resize", put(resize_disk))
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/agent/exec",
+                post(agent_exec),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/vncproxy",
+                post(vnc_proxy),
+            )
+            .route("/api2/json/nodes/:node/qemu/:vmid/sendkey", put(send_key))
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/firewall/rules",
+                get(list_firewall_rules),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/firewall/rules/:pos",
+                put(update_firewall_rule),
+            )
+            .route(
+                "/api2/json/nodes/:node/qemu/:vmid/agent/exec-status",
+                get(agent_exec_status),
+            )
+            .route("/api2/json/nodes/:node/qemu/:vmid/rrddata", get(vm_rrddata))
+            .route("/api2/json/nodes/:node/tasks", get(list_tasks))
+            .route(
+                "/api2/json/nodes/:node/tasks/:upid/status",
+                get(task_status),
+            )
+            .route("/api2/json/nodes/:node/tasks/:upid/log", get(task_log))
+            .route("/api2/json/nodes/:node/storage", get(list_storages))
+            .route(
+                "/api2/json/nodes/:node/storage/:storage/status",
+                get(storage_status),
+            )
+            .route(
+                "/api2/json/nodes/:node/storage/:storage/content",
+                get(storage_content),
+            )
+            .route(
+                "/api2/json/nodes/:node/storage/:storage/download-url",
+                post(download_url),
+            )
+            .route("/api2/json/nodes/:node/vzdump", post(vzdump))
+            .route("/api2/json/pools", get(list_pools))
+            .route("/api2/json/pools/:poolid", get(get_pool).put(update_pool))
             .route("/api2/json/cluster/resources", get(list_cluster_resources))
+            .route("/api2/json/cluster/nextid", get(next_vmid))
+            .route("/api2/json/cluster/status", get(cluster_status))
+            .route("/api2/json/version", get(version))
+            .route("/api2/json/access/acl", get(list_acl))
+            .route("/api2/json/access/permissions", get(permissions))
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                check_auth,
+            ))
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                inject_errors,
+            ))
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                record_request,
+            ))
+            .route("/admin/state", get(dump_state).post(load_state))
             .with_state(self.state.clone())
     }
 
-    pub async fn serve(self, listener: tokio::net::TcpListener) -> Result<(), std::io::Error> {
-        axum::serve(listener, self.router()).await
+    /// Serves on `listener`. The port is recorded for [`DummyHandle::port`]
+    /// right away, before the returned future is first polled.
+    pub fn serve(
+        self,
+        listener: tokio::net::TcpListener,
+    ) -> impl std::future::Future<Output = Result<(), std::io::Error>> {
+        if let Ok(addr) = listener.local_addr() {
+            self.port.store(addr.port(), Ordering::Relaxed);
+        }
+        let server = axum::serve(listener, self.router());
+        async move { server.await }
     }
 }
 
@@ -112,11 +867,55 @@ struct ResourceVm {
     status: Option<String>,
     node: Option<String>,
     description: Option<String>,
+    maxmem: Option<u64>,
+    maxcpu: Option<f64>,
+    disk: Option<u64>,
+    uptime: Option<u64>,
+}
+
+/// Entry of `/nodes/{node}/qemu`: no `node` or `description`, CPUs as `cpus`.
+#[derive(Debug, Serialize)]
+struct NodeVm {
+    vmid: u64,
+    name: String,
+    tags: String,
+    status: String,
+    maxmem: Option<u64>,
+    cpus: Option<f64>,
+    disk: Option<u64>,
+    uptime: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
 struct StatusPayload {
     status: String,
+    uptime: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigUpdate {
+    tags: Option<String>,
+    description: Option<String>,
+    sockets: Option<u32>,
+    cores: Option<u32>,
+    memory: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapshotForm {
+    snapname: String,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloneForm {
+    newid: u64,
+    name: Option<String>,
+    full: Option<u8>,
+    snapname: Option<String>,
+    storage: Option<String>,
+    target: Option<String>,
+    pool: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -126,10 +925,223 @@ struct ResourceQuery {
     vmid: Option<u64>,
 }
 
-async fn list_vms(
+/// Extracts `(vmid, action)` from a `.../qemu/{vmid}/status/{action}` path.
+fn status_action(path: &str) -> Option<(u64, &str)> {
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    match segments.as_slice() {
+        [.., "qemu", vmid, "status", action] => Some((vmid.parse().ok()?, *action)),
+        _ => None,
+    }
+}
+
+async fn record_request(
+    State(state): State<Arc<Mutex<DummyState>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let recorded = RecordedRequest {
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+    };
+    state.lock().await.requests.push(recorded);
+    next.run(request).await
+}
+
+/// Seconds advertised in `Retry-After` on injected 429 responses.
+const INJECTED_RETRY_AFTER_SECS: u64 = 7;
+
+/// How long a "permanent" partition lasts; far beyond any test.
+const PERMANENT_PARTITION: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+async fn inject_errors(
+    State(state): State<Arc<Mutex<DummyState>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (partitioned, injected) = {
+        let state = state.lock().await;
+        (
+            state
+                .partition_until
+                .is_some_and(|until| Instant::now() < until),
+            state.route_errors.get(request.uri().path()).copied(),
+        )
+    };
+    if partitioned {
+        let mut response = Response::new(axum::body::Body::from("network partition"));
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        return response;
+    }
+    match injected {
+        Some(InjectedError::Status(status)) => {
+            let mut response = Response::new(axum::body::Body::from(format!(
+                "injected {}",
+                status.as_u16()
+            )));
+            *response.status_mut() = status;
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, INJECTED_RETRY_AFTER_SECS.into());
+            }
+            response
+        }
+        Some(InjectedError::Delay(delay)) => {
+            tokio::time::sleep(delay).await;
+            next.run(request).await
+        }
+        None => next.run(request).await,
+    }
+}
+
+async fn check_auth(
+    State(state): State<Arc<Mutex<DummyState>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    {
+        let state = state.lock().await;
+        if state.auth_required {
+            let provided = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok());
+            if provided.is_none() || provided != state.auth_token.as_deref() {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Only the `DUMMY_ADMIN_SECRET` bearer may read or replace the state.
+fn check_admin_secret(
+    state: &DummyState,
+    headers: &axum::http::HeaderMap,
+) -> Result<(), StatusCode> {
+    let Some(secret) = state.admin_secret.as_deref() else {
+        return Err(StatusCode::FORBIDDEN);
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match provided {
+        None => Err(StatusCode::UNAUTHORIZED),
+        Some(provided) if provided == secret => Ok(()),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// The full state in the format of [`DummyHandle::save_to_file`].
+async fn dump_state(
+    State(state): State<Arc<Mutex<DummyState>>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let state = state.lock().await;
+    check_admin_secret(&state, &headers)?;
+    serde_json::to_value(&*state)
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Replaces the state with a dump; injected route errors, any network
+/// partition and the admin secret are kept.
+async fn load_state(
+    State(state): State<Arc<Mutex<DummyState>>>,
+    headers: axum::http::HeaderMap,
+    Json(loaded): Json<DummyState>,
+) -> Result<StatusCode, StatusCode> {
+    let mut state = state.lock().await;
+    check_admin_secret(&state, &headers)?;
+    let route_errors = std::mem::take(&mut state.route_errors);
+    let admin_secret = state.admin_secret.take();
+    *state = DummyState {
+        route_errors,
+        partition_until: state.partition_until,
+        admin_secret,
+        ..loaded
+    };
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Synthetic node figures: 16 GiB of RAM with a quarter in use.
+const NODE_MAXMEM: u64 = 16 * 1024 * 1024 * 1024;
+const NODE_MEM: u64 = NODE_MAXMEM / 4;
+const NODE_CPU: f64 = 0.05;
+const NODE_UPTIME: u64 = 3600;
+const NODE_KERNEL: &str = "Linux 6.8.12-4-pve #1 SMP PREEMPT_DYNAMIC";
+
+async fn list_nodes(
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = state.lock().await;
+    Json(ApiResponse {
+        data: serde_json::json!([{
+            "node": state.node,
+            "status": "online",
+            "cpu": NODE_CPU,
+            "mem": NODE_MEM,
+            "maxmem": NODE_MAXMEM,
+            "uptime": NODE_UPTIME,
+        }]),
+    })
+}
+
+/// A single-node cluster named after the configured node.
+async fn cluster_status(
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = state.lock().await;
+    Json(ApiResponse {
+        data: serde_json::json!([
+            {
+                "type": "cluster",
+                "id": "cluster",
+                "name": format!("{}-cluster", state.node),
+                "nodes": 1,
+                "quorate": 1,
+                "version": 1,
+            },
+            {
+                "type": "node",
+                "id": format!("node/{}", state.node),
+                "name": state.node,
+                "nodeid": 1,
+                "online": 1,
+                "local": 1,
+                "ip": "127.0.0.1",
+            },
+        ]),
+    })
+}
+
+async fn node_status(
     Path(node): Path<String>,
     State(state): State<Arc<Mutex<DummyState>>>,
-) -> Result<Json<ApiResponse<Vec<ResourceVm>>>, StatusCode> {
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(ApiResponse {
+        data: serde_json::json!({
+            "cpu": NODE_CPU,
+            "memory": {
+                "used": NODE_MEM,
+                "total": NODE_MAXMEM,
+                "free": NODE_MAXMEM - NODE_MEM,
+            },
+            "uptime": NODE_UPTIME,
+            "kversion": NODE_KERNEL,
+        }),
+    }))
+}
+
+async fn list_vms(
+    Path(node): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<Vec<NodeVm>>>, StatusCode> {
     let state = state.lock().await;
     if node != state.node {
         return Err(StatusCode::NOT_FOUND);
@@ -137,13 +1149,18 @@ async fn list_vms(
     let vms = state
         .vms
         .values()
-        .map(|vm| ResourceVm {
-            vmid: vm.vmid,
-            name: Some(vm.name.clone()),
-            tags: Some(vm.tags.join(";")),
-            status: Some(vm.status.as_str().to_string()),
-            node: Some(state.node.clone()),
-            description: vm.notes.clone(),
+        .map(|vm| {
+            let resources = state.resources.get(&vm.vmid).copied().unwrap_or_default();
+            NodeVm {
+                vmid: vm.vmid,
+                name: vm.name.clone(),
+                tags: vm.tags.join(";"),
+                status: vm.status.as_str().to_string(),
+                maxmem: resources.maxmem,
+                cpus: resources.maxcpu,
+                disk: resources.disk,
+                uptime: resources.uptime,
+            }
         })
         .collect::<Vec<_>>();
     Ok(Json(ApiResponse { data: vms }))
@@ -158,48 +1175,485 @@ async fn current_status(
         return Err(StatusCode::NOT_FOUND);
     }
     let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    // Counted from the last start; VMs inserted as running report their
+    // configured resources uptime instead.
+    let uptime = match vm.status {
+        VmStatus::Running => state
+            .started_at
+            .get(&vmid)
+            .map(|started| unix_now().saturating_sub(*started))
+            .or_else(|| state.resources.get(&vmid).and_then(|res| res.uptime))
+            .unwrap_or(0),
+        _ => 0,
+    };
     Ok(Json(ApiResponse {
         data: StatusPayload {
             status: vm.status.as_str().to_string(),
+            uptime,
         },
     }))
 }
 
 async fn start_vm(
+    path: Path<(String, u64)>,
+    state: State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    power_on(path, state, "qmstart").await
+}
+
+async fn resume_vm(
+    path: Path<(String, u64)>,
+    state: State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    power_on(path, state, "qmresume").await
+}
+
+async fn reboot_vm(
+    path: Path<(String, u64)>,
+    state: State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    power_on(path, state, "qmreboot").await
+}
+
+async fn reset_vm(
+    path: Path<(String, u64)>,
+    state: State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    power_on(path, state, "qmreset").await
+}
+
+/// Leaves `vmid` running, recording the action as a `kind` task.
+async fn power_on(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    kind: &str,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let shared = state.clone();
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if state.fail_starts {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.started_at.insert(vmid, unix_now());
+    let data = transition_power(&shared, &mut state, vmid, kind, VmStatus::Running);
+    Ok(Json(ApiResponse { data }))
+}
+
+async fn shutdown_vm(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let shared = state.clone();
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if state.ignore_shutdown {
+        return Ok(Json(ApiResponse {
+            data: serde_json::Value::Null,
+        }));
+    }
+    let data = transition_power(&shared, &mut state, vmid, "qmshutdown", VmStatus::Stopped);
+    Ok(Json(ApiResponse { data }))
+}
+
+/// Records a `kind` task and moves `vmid` to `status`, right away or once the
+/// VM's power transition delay has passed. Returns the task's UPID.
+fn transition_power(
+    shared: &Arc<Mutex<DummyState>>,
+    state: &mut DummyState,
+    vmid: u64,
+    kind: &str,
+    status: VmStatus,
+) -> serde_json::Value {
+    let task = state.record_task(kind, vmid);
+    let Some(delay) = state.power_delays.get(&vmid).copied() else {
+        state.set_vm_status(vmid, status);
+        return serde_json::Value::String(task.upid);
+    };
+    let shared = shared.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        shared.lock().await.set_vm_status(vmid, status);
+    });
+    serde_json::Value::String(task.upid)
+}
+
+async fn stop_vm(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.set_vm_status(vmid, VmStatus::Stopped);
+    let task = state.record_task("qmstop", vmid);
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::String(task.upid),
+    }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SuspendForm {
+    todisk: Option<u8>,
+}
+
+/// Like PVE, pauses the VM in RAM as a `qmpause` task, or with `todisk=1`
+/// hibernates it as a `qmsuspend` task, leaving it stopped.
+async fn suspend_vm(
     Path((node, vmid)): Path<(String, u64)>,
     State(state): State<Arc<Mutex<DummyState>>>,
+    form: Option<Form<SuspendForm>>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
     let mut state = state.lock().await;
     if node != state.node {
         return Err(StatusCode::NOT_FOUND);
     }
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let Form(form) = form.unwrap_or_default();
+    let (kind, status) = if form.todisk == Some(1) {
+        ("qmsuspend", VmStatus::Stopped)
+    } else {
+        ("qmpause", VmStatus::Paused)
+    };
+    state.set_vm_status(vmid, status);
+    let task = state.record_task(kind, vmid);
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::String(task.upid),
+    }))
+}
+
+async fn vm_config(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    let hardware = state.hardware.get(&vmid).copied().unwrap_or_default();
+    let mut config = serde_json::json!({
+        "name": vm.name,
+        "tags": vm.tags.join(";"),
+        "sockets": hardware.sockets,
+        "cores": hardware.cores,
+        "memory": hardware.memory.to_string(),
+    });
+    if let Some(notes) = &vm.notes {
+        config["description"] = serde_json::Value::String(notes.clone());
+    }
+    let cloudinit = state.cloudinit.get(&vmid).into_iter().flatten();
+    let disks = state.disks.get(&vmid).into_iter().flatten();
+    for (key, value) in cloudinit.chain(disks) {
+        config[key] = serde_json::Value::String(value.clone());
+    }
+    Ok(Json(ApiResponse { data: config }))
+}
+
+/// Config keys stored verbatim and echoed back by `vm_config`.
+fn is_cloudinit_key(key: &str) -> bool {
+    matches!(key, "ide2" | "ciuser" | "cipassword" | "sshkeys")
+        || key
+            .strip_prefix("ipconfig")
+            .is_some_and(|index| index.parse::<u8>().is_ok_and(|index| index <= 31))
+}
+
+/// `scsi0` to `scsi30`, stored verbatim and echoed back by `vm_config`.
+fn is_disk_key(key: &str) -> bool {
+    key.strip_prefix("scsi")
+        .is_some_and(|index| index.parse::<u8>().is_ok_and(|index| index <= 30))
+}
+
+async fn update_config(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    RawForm(form): RawForm,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let update: ConfigUpdate =
+        serde_urlencoded::from_bytes(&form).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    let fields: Vec<(String, String)> =
+        serde_urlencoded::from_bytes(&form).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    for (key, value) in fields {
+        if is_cloudinit_key(&key) {
+            state.cloudinit.entry(vmid).or_default().insert(key, value);
+        } else if is_disk_key(&key) {
+            state.disks.entry(vmid).or_default().insert(key, value);
+        }
+    }
     let vm = state.vms.get_mut(&vmid).ok_or(StatusCode::NOT_FOUND)?;
-    vm.status = VmStatus::Running;
+    if let Some(tags) = update.tags {
+        vm.tags = tags
+            .split([';', ','])
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    if let Some(description) = update.description {
+        vm.notes = Some(description).filter(|notes| !notes.is_empty());
+    }
+    if update.sockets.is_some() || update.cores.is_some() || update.memory.is_some() {
+        let hardware = state.hardware.entry(vmid).or_default();
+        hardware.sockets = update.sockets.unwrap_or(hardware.sockets);
+        hardware.cores = update.cores.unwrap_or(hardware.cores);
+        hardware.memory = update.memory.unwrap_or(hardware.memory);
+    }
     Ok(Json(ApiResponse {
         data: serde_json::Value::Null,
     }))
 }
 
-async fn shutdown_vm(
+#[derive(Debug, Deserialize)]
+struct ResizeForm {
+    disk: String,
+    size: String,
+}
+
+async fn resize_disk(
     Path((node, vmid)): Path<(String, u64)>,
     State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<ResizeForm>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
     let mut state = state.lock().await;
     if node != state.node {
         return Err(StatusCode::NOT_FOUND);
     }
-    let vm = state.vms.get_mut(&vmid).ok_or(StatusCode::NOT_FOUND)?;
-    vm.status = VmStatus::Stopped;
+    if !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.disk_resizes.push(DiskResizeRecord {
+        vmid,
+        disk: form.disk,
+        size: form.size,
+    });
     Ok(Json(ApiResponse {
         data: serde_json::Value::Null,
     }))
 }
 
-async fn stop_vm(
+#[derive(Debug, Deserialize)]
+struct SendKeyForm {
+    key: String,
+}
+
+/// Like QEMU, only running VMs accept key presses.
+async fn send_key(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<SendKeyForm>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    if vm.status != VmStatus::Running {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    state.sent_keys.push(SentKeyRecord {
+        vmid,
+        key: form.key,
+    });
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Null,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct FirewallRuleData {
+    pos: usize,
+    #[serde(flatten)]
+    rule: FirewallRuleEntry,
+}
+
+async fn list_firewall_rules(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<Vec<FirewallRuleData>>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node || !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let rules = state
+        .firewall_rules
+        .get(&vmid)
+        .into_iter()
+        .flatten()
+        .cloned()
+        .enumerate()
+        .map(|(pos, rule)| FirewallRuleData { pos, rule })
+        .collect();
+    Ok(Json(ApiResponse { data: rules }))
+}
+
+#[derive(Debug, Deserialize)]
+struct FirewallRuleForm {
+    enable: Option<u8>,
+}
+
+/// Only `enable` can be changed; Proxmox rejects positions past the end.
+async fn update_firewall_rule(
+    Path((node, vmid, pos)): Path<(String, u64, usize)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<FirewallRuleForm>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node || !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let rule = state
+        .firewall_rules
+        .get_mut(&vmid)
+        .and_then(|rules| rules.get_mut(pos))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if let Some(enable) = form.enable {
+        rule.enable = u8::from(enable != 0);
+    }
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Null,
+    }))
+}
+
+async fn list_snapshots(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<Vec<SnapshotEntry>>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node || !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let mut snapshots = state.snapshots.get(&vmid).cloned().unwrap_or_default();
+    let parent = snapshots.last().map(|snapshot| snapshot.name.clone());
+    snapshots.push(SnapshotEntry {
+        name: "current".to_string(),
+        description: Some("You are here!".to_string()),
+        snaptime: None,
+        parent,
+    });
+    Ok(Json(ApiResponse { data: snapshots }))
+}
+
+async fn create_snapshot(
     Path((node, vmid)): Path<(String, u64)>,
     State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<SnapshotForm>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
-    shutdown_vm(Path((node, vmid)), State(state)).await
+    let mut state = state.lock().await;
+    if node != state.node || !state.vms.contains_key(&vmid) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let snapshots = state.snapshots.entry(vmid).or_default();
+    if snapshots
+        .iter()
+        .any(|snapshot| snapshot.name == form.snapname)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    let parent = snapshots.last().map(|snapshot| snapshot.name.clone());
+    snapshots.push(SnapshotEntry {
+        name: form.snapname,
+        description: form.description,
+        snaptime: Some(unix_now()),
+        parent,
+    });
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Null,
+    }))
+}
+
+async fn delete_snapshot(
+    Path((node, vmid, snapname)): Path<(String, u64, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let snapshots = state
+        .snapshots
+        .get_mut(&vmid)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let index = snapshots
+        .iter()
+        .position(|snapshot| snapshot.name == snapname)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    snapshots.remove(index);
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Null,
+    }))
+}
+
+/// Records a `qmrollback` task; VM state and status are left as they are.
+async fn rollback_snapshot(
+    Path((node, vmid, snapname)): Path<(String, u64, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<String>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let exists = state
+        .snapshots
+        .get(&vmid)
+        .is_some_and(|snapshots| snapshots.iter().any(|snapshot| snapshot.name == snapname));
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let task = state.record_task("qmrollback", vmid);
+    Ok(Json(ApiResponse { data: task.upid }))
+}
+
+async fn list_storages(
+    Path(node): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<Vec<StorageEntry>>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(ApiResponse {
+        data: state.storages.clone(),
+    }))
+}
+
+async fn storage_status(
+    Path((node, storage)): Path<(String, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<StorageEntry>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let entry = state
+        .storages
+        .iter()
+        .find(|entry| entry.storage == storage)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse {
+        data: entry.clone(),
+    }))
 }
 
 async fn list_cluster_resources(
@@ -216,27 +1670,716 @@ async fn list_cluster_resources(
         .vms
         .values()
         .filter(|vm| query.vmid.map(|id| vm.vmid == id).unwrap_or(true))
-        .map(|vm| ResourceVm {
-            vmid: vm.vmid,
-            name: Some(vm.name.clone()),
-            tags: Some(vm.tags.join(";")),
-            status: Some(vm.status.as_str().to_string()),
-            node: Some(state.node.clone()),
-            description: vm.notes.clone(),
-        })
+        .map(|vm| state.resource_vm(vm))
         .collect::<Vec<_>>();
     Ok(Json(ApiResponse { data: vms }))
 }
 
+async fn clone_vm(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<CloneForm>,
+) -> Result<Json<ApiResponse<String>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let source = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    if state.vms.contains_key(&form.newid) {
+        return Err(StatusCode::CONFLICT);
+    }
+    if let Some(snapname) = form.snapname.as_deref() {
+        let exists = state
+            .snapshots
+            .get(&vmid)
+            .is_some_and(|snapshots| snapshots.iter().any(|entry| entry.name == snapname));
+        if !exists {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    // Mirror Proxmox's validation: storage needs a full clone and must exist,
+    // and this single-node dummy can only clone onto itself.
+    if let Some(storage) = form.storage.as_deref() {
+        let known = state.storages.iter().any(|entry| entry.storage == storage);
+        if form.full == Some(0) || !known {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    if form
+        .target
+        .as_deref()
+        .is_some_and(|target| target != state.node)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let clone = VmEntry {
+        vmid: form.newid,
+        name: form
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Copy-of-VM-{}", source.name)),
+        tags: source.tags.clone(),
+        status: VmStatus::Stopped,
+        notes: source.notes.clone(),
+    };
+    if let Some(pool) = form
+        .pool
+        .as_deref()
+        .and_then(|pool| state.pools.get_mut(pool))
+    {
+        pool.members.push(form.newid);
+    }
+    state.clones.push(CloneRecord {
+        source_vmid: vmid,
+        newid: form.newid,
+        name: form.name,
+        full: form.full,
+        snapname: form.snapname,
+        storage: form.storage,
+        target: form.target,
+        pool: form.pool,
+    });
+    let task = state.record_task("qmclone", vmid);
+    if !state.drop_clones && task.exit_status == "OK" {
+        state.vms.insert(clone.vmid, clone);
+    }
+    Ok(Json(ApiResponse { data: task.upid }))
+}
+
+async fn list_pools(
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Json<ApiResponse<Vec<serde_json::Value>>> {
+    let state = state.lock().await;
+    let pools = state
+        .pools
+        .iter()
+        .map(|(poolid, pool)| {
+            let mut entry = serde_json::json!({ "poolid": poolid });
+            if let Some(comment) = &pool.comment {
+                entry["comment"] = serde_json::Value::String(comment.clone());
+            }
+            entry
+        })
+        .collect();
+    Json(ApiResponse { data: pools })
+}
+
+async fn get_pool(
+    Path(poolid): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    let pool = state.pools.get(&poolid).ok_or(StatusCode::NOT_FOUND)?;
+    let members: Vec<serde_json::Value> = pool
+        .members
+        .iter()
+        .map(|vmid| {
+            serde_json::json!({
+                "id": format!("qemu/{vmid}"),
+                "type": "qemu",
+                "vmid": vmid,
+                "node": state.node,
+            })
+        })
+        .collect();
+    Ok(Json(ApiResponse {
+        data: serde_json::json!({ "comment": pool.comment, "members": members }),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolUpdateForm {
+    vms: Option<String>,
+}
+
+/// Adds the comma-separated `vms` to the pool, like Proxmox without `delete=1`.
+async fn update_pool(
+    Path(poolid): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<PoolUpdateForm>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let mut state = state.lock().await;
+    let vmids = form
+        .vms
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|vmid| !vmid.is_empty())
+        .map(|vmid| vmid.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    if vmids.iter().any(|vmid| !state.vms.contains_key(vmid)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let pool = state.pools.get_mut(&poolid).ok_or(StatusCode::NOT_FOUND)?;
+    for vmid in vmids {
+        if !pool.members.contains(&vmid) {
+            pool.members.push(vmid);
+        }
+    }
+    Ok(Json(ApiResponse { data: () }))
+}
+
+/// Like Proxmox, refuses to destroy a running VM.
+async fn destroy_vm(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<String>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    if vm.status != VmStatus::Stopped {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let task = state.record_task("qmdestroy", vmid);
+    if task.exit_status == "OK" {
+        state.vms.remove(&vmid);
+        state.resources.remove(&vmid);
+        state.snapshots.remove(&vmid);
+    }
+    Ok(Json(ApiResponse { data: task.upid }))
+}
+
+/// Like Proxmox, the guest agent is only reachable while the VM runs. The
+/// form repeats `command` once for the program and once per argument.
+async fn agent_exec(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    if vm.status != VmStatus::Running {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let command: Vec<String> = form
+        .into_iter()
+        .filter(|(key, _)| key == "command")
+        .map(|(_, value)| value)
+        .collect();
+    if command.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let pid = state.agent_execs.len() as u64 + 1;
+    state
+        .agent_execs
+        .push(AgentExecRecord { pid, vmid, command });
+    Ok(Json(ApiResponse {
+        data: serde_json::json!({ "pid": pid }),
+    }))
+}
+
+/// Issues a fixed-format ticket; like Proxmox, only running VMs have a console.
+async fn vnc_proxy(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    if vm.status != VmStatus::Running {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let task = state.record_task("vncproxy", vmid);
+    Ok(Json(ApiResponse {
+        data: serde_json::json!({
+            "cert": "-----BEGIN CERTIFICATE-----\ndummy\n-----END CERTIFICATE-----\n",
+            "port": "5900",
+            "ticket": format!("PVEVNC:{:08X}::dummy+ticket/{vmid}=", task.starttime),
+            "upid": task.upid,
+            "user": "root@pam",
+        }),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct AgentExecStatusQuery {
+    pid: u64,
+}
+
+/// Dummy commands finish immediately.
+async fn agent_exec_status(
+    Path((node, vmid)): Path<(String, u64)>,
+    Query(query): Query<AgentExecStatusQuery>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let exec = state
+        .agent_execs
+        .iter()
+        .find(|exec| exec.pid == query.pid && exec.vmid == vmid)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let data = match exec.command.split_first() {
+        Some((program, args)) if program == "echo" => serde_json::json!({
+            "exited": 1,
+            "exitcode": 0,
+            "out-data": format!("{}\n", args.join(" ")),
+        }),
+        _ => serde_json::json!({
+            "exited": 1,
+            "exitcode": 127,
+            "err-data": format!("{}: command not found\n", exec.command[0]),
+        }),
+    };
+    Ok(Json(ApiResponse { data }))
+}
+
+#[derive(Debug, Deserialize)]
+struct VzdumpForm {
+    vmid: u64,
+    storage: String,
+    mode: Option<String>,
+    compress: Option<String>,
+    #[serde(rename = "notes-template")]
+    notes_template: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DownloadUrlForm {
+    content: String,
+    filename: String,
+    url: String,
+}
+
+async fn download_url(
+    Path((node, storage)): Path<(String, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<DownloadUrlForm>,
+) -> Result<Json<ApiResponse<String>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !state.storages.iter().any(|entry| entry.storage == storage)
+        || !["iso", "vztmpl", "import"].contains(&form.content.as_str())
+        || form.filename.is_empty()
+        || form.filename.contains('/')
+        || !(form.url.starts_with("http://") || form.url.starts_with("https://"))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let task = state.record_task("download", 0);
+    if task.exit_status == "OK" {
+        state.downloads.push(DownloadRecord {
+            storage,
+            content: form.content,
+            filename: form.filename,
+            url: form.url,
+        });
+    }
+    Ok(Json(ApiResponse { data: task.upid }))
+}
+
+async fn vzdump(
+    Path(node): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Form(form): Form<VzdumpForm>,
+) -> Result<Json<ApiResponse<String>>, StatusCode> {
+    let mut state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !state.vms.contains_key(&form.vmid)
+        || !state
+            .storages
+            .iter()
+            .any(|entry| entry.storage == form.storage)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if form
+        .mode
+        .as_deref()
+        .is_some_and(|mode| !["snapshot", "suspend", "stop"].contains(&mode))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let task = state.record_task("vzdump", form.vmid);
+    if task.exit_status == "OK" {
+        let extension = match form.compress.as_deref() {
+            Some("lzo") => ".lzo",
+            Some("gzip") => ".gz",
+            Some("zstd") => ".zst",
+            _ => "",
+        };
+        let ctime = task.starttime;
+        state.backups.push(BackupEntry {
+            volid: format!(
+                "{}:backup/vzdump-qemu-{}-{ctime}.vma{extension}",
+                form.storage, form.vmid
+            ),
+            storage: form.storage,
+            vmid: form.vmid,
+            size: 1024 * 1024,
+            ctime,
+            format: format!("vma{extension}"),
+            notes: form.notes_template,
+        });
+    }
+    Ok(Json(ApiResponse { data: task.upid }))
+}
+
+#[derive(Debug, Deserialize)]
+struct StorageContentQuery {
+    content: Option<String>,
+    vmid: Option<u64>,
+}
+
+async fn storage_content(
+    Path((node, storage)): Path<(String, String)>,
+    Query(query): Query<StorageContentQuery>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node || !state.storages.iter().any(|entry| entry.storage == storage) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if query
+        .content
+        .as_deref()
+        .is_some_and(|content| content != "backup")
+    {
+        return Ok(Json(ApiResponse {
+            data: serde_json::json!([]),
+        }));
+    }
+    let backups: Vec<_> = state
+        .backups
+        .iter()
+        .filter(|backup| backup.storage == storage)
+        .filter(|backup| query.vmid.is_none_or(|vmid| backup.vmid == vmid))
+        .map(|backup| {
+            serde_json::json!({
+                "volid": backup.volid,
+                "vmid": backup.vmid,
+                "size": backup.size,
+                "ctime": backup.ctime,
+                "format": backup.format,
+                "notes": backup.notes,
+                "content": "backup",
+            })
+        })
+        .collect();
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Array(backups),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct RrdQuery {
+    timeframe: String,
+}
+
+/// Number of samples returned per timeframe, close to what Proxmox serves.
+const RRD_POINTS: u64 = 70;
+
+/// Synthetic averaged metrics, oldest first. Stopped VMs and the newest
+/// (still-open) sample carry only a timestamp, as on a real node.
+async fn vm_rrddata(
+    Path((node, vmid)): Path<(String, u64)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Query(query): Query<RrdQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let vm = state.vms.get(&vmid).ok_or(StatusCode::NOT_FOUND)?;
+    let step = match query.timeframe.as_str() {
+        "hour" => 60,
+        "day" => 1800,
+        "week" => 10800,
+        "month" => 43200,
+        "year" => 604800,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let hardware = state.hardware.get(&vmid).copied().unwrap_or_default();
+    let maxmem = hardware.memory * 1024 * 1024;
+    let latest = unix_now() / step * step;
+    let points: Vec<serde_json::Value> = (0..RRD_POINTS)
+        .map(|index| {
+            let time = latest - (RRD_POINTS - 1 - index) * step;
+            if vm.status != VmStatus::Running || index == RRD_POINTS - 1 {
+                return serde_json::json!({ "time": time });
+            }
+            let wave = (index % 10) as f64 / 10.0;
+            serde_json::json!({
+                "time": time,
+                "cpu": 0.05 + wave * 0.2,
+                "maxcpu": hardware.sockets * hardware.cores,
+                "mem": maxmem as f64 * (0.3 + wave * 0.4),
+                "maxmem": maxmem,
+                "netin": 1000.0 + wave * 4000.0,
+                "netout": 500.0 + wave * 2000.0,
+            })
+        })
+        .collect();
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Array(points),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskListQuery {
+    vmid: Option<u64>,
+    limit: Option<usize>,
+    /// Unix time; later tasks are left out.
+    until: Option<u64>,
+}
+
+/// Newest first, like Proxmox.
+async fn list_tasks(
+    Path(node): Path<String>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Query(query): Query<TaskListQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let tasks: Vec<serde_json::Value> = state
+        .tasks
+        .iter()
+        .rev()
+        .filter(|task| query.vmid.is_none_or(|vmid| task.vmid == vmid))
+        .filter(|task| query.until.is_none_or(|until| task.starttime <= until))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|task| {
+            serde_json::json!({
+                "upid": task.upid,
+                "node": node,
+                "type": task.kind,
+                "id": task.vmid.to_string(),
+                "user": "root@pam",
+                "starttime": task.starttime,
+                "endtime": task.starttime,
+                "status": task.exit_status,
+            })
+        })
+        .collect();
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Array(tasks),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskLogQuery {
+    limit: Option<usize>,
+}
+
+async fn task_status(
+    Path((node, upid)): Path<(String, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let task = state
+        .tasks
+        .iter()
+        .find(|task| task.upid == upid)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse {
+        data: serde_json::json!({
+            "upid": task.upid,
+            "node": node,
+            "type": task.kind,
+            "id": task.vmid.to_string(),
+            "user": "root@pam",
+            "starttime": task.starttime,
+            "status": "stopped",
+            "exitstatus": task.exit_status,
+        }),
+    }))
+}
+
+async fn task_log(
+    Path((node, upid)): Path<(String, String)>,
+    State(state): State<Arc<Mutex<DummyState>>>,
+    Query(query): Query<TaskLogQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
+    let state = state.lock().await;
+    if node != state.node {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let task = state
+        .tasks
+        .iter()
+        .find(|task| task.upid == upid)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let lines: Vec<serde_json::Value> = task
+        .log
+        .iter()
+        .take(query.limit.unwrap_or(usize::MAX))
+        .enumerate()
+        .map(|(index, line)| serde_json::json!({ "n": index + 1, "t": line }))
+        .collect();
+    Ok(Json(ApiResponse {
+        data: serde_json::Value::Array(lines),
+    }))
+}
+
+async fn next_vmid(State(state): State<Arc<Mutex<DummyState>>>) -> Json<ApiResponse<String>> {
+    let state = state.lock().await;
+    let next = state.vms.keys().max().map_or(100, |vmid| vmid + 1);
+    Json(ApiResponse {
+        data: next.to_string(),
+    })
+}
+
+async fn list_acl(State(state): State<Arc<Mutex<DummyState>>>) -> Json<ApiResponse<Vec<AclEntry>>> {
+    let state = state.lock().await;
+    Json(ApiResponse {
+        data: state.acl.clone(),
+    })
+}
+
+async fn permissions(
+    State(state): State<Arc<Mutex<DummyState>>>,
+) -> Json<ApiResponse<BTreeMap<String, BTreeMap<String, u8>>>> {
+    let state = state.lock().await;
+    let granted = |privileges: &[String]| {
+        privileges
+            .iter()
+            .map(|privilege| (privilege.clone(), 1))
+            .collect()
+    };
+    let data = match &state.permissions {
+        Some(permissions) => permissions
+            .iter()
+            .map(|(path, privileges)| (path.clone(), granted(privileges)))
+            .collect(),
+        None => {
+            let root: Vec<String> = ROOT_PRIVILEGES.iter().map(|p| p.to_string()).collect();
+            BTreeMap::from([("/".to_string(), granted(&root))])
+        }
+    };
+    Json(ApiResponse { data })
+}
+
+async fn version() -> Json<ApiResponse<serde_json::Value>> {
+    Json(ApiResponse {
+        data: serde_json::json!({
+            "release": "8.2",
+            "version": "8.2.4",
+            "repoid": "faa83925c9641325",
+        }),
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 pub async fn spawn_dummy_server(
     handle: DummyHandle,
 ) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), std::io::Error> {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
     let addr = listener.local_addr()?;
+    let server = handle.serve(listener);
     let join_handle = tokio::spawn(async move {
-        if let Err(err) = handle.serve(listener).await {
+        if let Err(err) = server.await {
             tracing::error!("dummy server failed: {err}");
         }
     });
     Ok((addr, join_handle))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn state_round_trips_through_file() {
+        let handle = DummyHandle::new("pve");
+        for (vmid, name) in [(100, "alpha"), (101, "beta")] {
+            handle
+                .insert_vm(VmEntry::builder(vmid, name).tags(&["dev"]).build())
+                .await;
+        }
+        handle.fail_next_task("clone failed").await;
+
+        let path = std::env::temp_dir().join(format!(
+            "proxmox-dummy-state-{}-{}.json",
+            std::process::id(),
+            unix_now()
+        ));
+        handle.save_to_file(&path).await.unwrap();
+        let loaded = DummyHandle::load_from_file(&path).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let vm_names = |state: &DummyState| {
+            let mut names: Vec<_> = state
+                .vms
+                .values()
+                .map(|vm| (vm.vmid, vm.name.clone()))
+                .collect();
+            names.sort();
+            names
+        };
+        let original = handle.state.lock().await;
+        let restored = loaded.state.lock().await;
+        assert_eq!(vm_names(&original), vm_names(&restored));
+        assert_eq!(restored.node, "pve");
+        assert_eq!(restored.next_task_failure.as_deref(), Some("clone failed"));
+    }
+
+    #[tokio::test]
+    async fn suspend_pauses_in_ram_or_hibernates_to_disk() {
+        let handle = DummyHandle::new("pve");
+        for vmid in [100, 101] {
+            handle
+                .insert_vm(
+                    VmEntry::builder(vmid, "guest")
+                        .status(VmStatus::Running)
+                        .build(),
+                )
+                .await;
+        }
+        let suspend = |vmid: u64, todisk: Option<u8>| {
+            suspend_vm(
+                Path(("pve".to_string(), vmid)),
+                State(handle.state.clone()),
+                todisk.map(|todisk| {
+                    Form(SuspendForm {
+                        todisk: Some(todisk),
+                    })
+                }),
+            )
+        };
+        let Json(paused) = suspend(100, None).await.unwrap();
+        let Json(hibernated) = suspend(101, Some(1)).await.unwrap();
+
+        assert_eq!(handle.status(100).await, Some(VmStatus::Paused));
+        assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+        let tasks = handle.tasks().await;
+        assert_eq!(paused.data, tasks[0].upid);
+        assert_eq!(hibernated.data, tasks[1].upid);
+        let kinds: Vec<(u64, &str)> = tasks
+            .iter()
+            .map(|task| (task.vmid, task.kind.as_str()))
+            .collect();
+        assert_eq!(kinds, [(100, "qmpause"), (101, "qmsuspend")]);
+    }
+}
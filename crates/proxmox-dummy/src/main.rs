@@ -1,10 +1,15 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tracing::info;
 
 use proxmox_dummy::DummyHandle;
 
+/// Bearer secret guarding `/admin/state`, read by the server and the `state`
+/// commands alike.
+const ADMIN_SECRET_ENV: &str = "DUMMY_ADMIN_SECRET";
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
@@ -14,6 +19,31 @@ struct Args {
     port: u16,
     #[arg(long, default_value = "pve")]
     node: String,
+    /// Load the initial state from this file if it exists.
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Checkpoint or restore the state of a running dummy server.
+    State {
+        /// Base URL of the running server, e.g. `http://127.0.0.1:8006`.
+        #[arg(long)]
+        url: String,
+        #[command(subcommand)]
+        action: StateAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StateAction {
+    /// Write the server's full state to a JSON file.
+    Dump { file: PathBuf },
+    /// Replace the server's state with a JSON file written by `dump`.
+    Load { file: PathBuf },
 }
 
 #[tokio::main]
@@ -23,11 +53,65 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init();
 
     let args = Args::parse();
-    let handle = DummyHandle::new(args.node);
+    if let Some(Command::State { url, action }) = args.command {
+        return run_state_command(&url, action).await;
+    }
+
+    let handle = match &args.state_file {
+        Some(path) if path.exists() => {
+            info!("Loading dummy state from {}", path.display());
+            DummyHandle::load_from_file(path).await?
+        }
+        _ => DummyHandle::new(args.node),
+    };
+    match std::env::var(ADMIN_SECRET_ENV) {
+        Ok(secret) if !secret.is_empty() => handle.set_admin_secret(secret).await,
+        _ => info!("{ADMIN_SECRET_ENV} not set; /admin/state is disabled"),
+    }
     let addr: SocketAddr = format!("{}:{}", args.bind, args.port).parse()?;
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    let actual_addr = listener.local_addr()?;
-    info!("Dummy Proxmox server listening on {actual_addr}");
-    handle.serve(listener).await?;
+    let server = handle.clone().serve(listener);
+    let port = handle.port().ok_or("listener has no port")?;
+    info!("Dummy Proxmox server listening on {}:{port}", args.bind);
+    // Printed even without RUST_LOG so scripts can find a random port.
+    println!("Listening on http://{}:{port}", args.bind);
+    server.await?;
+    Ok(())
+}
+
+async fn run_state_command(
+    url: &str,
+    action: StateAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let secret = std::env::var(ADMIN_SECRET_ENV)
+        .map_err(|_| format!("{ADMIN_SECRET_ENV} must be set for state commands"))?;
+    let endpoint = format!("{}/admin/state", url.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    match action {
+        StateAction::Dump { file } => {
+            let state = client
+                .get(&endpoint)
+                .bearer_auth(&secret)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+            tokio::fs::write(&file, state).await?;
+            info!("Dumped dummy state to {}", file.display());
+        }
+        StateAction::Load { file } => {
+            let state = tokio::fs::read(&file).await?;
+            client
+                .post(&endpoint)
+                .bearer_auth(&secret)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(state)
+                .send()
+                .await?
+                .error_for_status()?;
+            info!("Loaded dummy state from {}", file.display());
+        }
+    }
     Ok(())
 }
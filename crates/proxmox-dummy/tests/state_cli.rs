@@ -0,0 +1,121 @@
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use proxmox_dummy::{DummyHandle, VmEntry};
+
+const SECRET: &str = "dummy-secret";
+
+/// Running dummy binary, killed on drop.
+struct DummyProcess {
+    child: Child,
+    url: String,
+}
+
+impl DummyProcess {
+    fn spawn(state_file: Option<&Path>) -> Self {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_proxmox-dummy"));
+        command
+            .args(["--bind", "127.0.0.1", "--port", "0"])
+            .env("DUMMY_ADMIN_SECRET", SECRET)
+            .env_remove("RUST_LOG")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        if let Some(path) = state_file {
+            command.arg("--state-file").arg(path);
+        }
+        let mut child = command.spawn().expect("dummy binary should start");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let url = line
+            .trim()
+            .strip_prefix("Listening on ")
+            .unwrap_or_else(|| panic!("unexpected first line: {line:?}"))
+            .to_string();
+        Self { child, url }
+    }
+
+    fn state(&self, action: &str, file: &Path) -> bool {
+        Command::new(env!("CARGO_BIN_EXE_proxmox-dummy"))
+            .args(["state", "--url", &self.url, action])
+            .arg(file)
+            .env("DUMMY_ADMIN_SECRET", SECRET)
+            .env("NO_PROXY", "127.0.0.1,localhost")
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    async fn vmids(&self) -> Vec<u64> {
+        let body = reqwest::get(format!("{}/api2/json/cluster/resources", self.url))
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        let mut vmids: Vec<u64> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|vm| vm["vmid"].as_u64().unwrap())
+            .collect();
+        vmids.sort();
+        vmids
+    }
+}
+
+impl Drop for DummyProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("proxmox-dummy-{name}-{}.json", std::process::id()))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn state_survives_dump_restart_and_load() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let seed = temp_path("seed");
+    let dump = temp_path("dump");
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(VmEntry::builder(100, "alpha").build())
+        .await;
+    handle
+        .insert_vm(VmEntry::builder(101, "beta").build())
+        .await;
+    handle.save_to_file(&seed).await.unwrap();
+
+    let server = DummyProcess::spawn(Some(&seed));
+    assert_eq!(server.vmids().await, [100, 101]);
+    let response = reqwest::get(format!("{}/admin/state", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert!(server.state("dump", &dump));
+    drop(server);
+
+    let server = DummyProcess::spawn(None);
+    assert!(server.vmids().await.is_empty());
+    assert!(server.state("load", &dump));
+    assert_eq!(server.vmids().await, [100, 101]);
+
+    let _ = std::fs::remove_file(&seed);
+    let _ = std::fs::remove_file(&dump);
+}
+
+#[tokio::test]
+async fn port_is_known_once_serving() {
+    let handle = DummyHandle::new("pve");
+    assert_eq!(handle.port(), None);
+    let (addr, _task) = proxmox_dummy::spawn_dummy_server(handle.clone())
+        .await
+        .unwrap();
+    assert_eq!(handle.port(), Some(addr.port()));
+}
@@ -0,0 +1,200 @@
+use axum::extract::{FromRequestParts, Request};
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// API versions this agent can serve; the first is used when the client
+/// does not ask for one.
+pub const SUPPORTED_API_VERSIONS: &[u32] = &[1];
+
+/// Sent on every `/api` response, whatever the client asked for.
+pub const API_VERSION_HEADER: HeaderName = HeaderName::from_static("x-api-version");
+
+/// Served as plain `application/json` so OpenAPI tooling recognises it.
+const UNLABELLED_PATHS: &[&str] = &["/api/openapi.json"];
+
+const VENDOR_PREFIX: &str = "application/vnd.risky-agent.v";
+const VENDOR_SUFFIX: &str = "+json";
+
+/// API version negotiated from `Accept: application/vnd.risky-agent.v{N}+json`.
+/// Requests without a vendor media type get the default version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiVersion(pub u32);
+
+impl ApiVersion {
+    /// Vendor media type JSON responses of this version are labelled with.
+    pub fn media_type(self) -> String {
+        format!("{VENDOR_PREFIX}{}{VENDOR_SUFFIX}", self.0)
+    }
+}
+
+impl Default for ApiVersion {
+    fn default() -> Self {
+        Self(SUPPORTED_API_VERSIONS[0])
+    }
+}
+
+/// Why an `Accept` header could not be satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiVersionRejection {
+    /// Only vendor media types naming unsupported versions were offered.
+    Unsupported(Vec<u32>),
+    /// A vendor media type without a numeric version, e.g. `v1.2` or `vx`.
+    Malformed(String),
+}
+
+#[derive(Serialize)]
+struct RejectionBody {
+    error: String,
+}
+
+impl IntoResponse for ApiVersionRejection {
+    fn into_response(self) -> Response {
+        let supported: Vec<String> = SUPPORTED_API_VERSIONS.iter().map(u32::to_string).collect();
+        let (status, error) = match self {
+            Self::Unsupported(versions) => {
+                let versions: Vec<String> = versions.iter().map(u32::to_string).collect();
+                (
+                    StatusCode::NOT_ACCEPTABLE,
+                    format!(
+                        "API version {} is not supported; supported versions: {}",
+                        versions.join(", "),
+                        supported.join(", ")
+                    ),
+                )
+            }
+            Self::Malformed(media_type) => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Malformed API version in Accept header: {media_type}; expected {VENDOR_PREFIX}<N>{VENDOR_SUFFIX}"
+                ),
+            ),
+        };
+        (status, Json(RejectionBody { error })).into_response()
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = ApiVersionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok());
+        negotiate(accept)
+    }
+}
+
+/// Picks the first supported version among the vendor media types in
+/// `accept`, ignoring parameters such as `q`. Other media types are skipped.
+fn negotiate<'a>(
+    accept: impl IntoIterator<Item = &'a str>,
+) -> Result<ApiVersion, ApiVersionRejection> {
+    let mut requested = Vec::new();
+    for media_type in accept.into_iter().flat_map(|value| value.split(',')) {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+        if !media_type.starts_with("application/vnd.risky-agent") {
+            continue;
+        }
+        let version = media_type
+            .strip_prefix(VENDOR_PREFIX)
+            .and_then(|rest| rest.strip_suffix(VENDOR_SUFFIX))
+            .filter(|version| {
+                !version.is_empty() && version.bytes().all(|byte| byte.is_ascii_digit())
+            })
+            .and_then(|version| version.parse::<u32>().ok())
+            .ok_or_else(|| ApiVersionRejection::Malformed(media_type.to_string()))?;
+        requested.push(version);
+    }
+    if requested.is_empty() {
+        return Ok(ApiVersion::default());
+    }
+    match requested
+        .iter()
+        .find(|version| SUPPORTED_API_VERSIONS.contains(version))
+    {
+        Some(&version) => Ok(ApiVersion(version)),
+        None => Err(ApiVersionRejection::Unsupported(requested)),
+    }
+}
+
+/// Middleware negotiating the [`ApiVersion`] for `/api` requests. Unsupported
+/// versions are refused before the handler runs, JSON responses are labelled
+/// with the version's media type (except the OpenAPI spec itself), and every
+/// `/api` response carries `X-Api-Version`. Other paths pass through untouched.
+pub async fn negotiate_api_version(request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+    let relabel = !UNLABELLED_PATHS.contains(&request.uri().path());
+    let (mut parts, body) = request.into_parts();
+    let mut response = match ApiVersion::from_request_parts(&mut parts, &()).await {
+        Ok(version) => {
+            parts.extensions.insert(version);
+            let mut response = next.run(Request::from_parts(parts, body)).await;
+            let is_json = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+            if relabel && is_json {
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_str(&version.media_type())
+                        .expect("vendor media type is a valid header value"),
+                );
+            }
+            response
+        }
+        Err(rejection) => rejection.into_response(),
+    };
+    response.headers_mut().insert(
+        API_VERSION_HEADER,
+        HeaderValue::from(ApiVersion::default().0),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_header_selects_a_supported_version() {
+        assert_eq!(negotiate([]), Ok(ApiVersion(1)));
+        assert_eq!(negotiate(["application/json"]), Ok(ApiVersion(1)));
+        assert_eq!(
+            negotiate(["application/vnd.risky-agent.v1+json"]),
+            Ok(ApiVersion(1))
+        );
+        assert_eq!(
+            negotiate([
+                "application/vnd.risky-agent.v2+json, application/vnd.risky-agent.v1+json;q=0.5"
+            ]),
+            Ok(ApiVersion(1))
+        );
+        assert_eq!(
+            negotiate(["text/html", "application/vnd.risky-agent.v2+json"]),
+            Err(ApiVersionRejection::Unsupported(vec![2]))
+        );
+        for malformed in [
+            "application/vnd.risky-agent+json",
+            "application/vnd.risky-agent.v+json",
+            "application/vnd.risky-agent.vx+json",
+            "application/vnd.risky-agent.v+1+json",
+            "application/vnd.risky-agent.v1",
+        ] {
+            assert_eq!(
+                negotiate([malformed]),
+                Err(ApiVersionRejection::Malformed(malformed.to_string())),
+                "{malformed}"
+            );
+        }
+    }
+}
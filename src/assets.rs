@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+const INDEX_HTML: &[u8] = include_bytes!("../assets/index.html");
+const APP_JS: &[u8] = include_bytes!("../assets/app.js");
+const APP_CSS: &[u8] = include_bytes!("../assets/app.css");
+const BACKGROUND_JPG: &[u8] = include_bytes!("../assets/background.jpg");
+
+#[derive(Debug, Clone)]
+pub struct StaticAsset {
+    pub bytes: Arc<[u8]>,
+    pub from_disk: bool,
+}
+
+impl StaticAsset {
+    fn embedded(bytes: &'static [u8]) -> Self {
+        Self {
+            bytes: Arc::from(bytes),
+            from_disk: false,
+        }
+    }
+
+    fn load(dir: Option<&Path>, file_name: &str, embedded: &'static [u8]) -> Self {
+        let Some(dir) = dir else {
+            return Self::embedded(embedded);
+        };
+        let path = dir.join(file_name);
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                info!(path = %path.display(), "Loaded static asset override from disk");
+                Self {
+                    bytes: Arc::from(bytes),
+                    from_disk: true,
+                }
+            }
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "Static asset override unavailable; using embedded copy");
+                Self::embedded(embedded)
+            }
+        }
+    }
+
+    /// Disk overrides are served uncached so operators can edit them live.
+    pub fn cache_control(&self) -> &'static str {
+        if self.from_disk {
+            "no-cache"
+        } else {
+            "max-age=3600"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StaticAssets {
+    pub index_html: StaticAsset,
+    pub app_js: StaticAsset,
+    pub app_css: StaticAsset,
+    pub background_jpg: StaticAsset,
+}
+
+impl StaticAssets {
+    pub fn embedded() -> Self {
+        Self::load(None)
+    }
+
+    pub fn load(dir: Option<&Path>) -> Self {
+        Self {
+            index_html: StaticAsset::load(dir, "index.html", INDEX_HTML),
+            app_js: StaticAsset::load(dir, "app.js", APP_JS),
+            app_css: StaticAsset::load(dir, "app.css", APP_CSS),
+            background_jpg: StaticAsset::load(dir, "background.jpg", BACKGROUND_JPG),
+        }
+    }
+}
+
+impl Default for StaticAssets {
+    fn default() -> Self {
+        Self::embedded()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_directory_falls_back_to_embedded_assets() {
+        let assets = StaticAssets::load(Some(Path::new("/nonexistent/risky-proxmox-assets")));
+        assert!(!assets.index_html.from_disk);
+        assert_eq!(&*assets.index_html.bytes, INDEX_HTML);
+        assert_eq!(&*assets.app_js.bytes, APP_JS);
+        assert_eq!(&*assets.app_css.bytes, APP_CSS);
+        assert_eq!(&*assets.background_jpg.bytes, BACKGROUND_JPG);
+        assert_eq!(assets.index_html.cache_control(), "max-age=3600");
+    }
+
+    #[test]
+    fn files_in_directory_override_embedded_assets() {
+        let dir = std::env::temp_dir().join(format!("risky-assets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), b"<h1>custom</h1>").unwrap();
+
+        let assets = StaticAssets::load(Some(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(assets.index_html.from_disk);
+        assert_eq!(&*assets.index_html.bytes, b"<h1>custom</h1>");
+        assert_eq!(assets.index_html.cache_control(), "no-cache");
+        assert!(!assets.app_js.from_disk);
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use tokio::sync::Semaphore;
+
+use crate::metrics::ActiveConnection;
+use crate::server::ApiError;
+
+/// Paths that are never limited so probes keep working while saturated.
+const EXEMPT_PATHS: &[&str] = &["/health"];
+
+/// Seconds clients are told to wait before retrying a rejected request.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// Caps the number of requests handled at once. Requests beyond the cap are
+/// rejected immediately rather than queued, so slow clients cannot pile up
+/// work behind them.
+///
+/// A slot is released as soon as the handler returns its response headers,
+/// not when the body finishes, so long-lived SSE streams and upgraded
+/// WebSocket connections do not count against the cap.
+pub struct ConnectionLimiter {
+    permits: Semaphore,
+    max_concurrent: usize,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_concurrent: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            max_concurrent,
+        }
+    }
+
+    /// Requests currently holding a slot.
+    pub fn active_connections(&self) -> usize {
+        self.max_concurrent - self.permits.available_permits()
+    }
+}
+
+/// Middleware applying the optional [`ConnectionLimiter`]; passes everything through when `None`.
+pub async fn connection_limit(
+    State(limiter): State<Option<Arc<ConnectionLimiter>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = limiter else {
+        return next.run(request).await;
+    };
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let Ok(_permit) = limiter.permits.try_acquire() else {
+        tracing::warn!(
+            active_connections = limiter.max_concurrent,
+            path = %request.uri().path(),
+            "Concurrent request limit reached"
+        );
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiError {
+                error: "Server busy".to_string(),
+            }),
+        )
+            .into_response();
+        response.headers_mut().insert(
+            axum::http::header::RETRY_AFTER,
+            HeaderValue::from(RETRY_AFTER_SECS),
+        );
+        return response;
+    };
+    let _active = ActiveConnection::track();
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_connections_counts_held_permits() {
+        let limiter = ConnectionLimiter::new(2);
+        assert_eq!(limiter.active_connections(), 0);
+        let first = limiter.permits.try_acquire().unwrap();
+        let _second = limiter.permits.try_acquire().unwrap();
+        assert_eq!(limiter.active_connections(), 2);
+        assert!(limiter.permits.try_acquire().is_err());
+        drop(first);
+        assert_eq!(limiter.active_connections(), 1);
+    }
+}
@@ -1,54 +1,209 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use tokio::sync::Notify;
 use tokio::time::{interval, sleep};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-use crate::proxmox::types::VmStatus;
+use crate::proxmox::types::{VmInfo, VmStatus};
 use crate::proxmox::ProxmoxClient;
 
 const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(30);
 const FALLBACK_RECHECK_DELAY: Duration = Duration::from_secs(10);
 
-pub fn spawn_fallback_task(client: ProxmoxClient, fallback_name: String) {
+#[derive(Debug, Clone)]
+pub struct FallbackHandle {
+    inhibited: Arc<AtomicBool>,
+    /// Fallback VM name; replaced at runtime via `PUT /api/config/fallback`.
+    name: Arc<Mutex<Option<String>>>,
+    tag: Option<String>,
+    /// Wakes the polling task before its next tick.
+    trigger: Arc<Notify>,
+}
+
+impl FallbackHandle {
+    /// Handle for the VM picked by `selector`; [`spawn_fallback_task`] also polls for it.
+    pub fn new(selector: FallbackSelector) -> Self {
+        Self {
+            inhibited: Arc::default(),
+            name: Arc::new(Mutex::new(selector.name)),
+            tag: selector.tag,
+            trigger: Arc::default(),
+        }
+    }
+
+    /// Runs the fallback check now instead of at the next poll, skipping the
+    /// recheck delay. Returns without waiting for the check.
+    pub fn trigger_now(&self) {
+        info!("Fallback VM check triggered manually");
+        self.trigger.notify_one();
+    }
+
+    /// The selector as currently configured; the name may change between calls.
+    pub fn selector(&self) -> FallbackSelector {
+        FallbackSelector {
+            name: self.name(),
+            tag: self.tag.clone(),
+        }
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.name
+            .lock()
+            .expect("fallback name lock poisoned")
+            .clone()
+    }
+
+    /// Takes effect on the next poll. `None` leaves only the tag, if any, to
+    /// select the VM; without a tag the fallback is disabled.
+    pub fn set_name(&self, name: Option<String>) {
+        info!(fallback_vm = ?name, "Fallback VM name changed");
+        *self.name.lock().expect("fallback name lock poisoned") = name;
+    }
+
+    pub fn inhibit(&self) {
+        info!("Fallback VM auto-start inhibited");
+        self.inhibited.store(true, Ordering::SeqCst);
+    }
+
+    pub fn enable(&self) {
+        info!("Fallback VM auto-start enabled");
+        self.inhibited.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_inhibited(&self) -> bool {
+        self.inhibited.load(Ordering::SeqCst)
+    }
+}
+
+/// Which VM the fallback task starts. When both a name and a tag are set, a VM
+/// must match both; ties are broken by the lowest vmid.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FallbackSelector {
+    pub name: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl FallbackSelector {
+    /// `None` when neither a name nor a tag is configured.
+    pub fn new(name: Option<String>, tag: Option<String>) -> Option<Self> {
+        (name.is_some() || tag.is_some()).then_some(Self { name, tag })
+    }
+
+    pub fn matches(&self, vm: &VmInfo) -> bool {
+        self.name.as_ref().is_none_or(|name| &vm.name == name)
+            && self.tag.as_ref().is_none_or(|tag| {
+                vm.tags
+                    .iter()
+                    .any(|vm_tag| vm_tag.eq_ignore_ascii_case(tag))
+            })
+    }
+
+    /// `None` when no VM matches or no criteria are set.
+    pub fn select<'a>(&self, vms: &'a [VmInfo]) -> Option<&'a VmInfo> {
+        if !self.is_configured() {
+            return None;
+        }
+        vms.iter()
+            .filter(|vm| self.matches(vm))
+            .min_by_key(|vm| vm.vmid)
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.name.is_some() || self.tag.is_some()
+    }
+
+    fn reason(&self) -> String {
+        match (&self.name, &self.tag) {
+            (Some(name), Some(tag)) => format!("name '{name}' and tag '{tag}'"),
+            (Some(name), None) => format!("name '{name}'"),
+            (None, Some(tag)) => format!("tag '{tag}'"),
+            (None, None) => "no criteria".to_string(),
+        }
+    }
+}
+
+pub fn spawn_fallback_task(client: ProxmoxClient, selector: FallbackSelector) -> FallbackHandle {
+    let handle = FallbackHandle::new(selector);
+    let task_handle = handle.clone();
     tokio::spawn(async move {
-        info!("Fallback VM polling enabled for '{}'", fallback_name);
+        let selector = task_handle.selector();
+        if selector.is_configured() {
+            info!("Fallback VM polling enabled for {}", selector.reason());
+        } else {
+            info!("Fallback VM polling idle until a fallback VM is configured");
+        }
         let mut ticker = interval(FALLBACK_POLL_INTERVAL);
         loop {
-            ticker.tick().await;
-            if let Err(err) = poll_and_start(&client, &fallback_name).await {
+            let triggered = tokio::select! {
+                _ = ticker.tick() => false,
+                _ = task_handle.trigger.notified() => true,
+            };
+            if task_handle.is_inhibited() {
+                debug!("Fallback VM poll skipped while inhibited");
+                continue;
+            }
+            if !task_handle.selector().is_configured() {
+                debug!("Fallback VM poll skipped; no fallback VM configured");
+                continue;
+            }
+            if let Err(err) = poll_and_start(&client, &task_handle, triggered).await {
                 warn!("Fallback VM poll failed: {err}");
             }
         }
     });
+    handle
 }
 
 async fn poll_and_start(
     client: &ProxmoxClient,
-    fallback_name: &str,
+    handle: &FallbackHandle,
+    triggered: bool,
 ) -> Result<(), crate::proxmox::error::ProxmoxError> {
-    let vms = client.list_vms().await?;
+    let mut vms = client.list_vms().await?;
     if vms.iter().any(|vm| vm.status == VmStatus::Running) {
         return Ok(());
     }
 
-    sleep(FALLBACK_RECHECK_DELAY).await;
+    // A manual trigger skips the recheck, also when it arrives during the wait.
+    if !triggered {
+        tokio::select! {
+            _ = sleep(FALLBACK_RECHECK_DELAY) => {}
+            _ = handle.trigger.notified() => {
+                debug!("Fallback VM recheck delay cut short by manual trigger");
+            }
+        }
 
-    let vms = client.list_vms().await?;
-    if vms.iter().any(|vm| vm.status == VmStatus::Running) {
+        vms = client.list_vms_fresh().await?;
+        if vms.iter().any(|vm| vm.status == VmStatus::Running) {
+            return Ok(());
+        }
+    }
+
+    if handle.is_inhibited() {
+        debug!("Fallback VM inhibited during recheck; skipping auto-start");
         return Ok(());
     }
 
-    let fallback_vm = vms.iter().find(|vm| vm.name == fallback_name);
-    if let Some(vm) = fallback_vm {
+    // Read after the recheck so a name changed meanwhile is honoured.
+    let selector = handle.selector();
+    if !selector.is_configured() {
+        debug!("Fallback VM disabled during recheck; skipping auto-start");
+        return Ok(());
+    }
+    if let Some(vm) = selector.select(&vms) {
         info!(
-            "No running VMs detected; starting fallback VM '{}' ({})",
-            vm.name, vm.vmid
+            "No running VMs detected; starting fallback VM '{}' ({}) matched by {}",
+            vm.name,
+            vm.vmid,
+            selector.reason()
         );
         client.start_vm(vm.vmid).await?;
     } else {
         warn!(
-            "Fallback VM '{}' not found; skipping auto-start",
-            fallback_name
+            "No fallback VM matches {}; skipping auto-start",
+            selector.reason()
         );
     }
 
@@ -1,6 +1,13 @@
+pub mod api_version;
+pub mod assets;
 pub mod config;
+pub mod connection_limit;
 pub mod fallback;
+pub mod metrics;
 pub mod proxmox;
+pub mod rate_limit;
+pub mod security_headers;
 pub mod server;
+pub mod vm_events;
 
 pub mod remote_log;
@@ -1,9 +1,16 @@
-use risky_proxmox_agent::config::Config;
-use risky_proxmox_agent::fallback::spawn_fallback_task;
+use std::time::Duration;
+
+use axum_server::tls_rustls::RustlsConfig;
+use risky_proxmox_agent::assets::StaticAssets;
+use risky_proxmox_agent::config::{Config, RemoteLogBackend};
+use risky_proxmox_agent::fallback::{spawn_fallback_task, FallbackSelector};
+use risky_proxmox_agent::proxmox::error::ProxmoxError;
 use risky_proxmox_agent::proxmox::ProxmoxClient;
-use risky_proxmox_agent::remote_log::{RemoteLogHandle, RemoteLogMakeWriter};
-use risky_proxmox_agent::server::{router, AppState};
-use tracing::{debug, info};
+use risky_proxmox_agent::rate_limit::RateLimitConfig;
+use risky_proxmox_agent::remote_log::{RemoteLogHandle, RemoteLogMakeWriter, SyslogWriter};
+use risky_proxmox_agent::security_headers::SecurityHeaders;
+use risky_proxmox_agent::server::{router, AppState, CompressionConfig, ShutdownConfig};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::prelude::*;
 
 #[tokio::main]
@@ -16,61 +23,304 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let env_filter = tracing_subscriber::EnvFilter::from_default_env();
     let stdout_layer = tracing_subscriber::fmt::layer().with_filter(env_filter.clone());
 
-    if let Some(remote_config) = config.remote_log.clone() {
-        info!("Remote log forwarding enabled");
-        let remote = RemoteLogHandle::new(remote_config);
-        remote.spawn_upload_loop();
-
+    let (remote_writer, remote_log) = match config.remote_log.clone() {
+        Some(RemoteLogBackend::Http(remote_config)) => {
+            let remote = RemoteLogHandle::new(remote_config);
+            remote.spawn_upload_loop();
+            (Some(RemoteLogMakeWriter::new(remote.clone())), Some(remote))
+        }
+        Some(RemoteLogBackend::Syslog {
+            facility,
+            socket_path,
+        }) => {
+            let writer = SyslogWriter::connect(&socket_path, facility).map_err(|err| {
+                eprintln!(
+                    "Failed to open syslog socket {}: {err}",
+                    socket_path.display()
+                );
+                err
+            })?;
+            (Some(RemoteLogMakeWriter::syslog(writer)), None)
+        }
+        None => (None, None),
+    };
+    if let Some(writer) = remote_writer {
         let remote_layer = tracing_subscriber::fmt::layer()
             .json()
             .with_current_span(false)
             .with_span_list(false)
-            .with_writer(RemoteLogMakeWriter::new(remote))
+            .with_writer(writer)
             .with_filter(env_filter);
 
         tracing_subscriber::registry()
             .with(stdout_layer)
             .with(remote_layer)
             .init();
+        info!("Remote log forwarding enabled");
     } else {
         tracing_subscriber::registry().with(stdout_layer).init();
     }
 
+    let safe = config.display_safe();
     info!(
-        bind = %config.bind,
-        port = config.port,
-        pve_host = %config.pve_host,
-        insecure_ssl = config.pve_insecure_ssl,
-        fallback_vm = ?config.pve_fallback_vm,
-        remote_log_enabled = config.remote_log.is_some(),
+        bind = %safe.bind,
+        port = %safe.port,
+        pve_host = %safe.pve_host,
+        pve_token_id = %safe.pve_token_id,
+        pve_token_secret = %safe.pve_token_secret,
+        admin_api_key = %safe.admin_api_key,
+        pve_insecure_ssl = %safe.pve_insecure_ssl,
+        pve_cert_fingerprint = %safe.pve_cert_fingerprint,
+        pve_node = %safe.pve_node,
+        pve_fallback_vm = %safe.pve_fallback_vm,
+        pve_fallback_tag = %safe.pve_fallback_tag,
+        pve_shutdown_dry_run = %safe.pve_shutdown_dry_run,
+        pve_protected_vmids = %safe.pve_protected_vmids,
+        pve_launch_target_vmid = %safe.pve_launch_target_vmid,
+        pve_allowed_vmids = %safe.pve_allowed_vmids,
+        pve_vm_list_cache = %safe.pve_vm_list_cache,
+        tls_enabled = %safe.tls_enabled,
+        rate_limit = %safe.rate_limit,
+        connection_limit = %safe.connection_limit,
+        ws_poll_interval_secs = %safe.ws_poll_interval_secs,
+        security_headers = %safe.security_headers,
+        startup_probe = %safe.startup_probe,
+        remote_log = %safe.remote_log,
+        remote_log_enabled = %safe.remote_log_enabled,
+        static_assets_dir = ?config.static_assets_dir,
         "Configuration loaded"
     );
     debug!("Tracing initialized");
 
-    let client = ProxmoxClient::new(
-        config.pve_host,
+    let mut client = ProxmoxClient::builder(
+        config.pve_host.clone(),
         &config.pve_token_id,
         &config.pve_token_secret,
-        config.pve_insecure_ssl,
-    )?;
+    )
+    .insecure_ssl(config.pve_insecure_ssl)
+    .cert_fingerprint(config.pve_cert_fingerprint.clone())
+    .vm_list_cache_ttl(Duration::from_millis(config.pve_vm_list_cache_ttl_ms))
+    .build()?;
+    if let Some(node) = config.pve_node.clone() {
+        info!(node = %node, "Using PVE_NODE as node hint for VM calls");
+        client = client.with_node_hint(node);
+    }
     info!("Proxmox client initialized");
-
-    if let Some(fallback_name) = config.pve_fallback_vm.clone() {
-        info!(fallback_vm = %fallback_name, "Starting fallback monitoring task");
-        spawn_fallback_task(client.clone(), fallback_name);
+    if config.startup_probe_disabled {
+        info!("STARTUP_PROBE_DISABLED is set; not checking Proxmox before serving");
     } else {
-        info!("Fallback monitoring task disabled");
+        let probe_timeout = Duration::from_secs(config.startup_probe_timeout_secs);
+        let probe = async {
+            let version = client.probe_and_version().await?;
+            client.check_required_privileges().await;
+            Ok::<_, ProxmoxError>(version)
+        };
+        match tokio::time::timeout(probe_timeout, probe).await {
+            Ok(Ok(version)) => info!(
+                version = %version.version,
+                release = %version.release,
+                repoid = %version.repoid,
+                "Connected to Proxmox"
+            ),
+            Ok(Err(err)) => {
+                error!("Proxmox is not reachable at startup: {err}");
+                exit_after_flush(remote_log).await;
+            }
+            Err(_) => {
+                error!(
+                    "Proxmox did not respond to the startup probe within {}s",
+                    probe_timeout.as_secs()
+                );
+                exit_after_flush(remote_log).await;
+            }
+        }
     }
 
-    let app = router(AppState::new(client));
+    // Spawned even without a fallback VM so one can be set at runtime.
+    let selector = FallbackSelector {
+        name: config.pve_fallback_vm.clone(),
+        tag: config.pve_fallback_tag.clone(),
+    };
+    info!(
+        fallback_vm = ?selector.name,
+        fallback_tag = ?selector.tag,
+        "Starting fallback monitoring task"
+    );
+    let fallback = spawn_fallback_task(client.clone(), selector);
+
+    let assets = StaticAssets::load(config.static_assets_dir.as_deref());
+    let mut state = AppState::new(client)
+        .with_static_assets(assets)
+        .with_shutdown_config(ShutdownConfig {
+            dry_run: config.pve_shutdown_dry_run,
+        })
+        .with_protected_vmids(config.pve_protected_vmids.iter().copied())
+        .with_compression(CompressionConfig {
+            enabled: config.response_compression_enabled,
+            min_size_bytes: config.compression_min_size_bytes,
+        })
+        .with_vm_events_poll_interval(Duration::from_secs(config.ws_poll_interval_secs));
+    if config.rate_limit_requests_per_second > 0.0 {
+        state = state.with_rate_limit(RateLimitConfig {
+            requests_per_second: config.rate_limit_requests_per_second,
+            burst: config.rate_limit_burst,
+        });
+    }
+    if config.max_concurrent_connections > 0 {
+        state = state.with_connection_limit(config.max_concurrent_connections);
+    }
+    if config.disable_security_headers {
+        warn!("DISABLE_SECURITY_HEADERS is set; the UI may be framed by other sites");
+        state = state.without_security_headers();
+    } else {
+        let headers = SecurityHeaders::new(&config.csp_header).map_err(|err| {
+            eprintln!("Invalid CSP_HEADER: {err}");
+            err
+        })?;
+        state = state.with_security_headers(headers);
+    }
+    if let Some(key) = config.admin_api_key.clone() {
+        state = state.with_admin_api_key(key);
+    } else {
+        info!("ADMIN_API_KEY not set; /admin routes are disabled");
+    }
+    if let Some(vmids) = config.pve_allowed_vmids.clone() {
+        state = state.with_allowed_vmids(vmids);
+    }
+    if let Some(vmid) = config.pve_launch_target_vmid {
+        state = state.with_launch_target_vmid(vmid);
+    }
+    state = state.with_fallback(fallback);
+    if let Some(remote) = remote_log.clone() {
+        state = state.with_remote_log(remote);
+    }
+    let app = router(state.clone());
     info!("HTTP routes initialized");
 
+    let tls = match config.tls_paths() {
+        Some((cert, key)) => {
+            // reqwest and the TLS listener share rustls; pin the ring provider for both.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let tls = RustlsConfig::from_pem_file(cert, key)
+                .await
+                .map_err(|err| {
+                    let message = format!(
+                        "Failed to load TLS certificate {} / key {}: {err}",
+                        cert.display(),
+                        key.display()
+                    );
+                    eprintln!("{message}");
+                    message
+                })?;
+            info!("TLS enabled");
+            Some(tls)
+        }
+        None => None,
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+
     let addr = std::net::SocketAddr::from((config.bind, config.port));
-    info!("Starting server on {addr}");
+    info!("Starting server on {scheme}://{addr}");
 
+    // Register signal handlers before announcing readiness so an early SIGTERM is not lost.
+    let shutdown = shutdown_signal();
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("TCP listener bound successfully");
-    axum::serve(listener, app).await?;
+    info!(
+        local_addr = %format!("{scheme}://{}", listener.local_addr()?),
+        "TCP listener bound successfully"
+    );
+    let tls_handle = axum_server::Handle::new();
+    let mut server = match tls {
+        Some(tls) => tokio::spawn(
+            axum_server::from_tcp_rustls(listener.into_std()?, tls)
+                .handle(tls_handle.clone())
+                .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>()),
+        ),
+        None => {
+            let serve = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .with_graceful_shutdown(state.shutdown_started());
+            tokio::spawn(async move { serve.await })
+        }
+    };
+
+    let server_stopped = tokio::select! {
+        result = &mut server => {
+            result??;
+            true
+        }
+        _ = shutdown => false,
+    };
+    // Refuse new launch work and end long request waits before the open
+    // connections are drained; one grace period covers both phases.
+    state.begin_shutdown();
+    let grace_period = Duration::from_secs(config.shutdown_grace_period_secs);
+    let deadline = tokio::time::Instant::now() + grace_period;
+    if !server_stopped {
+        tls_handle.graceful_shutdown(Some(grace_period));
+        match tokio::time::timeout_at(deadline, &mut server).await {
+            Ok(result) => result??,
+            Err(_) => {
+                warn!("Grace period elapsed with requests still open; closing them");
+                server.abort();
+            }
+        }
+    }
+
+    info!("HTTP server stopped; draining in-flight flows");
+    let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+    if state.drain(remaining).await {
+        info!("All in-flight flows completed");
+    } else {
+        warn!("Grace period elapsed with flows still running; exiting anyway");
+    }
+    if let Some(remote) = remote_log {
+        remote.flush().await;
+    }
 
     Ok(())
 }
+
+/// Exits with status 1 once any buffered remote log entries are sent.
+async fn exit_after_flush(remote_log: Option<RemoteLogHandle>) -> ! {
+    if let Some(remote) = remote_log {
+        remote.flush().await;
+    }
+    std::process::exit(1)
+}
+
+fn shutdown_signal() -> impl std::future::Future<Output = ()> {
+    #[cfg(unix)]
+    let sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .map_err(|err| warn!("Failed to listen for SIGTERM: {err}"))
+        .ok();
+
+    async move {
+        let ctrl_c = async {
+            if let Err(err) = tokio::signal::ctrl_c().await {
+                warn!("Failed to listen for Ctrl+C: {err}");
+                std::future::pending::<()>().await;
+            }
+        };
+
+        #[cfg(unix)]
+        let terminate = async move {
+            match sigterm {
+                Some(mut signal) => {
+                    signal.recv().await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => info!("Received Ctrl+C; shutting down"),
+            _ = terminate => info!("Received SIGTERM; shutting down"),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{LabelPair, Metric, MetricFamily, MetricType, Quantile, Summary};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntGauge, Opts, Registry, TextEncoder};
+
+/// Quantiles reported by `http_request_duration_p99_seconds`.
+const SUMMARY_QUANTILES: &[f64] = &[0.5, 0.9, 0.99];
+
+/// Recent observations per label set that the summary quantiles are computed over.
+const SUMMARY_WINDOW: usize = 1024;
+
+const HTTP_LABELS: &[&str] = &["method", "matched_path", "status_code"];
+
+/// Content type of [`render`]'s output.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+struct Metrics {
+    registry: Registry,
+    http_request_duration: HistogramVec,
+    http_request_quantiles: SummaryVec,
+    proxmox_api_call_duration: HistogramVec,
+    active_connections: IntGauge,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new();
+    let http_request_duration = HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "HTTP request latency by route and status",
+        ),
+        HTTP_LABELS,
+    )
+    .expect("valid histogram options");
+    let http_request_quantiles = SummaryVec::new(
+        Opts::new(
+            "http_request_duration_p99_seconds",
+            "HTTP request latency quantiles over recent requests by route and status",
+        ),
+        HTTP_LABELS,
+    );
+    let proxmox_api_call_duration = HistogramVec::new(
+        HistogramOpts::new(
+            "proxmox_api_call_duration_seconds",
+            "Proxmox API request latency by HTTP method and status",
+        ),
+        &["method", "status_code"],
+    )
+    .expect("valid histogram options");
+    let active_connections = IntGauge::new(
+        "active_connections",
+        "Requests currently holding a concurrent request slot",
+    )
+    .expect("valid gauge options");
+    for collector in [
+        Box::new(http_request_duration.clone()) as Box<dyn Collector>,
+        Box::new(http_request_quantiles.clone()),
+        Box::new(proxmox_api_call_duration.clone()),
+        Box::new(active_connections.clone()),
+    ] {
+        registry.register(collector).expect("unique metric names");
+    }
+    Metrics {
+        registry,
+        http_request_duration,
+        http_request_quantiles,
+        proxmox_api_call_duration,
+        active_connections,
+    }
+});
+
+/// Method and route template of the request a response answers, attached by
+/// [`label_route`] so the trace layer can label latency metrics.
+#[derive(Debug, Clone)]
+pub struct RouteLabels {
+    method: Method,
+    matched_path: String,
+}
+
+/// Copies the request's method and matched route onto its response.
+pub async fn label_route(request: Request, next: Next) -> Response {
+    let labels = RouteLabels {
+        method: request.method().clone(),
+        matched_path: request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(MatchedPath::as_str)
+            .unwrap_or("<unmatched>")
+            .to_string(),
+    };
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(labels);
+    response
+}
+
+/// Records a served request; responses without [`RouteLabels`] are skipped.
+pub fn observe_http_request<B>(response: &axum::http::Response<B>, latency: Duration) {
+    let Some(labels) = response.extensions().get::<RouteLabels>() else {
+        return;
+    };
+    let status = response.status();
+    let values = [
+        labels.method.as_str(),
+        labels.matched_path.as_str(),
+        status.as_str(),
+    ];
+    let seconds = latency.as_secs_f64();
+    METRICS
+        .http_request_duration
+        .with_label_values(&values)
+        .observe(seconds);
+    METRICS.http_request_quantiles.observe(&values, seconds);
+}
+
+/// Records one Proxmox API round trip; `status_code` is `error` when no
+/// response arrived.
+pub fn observe_proxmox_call(method: &str, status_code: Option<u16>, latency: Duration) {
+    let status_code = status_code.map_or_else(|| "error".to_string(), |code| code.to_string());
+    METRICS
+        .proxmox_api_call_duration
+        .with_label_values(&[method, status_code.as_str()])
+        .observe(latency.as_secs_f64());
+}
+
+/// Counts one request in `active_connections` until dropped.
+pub struct ActiveConnection(());
+
+impl ActiveConnection {
+    pub fn track() -> Self {
+        METRICS.active_connections.inc();
+        Self(())
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        METRICS.active_connections.dec();
+    }
+}
+
+/// All metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .expect("text encoding cannot fail for gathered metrics");
+    String::from_utf8(buffer).expect("text encoder writes UTF-8")
+}
+
+/// Summary with quantiles over a sliding window, which the `prometheus`
+/// crate does not provide.
+#[derive(Clone)]
+struct SummaryVec {
+    desc: Desc,
+    labels: Vec<String>,
+    series: Arc<Mutex<HashMap<Vec<String>, SummarySeries>>>,
+}
+
+#[derive(Default)]
+struct SummarySeries {
+    recent: VecDeque<f64>,
+    count: u64,
+    sum: f64,
+}
+
+impl SummaryVec {
+    fn new(opts: Opts, labels: &[&str]) -> Self {
+        let labels: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+        let desc = Desc::new(opts.name, opts.help, labels.clone(), HashMap::new())
+            .expect("valid summary options");
+        Self {
+            desc,
+            labels,
+            series: Arc::default(),
+        }
+    }
+
+    fn observe(&self, values: &[&str], value: f64) {
+        let key = values.iter().map(|value| value.to_string()).collect();
+        let mut series = self.series.lock().unwrap_or_else(|err| err.into_inner());
+        let series = series.entry(key).or_default();
+        if series.recent.len() == SUMMARY_WINDOW {
+            series.recent.pop_front();
+        }
+        series.recent.push_back(value);
+        series.count += 1;
+        series.sum += value;
+    }
+}
+
+impl Collector for SummaryVec {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let series = self.series.lock().unwrap_or_else(|err| err.into_inner());
+        let mut series: Vec<_> = series.iter().collect();
+        series.sort_by(|a, b| a.0.cmp(b.0));
+        let metrics = series
+            .into_iter()
+            .map(|(values, series)| {
+                let mut sorted: Vec<f64> = series.recent.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                let quantiles = SUMMARY_QUANTILES
+                    .iter()
+                    .map(|&quantile| {
+                        let mut entry = Quantile::default();
+                        entry.set_quantile(quantile);
+                        entry.set_value(quantile_of(&sorted, quantile));
+                        entry
+                    })
+                    .collect();
+                let mut summary = Summary::default();
+                summary.set_sample_count(series.count);
+                summary.set_sample_sum(series.sum);
+                summary.set_quantile(quantiles);
+
+                // Sorted by name, as the `prometheus` collectors emit them.
+                let mut labels: Vec<LabelPair> = self
+                    .labels
+                    .iter()
+                    .zip(values)
+                    .map(|(name, value)| {
+                        let mut pair = LabelPair::default();
+                        pair.set_name(name.clone());
+                        pair.set_value(value.clone());
+                        pair
+                    })
+                    .collect();
+                labels.sort_by(|a, b| a.name().cmp(b.name()));
+                let mut metric = Metric::default();
+                metric.set_label(labels);
+                metric.set_summary(summary);
+                metric
+            })
+            .collect();
+
+        let mut family = MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(MetricType::SUMMARY);
+        family.set_metric(metrics);
+        vec![family]
+    }
+}
+
+/// Nearest-rank quantile of ascending `sorted`; zero when empty.
+fn quantile_of(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantiles_use_nearest_rank() {
+        let sorted: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(quantile_of(&sorted, 0.5), 50.0);
+        assert_eq!(quantile_of(&sorted, 0.9), 90.0);
+        assert_eq!(quantile_of(&sorted, 0.99), 99.0);
+        assert_eq!(quantile_of(&[0.25], 0.99), 0.25);
+        assert_eq!(quantile_of(&[], 0.5), 0.0);
+    }
+}
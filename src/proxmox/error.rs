@@ -1,34 +1,173 @@
 use std::fmt;
 
+use crate::proxmox::fingerprint::FINGERPRINT_MISMATCH;
+
 #[derive(Debug)]
 pub enum ProxmoxError {
     Api(String),
+    NotFound(String),
+    Unauthorized,
+    Forbidden,
+    Conflict(String),
+    /// Proxmox (or a proxy in front of it) asked us to slow down.
+    RateLimited,
+    /// 502 or 503, e.g. while `pveproxy` restarts or a proxy lost the host.
+    Unavailable,
+    Timeout,
     MissingNode(u64),
+    TaskFailed {
+        upid: String,
+        exit_status: String,
+        log_lines: Vec<String>,
+    },
     Reqwest(reqwest::Error),
     Serde(serde_json::Error),
 }
 
+impl ProxmoxError {
+    pub fn from_status(status: reqwest::StatusCode, body: String) -> Self {
+        match status {
+            reqwest::StatusCode::NOT_FOUND => Self::NotFound(body),
+            reqwest::StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            reqwest::StatusCode::FORBIDDEN => Self::Forbidden,
+            reqwest::StatusCode::CONFLICT => Self::Conflict(body),
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            reqwest::StatusCode::BAD_GATEWAY | reqwest::StatusCode::SERVICE_UNAVAILABLE => {
+                Self::Unavailable
+            }
+            reqwest::StatusCode::REQUEST_TIMEOUT | reqwest::StatusCode::GATEWAY_TIMEOUT => {
+                Self::Timeout
+            }
+            _ => Self::Api(format!("status {status}, body {body}")),
+        }
+    }
+
+    /// Whether the same request may succeed if simply sent again later.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RateLimited | Self::Unavailable | Self::Timeout => true,
+            Self::Reqwest(err) => err.is_connect() || err.is_request(),
+            _ => false,
+        }
+    }
+
+    /// Whether Proxmox certainly did not act on the request, so even a
+    /// non-idempotent call such as a VM start can be sent again.
+    pub fn was_not_handled(&self) -> bool {
+        match self {
+            Self::RateLimited | Self::Unavailable => true,
+            Self::Reqwest(err) => err.is_connect(),
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for ProxmoxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Api(message) => write!(f, "Proxmox API error: {message}"),
+            Self::NotFound(message) => write!(f, "Proxmox resource not found: {message}"),
+            Self::Unauthorized => write!(f, "Proxmox rejected the API token"),
+            Self::Forbidden => write!(f, "Proxmox API token lacks permission"),
+            Self::Conflict(message) => write!(f, "Proxmox conflict: {message}"),
+            Self::RateLimited => write!(f, "Proxmox is rate limiting requests"),
+            Self::Unavailable => write!(f, "Proxmox is unavailable"),
+            Self::Timeout => write!(f, "Proxmox request timed out"),
             Self::MissingNode(vmid) => write!(f, "Missing node for VM {vmid}"),
+            Self::TaskFailed {
+                upid, exit_status, ..
+            } => write!(f, "Proxmox task {upid} failed: {exit_status}"),
             Self::Reqwest(err) => write!(f, "HTTP error: {err}"),
             Self::Serde(err) => write!(f, "Parse error: {err}"),
         }
     }
 }
 
-impl std::error::Error for ProxmoxError {}
+impl std::error::Error for ProxmoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Reqwest(err) => Some(err),
+            Self::Serde(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 impl From<reqwest::Error> for ProxmoxError {
     fn from(value: reqwest::Error) -> Self {
-        Self::Reqwest(value)
+        if value.is_timeout() {
+            Self::Timeout
+        } else if value.is_connect() && is_fingerprint_mismatch(&value) {
+            Self::Api(FINGERPRINT_MISMATCH.to_string())
+        } else {
+            Self::Reqwest(value)
+        }
     }
 }
 
+/// The pinned-certificate verifier's rejection is buried in the connect error's source chain.
+fn is_fingerprint_mismatch(err: &reqwest::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(current) = source {
+        if current.to_string().contains(FINGERPRINT_MISMATCH) {
+            return true;
+        }
+        source = current.source();
+    }
+    false
+}
+
 impl From<serde_json::Error> for ProxmoxError {
     fn from(value: serde_json::Error) -> Self {
         Self::Serde(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status_selects_typed_variants() {
+        use reqwest::StatusCode;
+
+        let body = || "detail".to_string();
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::NOT_FOUND, body()),
+            ProxmoxError::NotFound(_)
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::UNAUTHORIZED, body()),
+            ProxmoxError::Unauthorized
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::FORBIDDEN, body()),
+            ProxmoxError::Forbidden
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::CONFLICT, body()),
+            ProxmoxError::Conflict(_)
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::TOO_MANY_REQUESTS, body()),
+            ProxmoxError::RateLimited
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::GATEWAY_TIMEOUT, body()),
+            ProxmoxError::Timeout
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::SERVICE_UNAVAILABLE, body()),
+            ProxmoxError::Unavailable
+        ));
+        assert!(matches!(
+            ProxmoxError::from_status(StatusCode::INTERNAL_SERVER_ERROR, body()),
+            ProxmoxError::Api(_)
+        ));
+        assert!(ProxmoxError::Unavailable.is_transient());
+        assert!(!ProxmoxError::NotFound(body()).is_transient());
+        assert!(ProxmoxError::Unavailable.was_not_handled());
+        assert!(ProxmoxError::Timeout.is_transient());
+        assert!(!ProxmoxError::Timeout.was_not_handled());
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+
+use crate::proxmox::error::ProxmoxError;
+
+/// Marker carried by the TLS error so it can be told apart from other connect failures.
+pub(crate) const FINGERPRINT_MISMATCH: &str = "fingerprint mismatch";
+
+/// Accepts the server certificate only when its SHA-256 digest matches the
+/// pinned fingerprint, as shown in the Proxmox UI under Certificates.
+#[derive(Debug)]
+struct FingerprintVerifier {
+    expected: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+/// Parses `AB:CD:...` or plain hex into the raw SHA-256 digest.
+pub(crate) fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32], ProxmoxError> {
+    let hex: String = fingerprint
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    let invalid = || ProxmoxError::Api(format!("invalid certificate fingerprint: {fingerprint}"));
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(digest)
+}
+
+/// TLS config that trusts exactly the certificate with the given fingerprint.
+pub(crate) fn pinned_tls_config(fingerprint: &str) -> Result<rustls::ClientConfig, ProxmoxError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = FingerprintVerifier {
+        expected: parse_fingerprint(fingerprint)?,
+        provider: provider.clone(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| ProxmoxError::Api(format!("TLS configuration error: {err}")))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(config)
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = ring::digest::digest(&ring::digest::SHA256, end_entity.as_ref());
+        if actual.as_ref() == self.expected {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(FINGERPRINT_MISMATCH.to_string()))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_fingerprint(data: &[u8]) -> String {
+        ring::digest::digest(&ring::digest::SHA256, data)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    #[test]
+    fn verifier_accepts_only_the_pinned_certificate() {
+        let cert = CertificateDer::from(b"pinned certificate".to_vec());
+        let other = CertificateDer::from(b"some other certificate".to_vec());
+        let verifier = FingerprintVerifier {
+            expected: parse_fingerprint(&hex_fingerprint(cert.as_ref())).unwrap(),
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        };
+        let name = ServerName::try_from("pve.example").unwrap();
+
+        assert!(verifier
+            .verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+            .is_ok());
+        let err = verifier
+            .verify_server_cert(&other, &[], &name, &[], UnixTime::now())
+            .unwrap_err();
+        assert!(err.to_string().contains(FINGERPRINT_MISMATCH));
+    }
+
+    #[test]
+    fn parse_fingerprint_accepts_colon_separated_and_plain_hex() {
+        let colons = hex_fingerprint(b"cert");
+        let plain = colons.replace(':', "").to_lowercase();
+        assert_eq!(
+            parse_fingerprint(&colons).unwrap(),
+            parse_fingerprint(&plain).unwrap()
+        );
+        assert!(parse_fingerprint("AB:CD").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+}
@@ -1,22 +1,90 @@
 pub mod error;
+mod fingerprint;
 pub mod types;
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info, warn};
+use tracing::field::Empty;
+use tracing::{debug, info, warn, Instrument, Span};
 
+use crate::metrics::observe_proxmox_call;
 use crate::proxmox::error::ProxmoxError;
-use crate::proxmox::types::{parse_tags, VmInfo, VmStatus};
+use crate::proxmox::types::{
+    import_disk_filename, is_allowed_keysym, is_valid_cloudinit_user, is_valid_cpu_topology,
+    is_valid_disk_size, is_valid_import_url, is_valid_ipconfig, is_valid_memory_mib,
+    is_valid_ssh_public_key, is_valid_storage_id, parse_tags, AclEntry, AgentExecResult,
+    BackupInfo, BackupOptions, ClusterStatus, ConsoleTicket, DiskImport, FirewallRule, ForkOptions,
+    NodeInfo, NodeStatus, PoolDetail, PoolInfo, ProvisionOptions, ProxmoxVersion, RrdTimeframe,
+    SnapshotInfo, StorageInfo, StorageStatus, TaskId, TaskSummary, VmConfig, VmInfo, VmRrdData,
+    VmStatus, MAX_IPCONFIG_INDEX, MAX_SCSI_INDEX,
+};
+
+/// Span for a public client call. The HTTP helpers fill in `method`, `url` and
+/// `result` (the HTTP status) of the latest request; [`traced`] adds `duration_ms`.
+macro_rules! call_span {
+    ($name:literal $(, $field:ident)*) => {
+        tracing::info_span!(
+            $name,
+            $($field,)*
+            method = Empty,
+            url = Empty,
+            result = Empty,
+            duration_ms = Empty,
+        )
+    };
+}
+
+async fn traced<T>(span: Span, call: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let output = call.instrument(span.clone()).await;
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    output
+}
+
+fn record_request(method: &'static str, url: &str) {
+    let span = Span::current();
+    span.record("method", method);
+    span.record("url", url);
+}
 
 #[derive(Clone)]
 pub struct ProxmoxClient {
     base_url: String,
+    token_id: String,
+    token_secret: String,
+    insecure_ssl: bool,
+    cert_fingerprint: Option<String>,
+    /// Node assumed to host every VM, skipping the per-call lookup.
+    node_hint: Option<String>,
+    agent_exec_timeout: Duration,
+    request_timeout: Duration,
+    /// Zero disables the VM list cache.
+    vm_list_cache_ttl: Duration,
+    /// Shared by clones, so every user of the client sees one cache.
+    vm_list_cache: Arc<Mutex<VmListCache>>,
     token: String,
     client: reqwest::Client,
 }
 
+/// Result of the last [`ProxmoxClient::list_vms_fresh`] call.
+struct CachedVmList {
+    data: Vec<VmInfo>,
+    fetched_at: Instant,
+}
+
+#[derive(Default)]
+struct VmListCache {
+    entry: Option<CachedVmList>,
+    /// Bumped by every invalidation, so a listing that was in flight across
+    /// one is not stored.
+    generation: u64,
+}
+
 impl ProxmoxClient {
     pub fn new(
         base_url: impl Into<String>,
@@ -24,233 +92,2794 @@ impl ProxmoxClient {
         token_secret: &str,
         insecure_ssl: bool,
     ) -> Result<Self, ProxmoxError> {
-        let base_url = base_url.into();
-        info!(%base_url, insecure_ssl, "Creating Proxmox HTTP client");
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(insecure_ssl)
-            .build()?;
-        Ok(Self {
-            base_url,
-            token: format!("PVEAPIToken={token_id}={token_secret}"),
-            client,
-        })
+        Self::builder(base_url, token_id, token_secret)
+            .insecure_ssl(insecure_ssl)
+            .build()
+    }
+
+    /// Starts a builder with standard CA verification.
+    pub fn builder(
+        base_url: impl Into<String>,
+        token_id: &str,
+        token_secret: &str,
+    ) -> ProxmoxClientBuilder {
+        ProxmoxClientBuilder {
+            base_url: base_url.into(),
+            token_id: token_id.to_string(),
+            token_secret: token_secret.to_string(),
+            insecure_ssl: false,
+            cert_fingerprint: None,
+            node_hint: None,
+            agent_exec_timeout: AGENT_EXEC_TIMEOUT,
+            request_timeout: REQUEST_TIMEOUT,
+            vm_list_cache_ttl: Duration::ZERO,
+            existing: None,
+        }
+    }
+
+    /// Assumes VMs live on `node` (typical for single-node setups) so VM calls
+    /// skip resolving the node from `/cluster/resources`.
+    pub fn with_node_hint(mut self, node: String) -> Self {
+        self.node_hint = Some(node);
+        self
+    }
+
+    /// Starts a builder seeded with this client's settings. Building it reuses
+    /// the underlying connection pool unless the TLS settings or the request
+    /// timeout change.
+    pub fn reconfigure(&self) -> ProxmoxClientBuilder {
+        ProxmoxClientBuilder {
+            base_url: self.base_url.clone(),
+            token_id: self.token_id.clone(),
+            token_secret: self.token_secret.clone(),
+            insecure_ssl: self.insecure_ssl,
+            cert_fingerprint: self.cert_fingerprint.clone(),
+            node_hint: self.node_hint.clone(),
+            agent_exec_timeout: self.agent_exec_timeout,
+            request_timeout: self.request_timeout,
+            vm_list_cache_ttl: self.vm_list_cache_ttl,
+            existing: Some((
+                (
+                    self.insecure_ssl,
+                    self.cert_fingerprint.clone(),
+                    self.request_timeout,
+                ),
+                self.client.clone(),
+            )),
+        }
+    }
+
+    /// Proxmox host the client talks to, e.g. `https://pve.example:8006`.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Cheap connectivity and credential check against `/version`.
+    pub async fn probe(&self) -> Result<(), ProxmoxError> {
+        self.probe_and_version().await.map(|_| ())
+    }
+
+    pub async fn probe_and_version(&self) -> Result<ProxmoxVersion, ProxmoxError> {
+        debug!("Probing Proxmox API version");
+        let version: ProxmoxVersion = self.get("/version").await?;
+        debug!(version = %version.version, release = %version.release, "Proxmox API reachable");
+        Ok(version)
+    }
+
+    /// Warns when the token lacks any of [`REQUIRED_PRIVILEGES`]. Only logs,
+    /// so a token with narrower rights still starts; run it once at startup.
+    pub async fn check_required_privileges(&self) {
+        let permissions = match self.get_current_permissions().await {
+            Ok(permissions) => permissions,
+            Err(err) => {
+                warn!(error = %err, "Could not read API token permissions");
+                return;
+            }
+        };
+        let missing = missing_privileges(&permissions);
+        if missing.is_empty() {
+            debug!("API token has the required privileges");
+        } else {
+            warn!(missing = ?missing, "API token lacks privileges the agent needs");
+        }
     }
 
+    /// VM inventory, served from the cache while it is younger than the
+    /// configured TTL.
     pub async fn list_vms(&self) -> Result<Vec<VmInfo>, ProxmoxError> {
-        debug!("Fetching VM inventory from Proxmox");
-        let resources: Vec<ResourceVm> = self.get("/cluster/resources?type=vm").await?;
+        if !self.vm_list_cache_ttl.is_zero() {
+            let cache = self.vm_list_cache.lock().expect("VM list cache poisoned");
+            if let Some(cached) = cache
+                .entry
+                .as_ref()
+                .filter(|cached| cached.fetched_at.elapsed() < self.vm_list_cache_ttl)
+            {
+                debug!(
+                    vm_count = cached.data.len(),
+                    "Serving VM inventory from cache"
+                );
+                return Ok(cached.data.clone());
+            }
+        }
+        self.list_vms_fresh().await
+    }
+
+    /// VM inventory straight from Proxmox, bypassing and then refreshing the
+    /// cache. For callers that poll for a change they just caused.
+    pub async fn list_vms_fresh(&self) -> Result<Vec<VmInfo>, ProxmoxError> {
+        traced(call_span!("list_vms"), async {
+            debug!("Fetching VM inventory from Proxmox");
+            let generation = self
+                .vm_list_cache
+                .lock()
+                .expect("VM list cache poisoned")
+                .generation;
+            let resources: Vec<ResourceVm> = self.get("/cluster/resources?type=vm").await?;
+
+            let vms: Vec<VmInfo> = resources.into_iter().map(VmInfo::from).collect();
+            info!(vm_count = vms.len(), "Fetched VM inventory");
+            if !self.vm_list_cache_ttl.is_zero() {
+                let mut cache = self.vm_list_cache.lock().expect("VM list cache poisoned");
+                if cache.generation == generation {
+                    cache.entry = Some(CachedVmList {
+                        data: vms.clone(),
+                        fetched_at: Instant::now(),
+                    });
+                } else {
+                    debug!("VM inventory may predate a change; not caching it");
+                }
+            }
+            Ok(vms)
+        })
+        .await
+    }
+
+    /// Drops the cached VM list, so the next [`Self::list_vms`] asks Proxmox.
+    /// Called after every request that may change a VM.
+    pub fn invalidate_vm_cache(&self) {
+        let mut cache = self.vm_list_cache.lock().expect("VM list cache poisoned");
+        cache.entry = None;
+        cache.generation += 1;
+    }
+
+    /// VMs hosted on `node` only, unlike the cluster-wide [`Self::list_vms`].
+    pub async fn list_vms_on_node(&self, node: &str) -> Result<Vec<VmInfo>, ProxmoxError> {
+        debug!(node, "Fetching VM inventory for node");
+        let path = format!("/nodes/{node}/qemu");
+        let vms: Vec<NodeVm> = self.get(&path).await?;
+        let vms: Vec<VmInfo> = vms.into_iter().map(|vm| vm.into_vm_info(node)).collect();
+        info!(node, vm_count = vms.len(), "Fetched node VM inventory");
+        Ok(vms)
+    }
+
+    pub async fn list_vms_by_tag(&self, tag: &str) -> Result<Vec<VmInfo>, ProxmoxError> {
+        debug!(tag, "Fetching VM inventory filtered by tag");
+        let resources: Vec<ResourceVm> = self
+            .get_with_query("/cluster/resources", &[("type", "vm"), ("tags", tag)])
+            .await?;
 
+        let fetched = resources.len();
         let vms: Vec<VmInfo> = resources
             .into_iter()
-            .map(|vm| VmInfo {
-                vmid: vm.vmid,
-                name: vm.name.unwrap_or_default(),
-                tags: parse_tags(vm.tags.as_deref()),
-                status: VmStatus::normalize(vm.status.as_deref()),
-                notes: vm.description.filter(|note| !note.trim().is_empty()),
+            .map(VmInfo::from)
+            .filter(|vm| {
+                vm.tags
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(tag))
             })
             .collect();
-        info!(vm_count = vms.len(), "Fetched VM inventory");
+        if vms.len() != fetched {
+            debug!(
+                tag,
+                fetched,
+                matched = vms.len(),
+                "Server-side tag filtering unsupported; filtered VMs client-side"
+            );
+        }
+        info!(tag, vm_count = vms.len(), "Fetched VM inventory by tag");
         Ok(vms)
     }
 
+    pub async fn list_vms_by_status(&self, status: VmStatus) -> Result<Vec<VmInfo>, ProxmoxError> {
+        let vms = self.list_vms().await?;
+        Ok(vms.into_iter().filter(|vm| vm.status == status).collect())
+    }
+
     pub async fn vm_status(&self, vmid: u64) -> Result<VmStatus, ProxmoxError> {
-        debug!(vmid, "Fetching VM status");
-        let node = self.node_for_vmid(vmid).await?;
-        let path = format!("/nodes/{node}/qemu/{vmid}/status/current");
-        let status: StatusResponse = self.get(&path).await?;
-        let normalized = VmStatus::normalize(Some(&status.status));
-        debug!(vmid, status = ?normalized, "Fetched VM status");
-        Ok(normalized)
+        traced(call_span!("vm_status", vmid), async {
+            debug!(vmid, "Fetching VM status");
+            let status: StatusResponse = self
+                .on_vm_node(
+                    vmid,
+                    |node| async move { self.get_status(&node, vmid).await },
+                )
+                .await?;
+            let normalized = VmStatus::normalize(Some(&status.status));
+            debug!(vmid, status = ?normalized, "Fetched VM status");
+            Ok(normalized)
+        })
+        .await
+    }
+
+    /// How long the VM has been running; zero unless it is running.
+    pub async fn vm_uptime(&self, vmid: u64) -> Result<Duration, ProxmoxError> {
+        debug!(vmid, "Fetching VM uptime");
+        let status: StatusResponse = self
+            .on_vm_node(
+                vmid,
+                |node| async move { self.get_status(&node, vmid).await },
+            )
+            .await?;
+        if VmStatus::normalize(Some(&status.status)) != VmStatus::Running {
+            return Ok(Duration::ZERO);
+        }
+        Ok(Duration::from_secs(status.uptime.unwrap_or(0)))
+    }
+
+    /// Like [`Self::vm_status`], but trusts `node` instead of resolving it.
+    pub async fn vm_status_with_node_hint(
+        &self,
+        vmid: u64,
+        node: &str,
+    ) -> Result<VmStatus, ProxmoxError> {
+        traced(call_span!("vm_status", vmid, node), async {
+            debug!(vmid, node, "Fetching VM status on known node");
+            let status = self.get_status(node, vmid).await?;
+            Ok(VmStatus::normalize(Some(&status.status)))
+        })
+        .await
+    }
+
+    /// Like [`Self::start_vm`], but trusts `node` instead of resolving it.
+    pub async fn start_vm_with_node_hint(&self, vmid: u64, node: &str) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("start_vm", vmid, node),
+            self.post_status_on(node, vmid, "start"),
+        )
+        .await
+    }
+
+    pub async fn shutdown_vm_with_node_hint(
+        &self,
+        vmid: u64,
+        node: &str,
+    ) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("shutdown_vm", vmid, node),
+            self.post_status_on(node, vmid, "shutdown"),
+        )
+        .await
+    }
+
+    pub async fn hibernate_vm_with_node_hint(
+        &self,
+        vmid: u64,
+        node: &str,
+    ) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("hibernate_vm", vmid, node),
+            self.post_status_on(node, vmid, "hibernate"),
+        )
+        .await
+    }
+
+    pub async fn suspend_vm_with_node_hint(
+        &self,
+        vmid: u64,
+        node: &str,
+    ) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("suspend_vm", vmid, node),
+            self.post_status_on(node, vmid, "suspend"),
+        )
+        .await
+    }
+
+    pub async fn terminate_vm_with_node_hint(
+        &self,
+        vmid: u64,
+        node: &str,
+    ) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("terminate_vm", vmid, node),
+            self.post_status_on(node, vmid, "stop"),
+        )
+        .await
     }
 
     pub async fn start_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
-        self.post_status(vmid, "start").await
+        traced(
+            call_span!("start_vm", vmid),
+            self.post_status(vmid, "start"),
+        )
+        .await
     }
 
     pub async fn stop_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
-        self.post_status(vmid, "shutdown").await
+        traced(
+            call_span!("stop_vm", vmid),
+            self.post_status(vmid, "shutdown"),
+        )
+        .await
     }
 
     pub async fn shutdown_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
-        self.post_status(vmid, "shutdown").await
+        traced(
+            call_span!("shutdown_vm", vmid),
+            self.post_status(vmid, "shutdown"),
+        )
+        .await
     }
 
     pub async fn hibernate_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
-        self.post_status(vmid, "hibernate").await
+        traced(
+            call_span!("hibernate_vm", vmid),
+            self.post_status(vmid, "hibernate"),
+        )
+        .await
+    }
+
+    /// Pauses the guest in RAM; it stays allocated until resumed.
+    pub async fn suspend_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("suspend_vm", vmid),
+            self.post_status(vmid, "suspend"),
+        )
+        .await
+    }
+
+    pub async fn resume_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("resume_vm", vmid),
+            self.post_status(vmid, "resume"),
+        )
+        .await
     }
 
     pub async fn terminate_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
-        self.post_status(vmid, "stop").await
+        traced(
+            call_span!("terminate_vm", vmid),
+            self.post_status(vmid, "stop"),
+        )
+        .await
     }
 
-    pub async fn fork_vm(&self, vmid: u64, name: &str) -> Result<u64, ProxmoxError> {
-        info!(source_vmid = vmid, new_name = %name, "Forking VM");
-        let snapshot = format!(
-            "fork-{}",
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs()
-        );
-        let newid = self.next_vmid().await?;
-        self.create_snapshot(vmid, &snapshot).await?;
-        self.clone_vm(vmid, newid, name, &snapshot).await?;
-        info!(source_vmid = vmid, new_vmid = newid, snapshot = %snapshot, "Fork command sent");
-        Ok(newid)
+    pub async fn reboot_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("reboot_vm", vmid),
+            self.post_status(vmid, "reboot"),
+        )
+        .await
     }
 
-    async fn node_for_vmid(&self, vmid: u64) -> Result<String, ProxmoxError> {
-        debug!(vmid, "Resolving node for VM");
-        let resources: Vec<ResourceVm> = self.get("/cluster/resources?type=vm").await?;
-        resources
-            .into_iter()
-            .find(|vm| vm.vmid == vmid)
-            .and_then(|vm| vm.node)
-            .ok_or(ProxmoxError::MissingNode(vmid))
-            .map(|node| {
-                debug!(vmid, node = %node, "Resolved node for VM");
-                node
+    pub async fn reset_vm(&self, vmid: u64) -> Result<(), ProxmoxError> {
+        traced(
+            call_span!("reset_vm", vmid),
+            self.post_status(vmid, "reset"),
+        )
+        .await
+    }
+
+    pub async fn fork_vm(&self, vmid: u64, opts: ForkOptions) -> Result<u64, ProxmoxError> {
+        traced(call_span!("fork_vm", vmid), async {
+            info!(source_vmid = vmid, new_name = %opts.name, ?opts, "Forking VM");
+            let snapshot = format!(
+                "fork-{}",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            );
+            let newid = self.allocate_vmid(&opts).await?;
+            self.create_snapshot(vmid, &snapshot).await?;
+            self.clone_vm(vmid, newid, &opts, Some(&snapshot)).await?;
+            info!(source_vmid = vmid, new_vmid = newid, snapshot = %snapshot, "Fork command sent");
+            Ok(newid)
+        })
+        .await
+    }
+
+    /// Clones `vmid` from an existing `snapshot`, or from its current state when
+    /// `snapshot` is `None`. A full clone without a snapshot takes a temporary
+    /// one first, exactly like `fork_vm`.
+    pub async fn clone_vm_from(
+        &self,
+        vmid: u64,
+        opts: ForkOptions,
+        snapshot: Option<&str>,
+    ) -> Result<u64, ProxmoxError> {
+        if snapshot.is_none() && opts.full_clone {
+            return self.fork_vm(vmid, opts).await;
+        }
+        traced(call_span!("clone_vm", vmid), async {
+            let newid = self.allocate_vmid(&opts).await?;
+            self.clone_vm(vmid, newid, &opts, snapshot).await?;
+            info!(
+                source_vmid = vmid,
+                new_vmid = newid,
+                snapshot,
+                "Clone command sent"
+            );
+            Ok(newid)
+        })
+        .await
+    }
+
+    /// Starts a vzdump backup; it runs asynchronously, so only the task is returned.
+    pub async fn backup_vm(&self, vmid: u64, opts: BackupOptions) -> Result<TaskId, ProxmoxError> {
+        info!(vmid, storage = %opts.storage, mode = opts.mode.as_str(), "Starting VM backup");
+        let body = &VzdumpRequest {
+            vmid,
+            storage: &opts.storage,
+            mode: opts.mode.as_str(),
+            compress: opts.compress.as_str(),
+            notes_template: opts.notes.as_deref(),
+        };
+        let upid = self
+            .on_vm_node(vmid, |node| async move {
+                self.post_form_task(&format!("/nodes/{node}/vzdump"), body)
+                    .await
             })
+            .await?;
+        info!(vmid, upid = %upid, "Backup task started");
+        Ok(TaskId(upid))
     }
 
-    async fn post_status(&self, vmid: u64, action: &str) -> Result<(), ProxmoxError> {
-        info!(vmid, action, "Sending VM status action");
-        let node = self.node_for_vmid(vmid).await?;
-        let path = format!("/nodes/{node}/qemu/{vmid}/status/{action}");
-        self.post(&path).await
+    pub async fn list_backups(
+        &self,
+        node: &str,
+        vmid: u64,
+        storage: &str,
+    ) -> Result<Vec<BackupInfo>, ProxmoxError> {
+        debug!(node, vmid, storage, "Fetching VM backups");
+        let vmid = vmid.to_string();
+        let entries: Vec<BackupContentResponse> = self
+            .get_with_query(
+                &format!("/nodes/{node}/storage/{storage}/content"),
+                &[("content", "backup"), ("vmid", vmid.as_str())],
+            )
+            .await?;
+        Ok(entries.into_iter().map(BackupInfo::from).collect())
     }
 
-    async fn next_vmid(&self) -> Result<u64, ProxmoxError> {
-        debug!("Requesting next available VMID");
-        let nextid: String = self.get("/cluster/nextid").await?;
-        nextid
-            .parse()
-            .map_err(|err| ProxmoxError::Api(format!("Invalid next VMID: {err}")))
-            .map(|id| {
-                debug!(next_vmid = id, "Received next VMID");
-                id
+    /// Node hosting `vmid`, or the configured node hint.
+    pub async fn vm_node(&self, vmid: u64) -> Result<String, ProxmoxError> {
+        match &self.node_hint {
+            Some(node) => Ok(node.clone()),
+            None => self.node_for_vmid(vmid).await,
+        }
+    }
+
+    pub async fn get_vm_config(&self, vmid: u64) -> Result<VmConfig, ProxmoxError> {
+        debug!(vmid, "Fetching VM config");
+        let config: VmConfigResponse = self
+            .on_vm_node(vmid, |node| async move {
+                self.get(&format!("/nodes/{node}/qemu/{vmid}/config")).await
             })
+            .await?;
+        Ok(VmConfig {
+            name: config.name,
+            description: config.description,
+            tags: parse_tags(config.tags.as_deref()),
+        })
     }
 
-    async fn create_snapshot(&self, vmid: u64, snapshot: &str) -> Result<(), ProxmoxError> {
-        info!(vmid, snapshot, "Creating VM snapshot for fork");
-        let node = self.node_for_vmid(vmid).await?;
-        let path = format!("/nodes/{node}/qemu/{vmid}/snapshot");
-        let body = SnapshotRequest { snapname: snapshot };
-        self.post_form(&path, &body).await
+    /// Opens a websocket VNC proxy for `vmid` and returns its ticket.
+    pub async fn create_console_ticket(&self, vmid: u64) -> Result<ConsoleTicket, ProxmoxError> {
+        info!(vmid, "Creating VM console ticket");
+        self.on_vm_node(vmid, |node| async move {
+            let proxy: VncProxyResponse = self
+                .post_form_data(
+                    &format!("/nodes/{node}/qemu/{vmid}/vncproxy"),
+                    &[("websocket", "1")],
+                )
+                .await?;
+            Ok(ConsoleTicket {
+                node,
+                ticket: proxy.ticket,
+                port: proxy.port,
+                user: proxy.user,
+                upid: proxy.upid,
+            })
+        })
+        .await
     }
 
-    async fn clone_vm(
+    pub async fn get_firewall_rules(&self, vmid: u64) -> Result<Vec<FirewallRule>, ProxmoxError> {
+        debug!(vmid, "Fetching VM firewall rules");
+        let rules: Vec<FirewallRuleResponse> = self
+            .on_vm_node(vmid, |node| async move {
+                self.get(&format!("/nodes/{node}/qemu/{vmid}/firewall/rules"))
+                    .await
+            })
+            .await?;
+        Ok(rules.into_iter().map(FirewallRule::from).collect())
+    }
+
+    pub async fn set_firewall_rule_enable(
         &self,
         vmid: u64,
-        newid: u64,
-        name: &str,
-        snapshot: &str,
+        pos: u32,
+        enabled: bool,
     ) -> Result<(), ProxmoxError> {
-        info!(source_vmid = vmid, new_vmid = newid, new_name = %name, snapshot, "Cloning VM from snapshot");
-        let node = self.node_for_vmid(vmid).await?;
-        let path = format!("/nodes/{node}/qemu/{vmid}/clone");
-        let body = CloneRequest {
-            newid,
-            name,
-            full: 1,
-            snapname: snapshot,
+        info!(vmid, pos, enabled, "Updating VM firewall rule");
+        let enable = if enabled { "1" } else { "0" };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(
+                &format!("/nodes/{node}/qemu/{vmid}/firewall/rules/{pos}"),
+                &[("enable", enable)],
+            )
+            .await
+        })
+        .await
+    }
+
+    pub async fn set_vm_tags(&self, vmid: u64, tags: &[&str]) -> Result<(), ProxmoxError> {
+        info!(vmid, ?tags, "Updating VM tags");
+        let tags = tags.join(";");
+        let body = &VmConfigUpdate {
+            tags: Some(&tags),
+            ..Default::default()
         };
-        self.post_form(&path, &body).await
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
 
-    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ProxmoxError> {
-        let url = self.endpoint(path);
-        debug!(method = "GET", %url, "Sending Proxmox request");
-        let response = self
-            .client
-            .get(&url)
-            .header(reqwest::header::AUTHORIZATION, self.token.clone())
-            .send()
-            .await?;
-        let response = Self::ensure_success(response).await?;
-        debug!(method = "GET", %url, status = %response.status(), "Proxmox request succeeded");
-        let response: ApiResponse<T> = response.json().await?;
-        Ok(response.data)
+    pub async fn set_vm_notes(&self, vmid: u64, notes: &str) -> Result<(), ProxmoxError> {
+        info!(vmid, notes_len = notes.len(), "Updating VM notes");
+        let body = &VmConfigUpdate {
+            description: Some(notes),
+            ..Default::default()
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
 
-    async fn post(&self, path: &str) -> Result<(), ProxmoxError> {
-        let url = self.endpoint(path);
-        debug!(method = "POST", %url, "Sending Proxmox request");
-        let response = self
-            .client
-            .post(&url)
-            .header(reqwest::header::AUTHORIZATION, self.token.clone())
-            .send()
-            .await?;
-        let response = Self::ensure_success(response).await?;
-        debug!(method = "POST", %url, status = %response.status(), "Proxmox request succeeded");
-        Ok(())
+    /// Sets the CPU topology. Proxmox applies it at the next start unless
+    /// CPU hotplug is enabled.
+    pub async fn set_vm_cpu(
+        &self,
+        vmid: u64,
+        sockets: u32,
+        cores: u32,
+    ) -> Result<(), ProxmoxError> {
+        if !is_valid_cpu_topology(sockets, cores) {
+            return Err(ProxmoxError::Api("invalid cpu/memory config".to_string()));
+        }
+        info!(vmid, sockets, cores, "Updating VM CPU topology");
+        let body = &VmConfigUpdate {
+            sockets: Some(sockets),
+            cores: Some(cores),
+            ..Default::default()
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
 
-    async fn post_form<T: Serialize>(&self, path: &str, body: &T) -> Result<(), ProxmoxError> {
-        let url = self.endpoint(path);
-        debug!(method = "POST", %url, "Sending Proxmox form request");
-        let response = self
-            .client
-            .post(&url)
-            .header(reqwest::header::AUTHORIZATION, self.token.clone())
-            .form(body)
-            .send()
-            .await?;
-        let response = Self::ensure_success(response).await?;
-        debug!(method = "POST", %url, status = %response.status(), "Proxmox form request succeeded");
-        Ok(())
+    pub async fn set_vm_memory(&self, vmid: u64, memory_mib: u64) -> Result<(), ProxmoxError> {
+        if !is_valid_memory_mib(memory_mib) {
+            return Err(ProxmoxError::Api("invalid cpu/memory config".to_string()));
+        }
+        info!(vmid, memory_mib, "Updating VM memory");
+        let body = &VmConfigUpdate {
+            memory: Some(memory_mib),
+            ..Default::default()
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
 
-    async fn ensure_success(
-        response: reqwest::Response,
-    ) -> Result<reqwest::Response, ProxmoxError> {
-        if response.status().is_success() {
-            Ok(response)
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            warn!(%status, body = %body, "Proxmox request returned non-success status");
-            Err(ProxmoxError::Api(format!("status {status}, body {body}")))
+    /// Adds a cloud-init drive on `storage` as `ide2`, where Proxmox expects it.
+    pub async fn add_cloudinit_drive(&self, vmid: u64, storage: &str) -> Result<(), ProxmoxError> {
+        if !is_valid_storage_id(storage) {
+            return Err(ProxmoxError::Api(format!("invalid storage: {storage}")));
         }
+        info!(vmid, storage, "Adding cloud-init drive");
+        let drive = format!("{storage}:cloudinit");
+        let body = &VmConfigUpdate {
+            ide2: Some(&drive),
+            ..Default::default()
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
 
-    fn endpoint(&self, path: &str) -> String {
-        format!("{}/api2/json{}", self.base_url.trim_end_matches('/'), path)
+    /// Sets the cloud-init login. `password_hash` must already be a crypt(3)
+    /// hash; `ssh_keys` replace any keys configured before.
+    pub async fn set_cloudinit_user_data(
+        &self,
+        vmid: u64,
+        username: &str,
+        password_hash: Option<&str>,
+        ssh_keys: &[String],
+    ) -> Result<(), ProxmoxError> {
+        if !is_valid_cloudinit_user(username) {
+            return Err(ProxmoxError::Api(format!("invalid username: {username}")));
+        }
+        if ssh_keys.iter().any(|key| !is_valid_ssh_public_key(key)) {
+            return Err(ProxmoxError::Api("invalid SSH public key".to_string()));
+        }
+        info!(
+            vmid,
+            username,
+            password = password_hash.is_some(),
+            ssh_key_count = ssh_keys.len(),
+            "Setting cloud-init user"
+        );
+        let sshkeys = (!ssh_keys.is_empty()).then(|| encode_ssh_keys(ssh_keys));
+        let body = &VmConfigUpdate {
+            ciuser: Some(username),
+            cipassword: password_hash,
+            sshkeys: sshkeys.as_deref(),
+            ..Default::default()
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
     }
-}
 
-#[derive(Debug, Deserialize)]
-struct ApiResponse<T> {
-    data: T,
-}
+    /// Sets `ipconfig{interface_index}`: `ip` is `dhcp` or an address in CIDR
+    /// notation, with an optional gateway for static addresses.
+    pub async fn set_vm_ipconfig(
+        &self,
+        vmid: u64,
+        interface_index: u8,
+        ip: &str,
+        gw: Option<&str>,
+    ) -> Result<(), ProxmoxError> {
+        if interface_index > MAX_IPCONFIG_INDEX || !is_valid_ipconfig(ip, gw) {
+            return Err(ProxmoxError::Api(format!(
+                "invalid ipconfig{interface_index}: {ip}"
+            )));
+        }
+        info!(vmid, interface_index, ip, gw, "Setting VM IP config");
+        let key = format!("ipconfig{interface_index}");
+        let value = match gw {
+            Some(gw) => format!("ip={ip},gw={gw}"),
+            None => format!("ip={ip}"),
+        };
+        let body = &[(key, value)];
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/config"), body)
+                .await
+        })
+        .await
+    }
 
-#[derive(Debug, Deserialize)]
-struct ResourceVm {
-    vmid: u64,
-    name: Option<String>,
-    tags: Option<String>,
-    status: Option<String>,
-    node: Option<String>,
-    description: Option<String>,
-}
+    /// Applies `options` in order: cloud-init drive, login, then IP configs.
+    /// Everything is validated before the first change, but a failing call
+    /// leaves the earlier changes in place.
+    pub async fn provision_clone(
+        &self,
+        vmid: u64,
+        options: &ProvisionOptions,
+    ) -> Result<(), ProxmoxError> {
+        options.validate().map_err(ProxmoxError::Api)?;
+        info!(vmid, "Provisioning VM with cloud-init");
+        if let Some(storage) = &options.cloudinit_storage {
+            self.add_cloudinit_drive(vmid, storage).await?;
+        }
+        if let Some(user) = &options.user {
+            self.set_cloudinit_user_data(
+                vmid,
+                &user.username,
+                user.password_hash.as_deref(),
+                &user.ssh_keys,
+            )
+            .await?;
+        }
+        for ipconfig in &options.ipconfigs {
+            self.set_vm_ipconfig(
+                vmid,
+                ipconfig.interface_index,
+                &ipconfig.ip,
+                ipconfig.gw.as_deref(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
 
-#[derive(Debug, Deserialize)]
-struct StatusResponse {
-    status: String,
-}
+    /// Presses `keysym` (a QEMU key name such as `ctrl-alt-delete`) in the VM.
+    /// Keys outside [`ALLOWED_KEYSYMS`](crate::proxmox::types::ALLOWED_KEYSYMS)
+    /// fail without contacting Proxmox.
+    pub async fn send_vm_keyboard_input(
+        &self,
+        vmid: u64,
+        keysym: &str,
+    ) -> Result<(), ProxmoxError> {
+        if !is_allowed_keysym(keysym) {
+            return Err(ProxmoxError::Api(format!("key not allowed: {keysym}")));
+        }
+        info!(vmid, keysym, "Sending key to VM");
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(
+                &format!("/nodes/{node}/qemu/{vmid}/sendkey"),
+                &[("key", keysym)],
+            )
+            .await
+        })
+        .await
+    }
 
-#[derive(Debug, Serialize)]
-struct SnapshotRequest<'a> {
-    snapname: &'a str,
-}
+    /// Grows `disk` (e.g. `scsi0`) by `increment` (`+10G`, `+512M`) or to an
+    /// absolute size (`32G`). Malformed sizes fail without contacting Proxmox.
+    pub async fn resize_disk(
+        &self,
+        vmid: u64,
+        disk: &str,
+        increment: &str,
+    ) -> Result<(), ProxmoxError> {
+        if !is_valid_disk_size(increment) {
+            return Err(ProxmoxError::Api("invalid size format".to_string()));
+        }
+        info!(vmid, disk, size = increment, "Resizing VM disk");
+        let body = &ResizeDiskRequest {
+            disk,
+            size: increment,
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.put_form(&format!("/nodes/{node}/qemu/{vmid}/resize"), body)
+                .await
+        })
+        .await
+    }
 
-#[derive(Debug, Serialize)]
-struct CloneRequest<'a> {
-    newid: u64,
-    name: &'a str,
-    full: u8,
+    /// Downloads the disk image at `url` into `storage` and attaches it to
+    /// the first free `scsi{N}` slot. Waits for the download, as the image
+    /// can only be attached once it is complete, and returns its task.
+    pub async fn import_vm_disk(
+        &self,
+        vmid: u64,
+        storage: &str,
+        url: &str,
+    ) -> Result<TaskId, ProxmoxError> {
+        let import = self.start_disk_import(vmid, storage, url).await?;
+        self.finish_disk_import(vmid, &import).await?;
+        Ok(import.upid)
+    }
+
+    /// Starts downloading the disk image at `url` into `storage`, on the
+    /// node of `vmid`. [`Self::finish_disk_import`] attaches it.
+    pub async fn start_disk_import(
+        &self,
+        vmid: u64,
+        storage: &str,
+        url: &str,
+    ) -> Result<DiskImport, ProxmoxError> {
+        let filename = is_valid_import_url(url)
+            .then(|| import_disk_filename(url))
+            .flatten()
+            .ok_or_else(|| ProxmoxError::Api("invalid URL".to_string()))?;
+        if !is_valid_storage_id(storage) {
+            return Err(ProxmoxError::Api(format!("invalid storage: {storage}")));
+        }
+        info!(vmid, storage, url, "Importing VM disk");
+        let body = &DownloadUrlRequest {
+            content: "import",
+            filename,
+            url,
+        };
+        let (node, upid) = self
+            .on_vm_node(vmid, |node| async move {
+                let upid = self
+                    .post_form_task(
+                        &format!("/nodes/{node}/storage/{storage}/download-url"),
+                        body,
+                    )
+                    .await?;
+                Ok((node, upid))
+            })
+            .await?;
+        Ok(DiskImport {
+            node,
+            upid: TaskId(upid),
+            storage: storage.to_string(),
+            filename: filename.to_string(),
+        })
+    }
+
+    /// Waits for the download of `import` to finish, then attaches the image
+    /// to the first free `scsi{N}` slot of `vmid`, which is returned.
+    pub async fn finish_disk_import(
+        &self,
+        vmid: u64,
+        import: &DiskImport,
+    ) -> Result<String, ProxmoxError> {
+        let DiskImport {
+            node,
+            upid,
+            storage,
+            filename,
+        } = import;
+        self.wait_for_task(node, upid.as_str()).await?;
+
+        let config_path = format!("/nodes/{node}/qemu/{vmid}/config");
+        let config: serde_json::Map<String, serde_json::Value> = self.get(&config_path).await?;
+        let slot = (0..=MAX_SCSI_INDEX)
+            .map(|index| format!("scsi{index}"))
+            .find(|key| !config.contains_key(key))
+            .ok_or_else(|| ProxmoxError::Api("no free SCSI slot".to_string()))?;
+        info!(vmid, disk = %slot, filename, "Attaching imported disk");
+        let drive = format!("{storage}:0,import-from={storage}:import/{filename}");
+        self.put_form(&config_path, &[(slot.clone(), drive)])
+            .await?;
+        Ok(slot)
+    }
+
+    pub async fn get_cluster_status(&self) -> Result<ClusterStatus, ProxmoxError> {
+        debug!("Fetching cluster status");
+        let status: ClusterStatus = self.get("/cluster/status").await?;
+        debug!(cluster = %status.name, quorate = status.quorate, node_count = status.nodes.len(), "Fetched cluster status");
+        Ok(status)
+    }
+
+    pub async fn list_nodes(&self) -> Result<Vec<NodeInfo>, ProxmoxError> {
+        debug!("Fetching cluster nodes");
+        let nodes: Vec<NodeResponse> = self.get("/nodes").await?;
+        let nodes: Vec<NodeInfo> = nodes.into_iter().map(NodeInfo::from).collect();
+        debug!(node_count = nodes.len(), "Fetched cluster nodes");
+        Ok(nodes)
+    }
+
+    pub async fn node_status(&self, node: &str) -> Result<NodeStatus, ProxmoxError> {
+        debug!(node, "Fetching node status");
+        let path = format!("/nodes/{node}/status");
+        let status: NodeStatusResponse = self.get(&path).await?;
+        Ok(NodeStatus {
+            cpu: status.cpu,
+            mem: status.memory.used,
+            maxmem: status.memory.total,
+            uptime: status.uptime,
+            kernel_version: status.kversion,
+        })
+    }
+
+    pub async fn list_storages(&self, node: &str) -> Result<Vec<StorageInfo>, ProxmoxError> {
+        debug!(node, "Fetching storage pools");
+        let path = format!("/nodes/{node}/storage");
+        let storages: Vec<StorageResponse> = self.get(&path).await?;
+        let storages: Vec<StorageInfo> = storages.into_iter().map(StorageInfo::from).collect();
+        debug!(
+            node,
+            storage_count = storages.len(),
+            "Fetched storage pools"
+        );
+        Ok(storages)
+    }
+
+    pub async fn get_access_control_list(&self) -> Result<Vec<AclEntry>, ProxmoxError> {
+        debug!("Fetching access control list");
+        let entries: Vec<AclResponse> = self.get("/access/acl").await?;
+        let entries: Vec<AclEntry> = entries.into_iter().map(AclEntry::from).collect();
+        debug!(entry_count = entries.len(), "Fetched access control list");
+        Ok(entries)
+    }
+
+    /// Privileges of the configured token by path, e.g. `/vms` ->
+    /// `["VM.Audit", "VM.PowerMgmt"]`, each list sorted.
+    pub async fn get_current_permissions(
+        &self,
+    ) -> Result<HashMap<String, Vec<String>>, ProxmoxError> {
+        debug!("Fetching API token permissions");
+        let permissions: HashMap<String, HashMap<String, u8>> =
+            self.get("/access/permissions").await?;
+        Ok(permissions
+            .into_iter()
+            .map(|(path, privileges)| {
+                let mut privileges: Vec<String> = privileges
+                    .into_iter()
+                    .filter(|(_, granted)| *granted != 0)
+                    .map(|(privilege, _)| privilege)
+                    .collect();
+                privileges.sort();
+                (path, privileges)
+            })
+            .collect())
+    }
+
+    pub async fn list_pools(&self) -> Result<Vec<PoolInfo>, ProxmoxError> {
+        debug!("Fetching resource pools");
+        let pools: Vec<PoolResponse> = self.get("/pools").await?;
+        Ok(pools.into_iter().map(PoolInfo::from).collect())
+    }
+
+    pub async fn get_pool(&self, poolid: &str) -> Result<PoolDetail, ProxmoxError> {
+        debug!(poolid, "Fetching resource pool");
+        let pool: PoolDetailResponse = self.get(&format!("/pools/{poolid}")).await?;
+        Ok(PoolDetail {
+            poolid: poolid.to_string(),
+            members: pool
+                .members
+                .into_iter()
+                .filter(|member| member.type_ == "qemu")
+                .filter_map(|member| member.vmid)
+                .collect(),
+        })
+    }
+
+    /// Adds `vmid` to the pool, leaving its other members in place.
+    pub async fn add_vm_to_pool(&self, vmid: u64, poolid: &str) -> Result<(), ProxmoxError> {
+        info!(vmid, poolid, "Adding VM to resource pool");
+        self.put_form(
+            &format!("/pools/{poolid}"),
+            &PoolUpdateRequest {
+                vms: &vmid.to_string(),
+            },
+        )
+        .await
+    }
+
+    pub async fn storage_status(
+        &self,
+        node: &str,
+        storage: &str,
+    ) -> Result<StorageStatus, ProxmoxError> {
+        debug!(node, storage, "Fetching storage status");
+        let path = format!("/nodes/{node}/storage/{storage}/status");
+        let status: StorageResponse = self.get(&path).await?;
+        Ok(StorageStatus {
+            storage: status.storage.unwrap_or_else(|| storage.to_string()),
+            type_: status.storage_type,
+            avail: status.avail,
+            total: status.total,
+            used: status.used,
+            enabled: status.enabled != 0,
+            active: status.active != 0,
+            shared: status.shared != 0,
+            content: status
+                .content
+                .as_deref()
+                .unwrap_or("")
+                .split(',')
+                .map(str::trim)
+                .filter(|content| !content.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+
+    /// Returns up to the first 100 lines of a task's output.
+    pub async fn get_task_log(&self, node: &str, upid: &str) -> Result<Vec<String>, ProxmoxError> {
+        debug!(node, upid, "Fetching task log");
+        let path = format!("/nodes/{node}/tasks/{upid}/log");
+        let lines: Vec<TaskLogLine> = self.get_with_query(&path, &[("limit", 100)]).await?;
+        Ok(lines.into_iter().map(|line| line.t).collect())
+    }
+
+    /// Averaged CPU, memory and network history for `vmid`, oldest first.
+    pub async fn get_vm_rrd_data(
+        &self,
+        vmid: u64,
+        timeframe: RrdTimeframe,
+    ) -> Result<VmRrdData, ProxmoxError> {
+        debug!(vmid, timeframe = timeframe.as_str(), "Fetching VM RRD data");
+        let points: Vec<RrdDataPoint> = self
+            .on_vm_node(vmid, |node| async move {
+                self.get_with_query(
+                    &format!("/nodes/{node}/qemu/{vmid}/rrddata"),
+                    &[("timeframe", timeframe.as_str()), ("cf", "AVERAGE")],
+                )
+                .await
+            })
+            .await?;
+        Ok(VmRrdData::from(points))
+    }
+
+    /// Tasks recorded for `vmid` on its node, newest first.
+    pub async fn list_vm_tasks(
+        &self,
+        vmid: u64,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskSummary>, ProxmoxError> {
+        debug!(vmid, ?limit, "Fetching VM tasks");
+        let mut query = vec![("vmid", vmid.to_string())];
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        let tasks: Vec<TaskListEntry> = self
+            .on_vm_node(vmid, |node| {
+                let query = &query;
+                async move {
+                    self.get_with_query(&format!("/nodes/{node}/tasks"), query)
+                        .await
+                }
+            })
+            .await?;
+        Ok(tasks.into_iter().map(TaskSummary::from).collect())
+    }
+
+    /// Up to `limit` tasks of `vmid` started before `cursor`, newest first, for
+    /// paging through long task lists. Proxmox can only filter by start time,
+    /// so tasks sharing the cursor's second are skipped up to the cursor.
+    pub async fn list_vm_tasks_before(
+        &self,
+        vmid: u64,
+        cursor: &TaskId,
+        limit: u32,
+    ) -> Result<Vec<TaskSummary>, ProxmoxError> {
+        let until = cursor
+            .starttime()
+            .ok_or_else(|| ProxmoxError::Api(format!("invalid task cursor {cursor}")))?;
+        debug!(vmid, %cursor, limit, "Fetching VM tasks before cursor");
+        let limit = limit as usize;
+        let mut fetch = limit + 1;
+        loop {
+            let query = [
+                ("vmid", vmid.to_string()),
+                ("until", until.to_string()),
+                ("limit", fetch.to_string()),
+            ];
+            let tasks: Vec<TaskListEntry> = self
+                .on_vm_node(vmid, |node| {
+                    let query = &query;
+                    async move {
+                        self.get_with_query(&format!("/nodes/{node}/tasks"), query)
+                            .await
+                    }
+                })
+                .await?;
+            let exhausted = tasks.len() < fetch;
+            let mut tasks: Vec<TaskSummary> = tasks.into_iter().map(TaskSummary::from).collect();
+            match tasks.iter().position(|task| task.upid == cursor.as_str()) {
+                Some(index) => {
+                    tasks.drain(..=index);
+                }
+                // The cursor's task has rotated out of the log; older seconds only.
+                None => tasks.retain(|task| task.starttime < until),
+            }
+            if tasks.len() >= limit || exhausted {
+                tasks.truncate(limit);
+                return Ok(tasks);
+            }
+            fetch += limit.max(1);
+        }
+    }
+
+    /// Polls an asynchronous task until it stops. A non-`OK` exit status is
+    /// reported as [`ProxmoxError::TaskFailed`] together with the task log.
+    pub async fn wait_for_task(&self, node: &str, upid: &str) -> Result<(), ProxmoxError> {
+        debug!(node, upid, "Waiting for Proxmox task");
+        let path = format!("/nodes/{node}/tasks/{upid}/status");
+        let started = Instant::now();
+        loop {
+            let status: TaskStatusResponse = self.get(&path).await?;
+            if status.status == "stopped" {
+                // Whatever the task changed is visible in the inventory now.
+                self.invalidate_vm_cache();
+                let exit_status = status.exitstatus.unwrap_or_default();
+                if exit_status == "OK" {
+                    debug!(node, upid, "Proxmox task completed");
+                    return Ok(());
+                }
+                let log_lines = self.get_task_log(node, upid).await.unwrap_or_else(|err| {
+                    warn!(upid, error = %err, "Failed to fetch log for failed task");
+                    Vec::new()
+                });
+                warn!(upid, exit_status = %exit_status, "Proxmox task failed");
+                for line in &log_lines {
+                    warn!(upid, "task log: {line}");
+                }
+                return Err(ProxmoxError::TaskFailed {
+                    upid: upid.to_string(),
+                    exit_status,
+                    log_lines,
+                });
+            }
+            if started.elapsed() >= TASK_TIMEOUT {
+                warn!(upid, "Timed out waiting for Proxmox task");
+                return Err(ProxmoxError::Timeout);
+            }
+            tokio::time::sleep(TASK_POLL_INTERVAL).await;
+        }
+    }
+
+    pub async fn list_snapshots(&self, vmid: u64) -> Result<Vec<SnapshotInfo>, ProxmoxError> {
+        debug!(vmid, "Fetching VM snapshots");
+        let snapshots: Vec<SnapshotInfo> = self
+            .on_vm_node(vmid, |node| async move {
+                self.get(&format!("/nodes/{node}/qemu/{vmid}/snapshot"))
+                    .await
+            })
+            .await?;
+        // Proxmox reports the live VM state as a pseudo-snapshot named "current".
+        let snapshots: Vec<SnapshotInfo> = snapshots
+            .into_iter()
+            .filter(|snapshot| snapshot.name != "current")
+            .collect();
+        debug!(
+            vmid,
+            snapshot_count = snapshots.len(),
+            "Fetched VM snapshots"
+        );
+        Ok(snapshots)
+    }
+
+    pub async fn create_snapshot_named(
+        &self,
+        vmid: u64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<(), ProxmoxError> {
+        info!(vmid, snapshot = name, "Creating named VM snapshot");
+        let existing = self.list_snapshots(vmid).await?;
+        if existing.iter().any(|snapshot| snapshot.name == name) {
+            return Err(ProxmoxError::Conflict(format!(
+                "snapshot '{name}' already exists for VM {vmid}"
+            )));
+        }
+        let body = &SnapshotRequest {
+            snapname: name,
+            description,
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.post_form(&format!("/nodes/{node}/qemu/{vmid}/snapshot"), body)
+                .await
+        })
+        .await
+    }
+
+    pub async fn delete_snapshot(&self, vmid: u64, name: &str) -> Result<(), ProxmoxError> {
+        info!(vmid, snapshot = name, "Deleting VM snapshot");
+        self.ensure_snapshot_exists(vmid, name).await?;
+        self.on_vm_node(vmid, |node| async move {
+            self.delete(&format!("/nodes/{node}/qemu/{vmid}/snapshot/{name}"))
+                .await
+        })
+        .await
+    }
+
+    /// Reverts the VM to snapshot `name`. It runs asynchronously, so only
+    /// the task is returned.
+    pub async fn rollback_snapshot(&self, vmid: u64, name: &str) -> Result<TaskId, ProxmoxError> {
+        info!(vmid, snapshot = name, "Rolling back VM to snapshot");
+        self.ensure_snapshot_exists(vmid, name).await?;
+        let upid = self
+            .on_vm_node(vmid, |node| async move {
+                self.post_form_task(
+                    &format!("/nodes/{node}/qemu/{vmid}/snapshot/{name}/rollback"),
+                    &[("start", "0")],
+                )
+                .await
+            })
+            .await?;
+        info!(vmid, upid = %upid, "Snapshot rollback task started");
+        Ok(TaskId(upid))
+    }
+
+    /// Proxmox answers an unknown snapshot name with a generic error, so
+    /// check the list first to report it as [`ProxmoxError::NotFound`].
+    async fn ensure_snapshot_exists(&self, vmid: u64, name: &str) -> Result<(), ProxmoxError> {
+        let snapshots = self.list_snapshots(vmid).await?;
+        if !snapshots.iter().any(|snapshot| snapshot.name == name) {
+            return Err(ProxmoxError::NotFound(format!(
+                "snapshot '{name}' does not exist for VM {vmid}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Destroys the VM. With `purge` it is also removed from backup jobs,
+    /// replication and HA, and unreferenced disks are deleted.
+    pub async fn delete_vm(&self, vmid: u64, purge: bool) -> Result<TaskId, ProxmoxError> {
+        info!(vmid, purge, "Deleting VM");
+        let query: &[(&str, &str)] = if purge {
+            &[("purge", "1"), ("destroy-unreferenced-disks", "1")]
+        } else {
+            &[]
+        };
+        let upid = self
+            .on_vm_node(vmid, |node| async move {
+                self.delete_task(&format!("/nodes/{node}/qemu/{vmid}"), query)
+                    .await
+            })
+            .await?;
+        info!(vmid, upid = %upid, "VM deletion task started");
+        Ok(TaskId(upid))
+    }
+
+    /// Runs `command` inside the VM through the QEMU guest agent and waits for
+    /// it to exit, up to the configured agent exec timeout.
+    pub async fn execute_command_via_agent(
+        &self,
+        vmid: u64,
+        command: &str,
+        args: &[&str],
+    ) -> Result<AgentExecResult, ProxmoxError> {
+        info!(vmid, command, ?args, "Running command via guest agent");
+        let mut body = vec![("command", command)];
+        body.extend(args.iter().map(|arg| ("command", *arg)));
+        body.push(("input-data", ""));
+        let body = &body;
+        traced(
+            call_span!("execute_command_via_agent", vmid),
+            self.on_vm_node(vmid, |node| async move {
+                let exec: AgentExecResponse = self
+                    .post_form_data(&format!("/nodes/{node}/qemu/{vmid}/agent/exec"), body)
+                    .await?;
+                self.wait_for_agent_exec(&node, vmid, exec.pid).await
+            }),
+        )
+        .await
+        .inspect(|result| {
+            info!(
+                vmid,
+                exit_code = result.exit_code,
+                "Guest agent command exited"
+            );
+        })
+    }
+
+    async fn wait_for_agent_exec(
+        &self,
+        node: &str,
+        vmid: u64,
+        pid: u64,
+    ) -> Result<AgentExecResult, ProxmoxError> {
+        let path = format!("/nodes/{node}/qemu/{vmid}/agent/exec-status");
+        let started = Instant::now();
+        loop {
+            let status: AgentExecStatusResponse =
+                self.get_with_query(&path, &[("pid", pid)]).await?;
+            if status.exited {
+                return Ok(AgentExecResult {
+                    exit_code: status.exitcode.unwrap_or_default(),
+                    stdout: status.out_data.unwrap_or_default(),
+                    stderr: status.err_data.unwrap_or_default(),
+                });
+            }
+            if started.elapsed() >= self.agent_exec_timeout {
+                warn!(vmid, pid, "Timed out waiting for guest agent command");
+                return Err(ProxmoxError::Timeout);
+            }
+            tokio::time::sleep(AGENT_EXEC_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Runs `request` against the node hosting `vmid`. With a node hint the
+    /// `/cluster/resources` lookup is skipped; if the hinted node turns out not
+    /// to host the VM (e.g. after a migration) the node is resolved and the
+    /// request retried there.
+    async fn on_vm_node<T, F, Fut>(&self, vmid: u64, request: F) -> Result<T, ProxmoxError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, ProxmoxError>>,
+    {
+        let Some(hint) = self.node_hint.as_deref() else {
+            return request(self.node_for_vmid(vmid).await?).await;
+        };
+        debug!(vmid, node = hint, "Using node hint for VM");
+        match request(hint.to_string()).await {
+            Err(err @ (ProxmoxError::NotFound(_) | ProxmoxError::Api(_))) => {
+                let node = self.node_for_vmid(vmid).await?;
+                if node == hint {
+                    return Err(err);
+                }
+                warn!(vmid, hint, node = %node, "VM is not on the hinted node; using resolved node");
+                request(node).await
+            }
+            result => result,
+        }
+    }
+
+    async fn node_for_vmid(&self, vmid: u64) -> Result<String, ProxmoxError> {
+        debug!(vmid, "Resolving node for VM");
+        let resources: Vec<ResourceVm> = self.get("/cluster/resources?type=vm").await?;
+        resources
+            .into_iter()
+            .find(|vm| vm.vmid == vmid)
+            .and_then(|vm| vm.node)
+            .ok_or(ProxmoxError::MissingNode(vmid))
+            .inspect(|node| {
+                debug!(vmid, node = %node, "Resolved node for VM");
+            })
+    }
+
+    async fn post_status(&self, vmid: u64, action: &str) -> Result<(), ProxmoxError> {
+        self.on_vm_node(vmid, |node| async move {
+            self.post_status_on(&node, vmid, action).await
+        })
+        .await
+    }
+
+    async fn post_status_on(
+        &self,
+        node: &str,
+        vmid: u64,
+        action: &str,
+    ) -> Result<(), ProxmoxError> {
+        info!(vmid, node, action, "Sending VM status action");
+        self.post(&format!("/nodes/{node}/qemu/{vmid}/status/{action}"))
+            .await
+    }
+
+    async fn get_status(&self, node: &str, vmid: u64) -> Result<StatusResponse, ProxmoxError> {
+        self.get(&format!("/nodes/{node}/qemu/{vmid}/status/current"))
+            .await
+    }
+
+    /// Lowest vmid in `start..=end` not used by any VM or container, for
+    /// operators who reserve ranges per project.
+    pub async fn get_next_vmid_in_range(&self, start: u64, end: u64) -> Result<u64, ProxmoxError> {
+        debug!(start, end, "Looking for a free VMID in range");
+        let used: HashSet<u64> = self
+            .list_vms_fresh()
+            .await?
+            .iter()
+            .map(|vm| vm.vmid)
+            .collect();
+        lowest_free_vmid(&used, start, end)
+            .ok_or_else(|| ProxmoxError::Api("no free vmid in range".to_string()))
+            .inspect(|&id| {
+                debug!(next_vmid = id, start, end, "Picked free VMID in range");
+            })
+    }
+
+    async fn next_vmid(&self) -> Result<u64, ProxmoxError> {
+        debug!("Requesting next available VMID");
+        let nextid: String = self.get("/cluster/nextid").await?;
+        nextid
+            .parse()
+            .map_err(|err| ProxmoxError::Api(format!("Invalid next VMID: {err}")))
+            .inspect(|&id| {
+                debug!(next_vmid = id, "Received next VMID");
+            })
+    }
+
+    async fn allocate_vmid(&self, opts: &ForkOptions) -> Result<u64, ProxmoxError> {
+        match opts.vmid_range {
+            Some((start, end)) => self.get_next_vmid_in_range(start, end).await,
+            None => self.next_vmid().await,
+        }
+    }
+
+    async fn create_snapshot(&self, vmid: u64, snapshot: &str) -> Result<(), ProxmoxError> {
+        info!(vmid, snapshot, "Creating VM snapshot for fork");
+        let body = &SnapshotRequest {
+            snapname: snapshot,
+            description: None,
+        };
+        self.on_vm_node(vmid, |node| async move {
+            self.post_form(&format!("/nodes/{node}/qemu/{vmid}/snapshot"), body)
+                .await
+        })
+        .await
+    }
+
+    async fn clone_vm(
+        &self,
+        vmid: u64,
+        newid: u64,
+        opts: &ForkOptions,
+        snapshot: Option<&str>,
+    ) -> Result<(), ProxmoxError> {
+        info!(source_vmid = vmid, new_vmid = newid, new_name = %opts.name, snapshot, "Cloning VM");
+        let body = &CloneRequest {
+            newid,
+            name: &opts.name,
+            full: u8::from(opts.full_clone),
+            snapname: snapshot,
+            storage: opts.target_storage.as_deref(),
+            target: opts.target_node.as_deref(),
+            pool: opts.target_pool.as_deref(),
+        };
+        self.on_vm_node(vmid, |node| async move {
+            let upid = self
+                .post_form_task(&format!("/nodes/{node}/qemu/{vmid}/clone"), body)
+                .await?;
+            self.wait_for_task(&node, &upid).await
+        })
+        .await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ProxmoxError> {
+        self.get_with_query(path, &[] as &[(&str, &str)]).await
+    }
+
+    async fn get_with_query<T: DeserializeOwned, Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<T, ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("GET", &url);
+        debug!(method = "GET", %url, "Sending Proxmox request");
+        let response = self
+            .send(
+                "GET",
+                self.client
+                    .get(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .query(query),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "GET", %url, status = %response.status(), "Proxmox request succeeded");
+        let response: ApiResponse<T> = response.json().await?;
+        Ok(response.data)
+    }
+
+    async fn post(&self, path: &str) -> Result<(), ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("POST", &url);
+        debug!(method = "POST", %url, "Sending Proxmox request");
+        let response = self
+            .send(
+                "POST",
+                self.client
+                    .post(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone()),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "POST", %url, status = %response.status(), "Proxmox request succeeded");
+        Ok(())
+    }
+
+    async fn post_form<T: Serialize>(&self, path: &str, body: &T) -> Result<(), ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("POST", &url);
+        debug!(method = "POST", %url, "Sending Proxmox form request");
+        let response = self
+            .send(
+                "POST",
+                self.client
+                    .post(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .form(body),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "POST", %url, status = %response.status(), "Proxmox form request succeeded");
+        Ok(())
+    }
+
+    /// Posts a form and returns the response data.
+    async fn post_form_data<T: DeserializeOwned, B: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("POST", &url);
+        debug!(method = "POST", %url, "Sending Proxmox form request");
+        let response = self
+            .send(
+                "POST",
+                self.client
+                    .post(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .form(body),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "POST", %url, status = %response.status(), "Proxmox form request succeeded");
+        let response: ApiResponse<T> = response.json().await?;
+        Ok(response.data)
+    }
+
+    /// Posts a form to an endpoint that starts an asynchronous task, returning its UPID.
+    async fn post_form_task<T: Serialize>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<String, ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("POST", &url);
+        debug!(method = "POST", %url, "Sending Proxmox task request");
+        let response = self
+            .send(
+                "POST",
+                self.client
+                    .post(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .form(body),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        let response: ApiResponse<String> = response.json().await?;
+        debug!(method = "POST", %url, upid = %response.data, "Proxmox task started");
+        Ok(response.data)
+    }
+
+    async fn delete_task(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<String, ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("DELETE", &url);
+        debug!(method = "DELETE", %url, "Sending Proxmox task request");
+        let response = self
+            .send(
+                "DELETE",
+                self.client
+                    .delete(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .query(query),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        let response: ApiResponse<String> = response.json().await?;
+        debug!(method = "DELETE", %url, upid = %response.data, "Proxmox task started");
+        Ok(response.data)
+    }
+
+    async fn put_form<T: Serialize>(&self, path: &str, body: &T) -> Result<(), ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("PUT", &url);
+        debug!(method = "PUT", %url, "Sending Proxmox form request");
+        let response = self
+            .send(
+                "PUT",
+                self.client
+                    .put(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone())
+                    .form(body),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "PUT", %url, status = %response.status(), "Proxmox form request succeeded");
+        Ok(())
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ProxmoxError> {
+        let url = self.endpoint(path);
+        record_request("DELETE", &url);
+        debug!(method = "DELETE", %url, "Sending Proxmox request");
+        let response = self
+            .send(
+                "DELETE",
+                self.client
+                    .delete(&url)
+                    .header(reqwest::header::AUTHORIZATION, self.token.clone()),
+            )
+            .await?;
+        let response = Self::ensure_success(response).await?;
+        debug!(method = "DELETE", %url, status = %response.status(), "Proxmox request succeeded");
+        Ok(())
+    }
+
+    /// Sends `request`, timing it for `proxmox_api_call_duration_seconds`.
+    /// Anything but a GET invalidates the VM list cache, also after a failure
+    /// since the request may have reached Proxmox.
+    async fn send(
+        &self,
+        method: &'static str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ProxmoxError> {
+        let started = Instant::now();
+        let response = request.send().await;
+        if method != "GET" {
+            self.invalidate_vm_cache();
+        }
+        let status = response
+            .as_ref()
+            .ok()
+            .map(|response| response.status().as_u16());
+        observe_proxmox_call(method, status, started.elapsed());
+        Ok(response?)
+    }
+
+    async fn ensure_success(
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, ProxmoxError> {
+        if response.status().is_success() {
+            Span::current().record("result", response.status().as_u16());
+            Ok(response)
+        } else {
+            let status = response.status();
+            Span::current().record("result", status.as_u16());
+            let body = response.text().await.unwrap_or_default();
+            warn!(%status, body = %body, "Proxmox request returned non-success status");
+            Err(ProxmoxError::from_status(status, body))
+        }
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/api2/json{}", self.base_url.trim_end_matches('/'), path)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    data: T,
+}
+
+fn lowest_free_vmid(used: &HashSet<u64>, start: u64, end: u64) -> Option<u64> {
+    (start..=end).find(|vmid| !used.contains(vmid))
+}
+
+#[derive(Debug, Deserialize)]
+struct ResourceVm {
+    vmid: u64,
+    name: Option<String>,
+    tags: Option<String>,
+    status: Option<String>,
+    node: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    maxmem: Option<u64>,
+    #[serde(default)]
+    maxcpu: Option<f64>,
+    #[serde(default)]
+    disk: Option<u64>,
+    #[serde(default)]
+    uptime: Option<u64>,
+}
+
+impl From<ResourceVm> for VmInfo {
+    fn from(vm: ResourceVm) -> Self {
+        Self {
+            vmid: vm.vmid,
+            name: vm.name.unwrap_or_default(),
+            tags: parse_tags(vm.tags.as_deref()),
+            status: VmStatus::normalize(vm.status.as_deref()),
+            notes: vm.description.filter(|note| !note.trim().is_empty()),
+            node: vm.node,
+            maxmem: vm.maxmem,
+            maxcpu: vm.maxcpu,
+            disk: vm.disk,
+            uptime: vm.uptime,
+        }
+    }
+}
+
+/// Entry of `/nodes/{node}/qemu`, which has neither `node` nor `description`
+/// and reports the CPU count as `cpus`.
+#[derive(Debug, Deserialize)]
+struct NodeVm {
+    vmid: u64,
+    name: Option<String>,
+    tags: Option<String>,
+    status: Option<String>,
+    #[serde(default)]
+    maxmem: Option<u64>,
+    #[serde(default)]
+    cpus: Option<f64>,
+    #[serde(default)]
+    disk: Option<u64>,
+    #[serde(default)]
+    uptime: Option<u64>,
+}
+
+impl NodeVm {
+    fn into_vm_info(self, node: &str) -> VmInfo {
+        VmInfo {
+            vmid: self.vmid,
+            name: self.name.unwrap_or_default(),
+            tags: parse_tags(self.tags.as_deref()),
+            status: VmStatus::normalize(self.status.as_deref()),
+            notes: None,
+            node: Some(node.to_string()),
+            maxmem: self.maxmem,
+            maxcpu: self.cpus,
+            disk: self.disk,
+            uptime: self.uptime,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeResponse {
+    node: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    cpu: f64,
+    #[serde(default)]
+    mem: u64,
+    #[serde(default)]
+    maxmem: u64,
+}
+
+impl From<NodeResponse> for NodeInfo {
+    fn from(node: NodeResponse) -> Self {
+        Self {
+            node: node.node,
+            status: node.status.unwrap_or_else(|| "unknown".to_string()),
+            cpu: node.cpu,
+            mem: node.mem,
+            maxmem: node.maxmem,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeStatusResponse {
+    #[serde(default)]
+    cpu: f64,
+    memory: NodeMemory,
+    #[serde(default)]
+    uptime: u64,
+    #[serde(default)]
+    kversion: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeMemory {
+    used: u64,
+    total: u64,
+}
+
+/// Shared shape of `/nodes/{node}/storage` entries and `/storage/{storage}/status`.
+/// Proxmox omits usage figures for inactive pools and reports flags as 0/1.
+#[derive(Debug, Deserialize)]
+struct StorageResponse {
+    storage: Option<String>,
+    #[serde(rename = "type")]
+    storage_type: String,
+    #[serde(default)]
+    avail: u64,
+    #[serde(default)]
+    total: u64,
+    #[serde(default)]
+    used: u64,
+    #[serde(default = "enabled_by_default")]
+    enabled: u8,
+    #[serde(default)]
+    active: u8,
+    #[serde(default)]
+    shared: u8,
+    content: Option<String>,
+}
+
+fn enabled_by_default() -> u8 {
+    1
+}
+
+impl From<StorageResponse> for StorageInfo {
+    fn from(storage: StorageResponse) -> Self {
+        Self {
+            storage: storage.storage.unwrap_or_default(),
+            type_: storage.storage_type,
+            avail: storage.avail,
+            total: storage.total,
+            used: storage.used,
+            enabled: storage.enabled != 0,
+        }
+    }
+}
+
+pub struct ProxmoxClientBuilder {
+    base_url: String,
+    token_id: String,
+    token_secret: String,
+    insecure_ssl: bool,
+    cert_fingerprint: Option<String>,
+    node_hint: Option<String>,
+    agent_exec_timeout: Duration,
+    request_timeout: Duration,
+    vm_list_cache_ttl: Duration,
+    /// HTTP client of the client being reconfigured, with the settings it was built for.
+    existing: Option<(HttpSettings, reqwest::Client)>,
+}
+
+/// `insecure_ssl`, the pinned certificate fingerprint and the request timeout.
+type HttpSettings = (bool, Option<String>, Duration);
+
+impl ProxmoxClientBuilder {
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn token_id(mut self, token_id: impl Into<String>) -> Self {
+        self.token_id = token_id.into();
+        self
+    }
+
+    pub fn token_secret(mut self, token_secret: impl Into<String>) -> Self {
+        self.token_secret = token_secret.into();
+        self
+    }
+
+    pub fn insecure_ssl(mut self, insecure_ssl: bool) -> Self {
+        self.insecure_ssl = insecure_ssl;
+        self
+    }
+
+    /// Pins the SHA-256 fingerprint (hex, optionally colon-separated) of the
+    /// server certificate instead of verifying it against the system CAs.
+    /// Ignored when `insecure_ssl` is set.
+    pub fn cert_fingerprint(mut self, cert_fingerprint: Option<String>) -> Self {
+        self.cert_fingerprint = cert_fingerprint;
+        self
+    }
+
+    /// How long [`ProxmoxClient::execute_command_via_agent`] waits for the
+    /// guest command to exit.
+    pub fn agent_exec_timeout(mut self, agent_exec_timeout: Duration) -> Self {
+        self.agent_exec_timeout = agent_exec_timeout;
+        self
+    }
+
+    /// How long [`ProxmoxClient::list_vms`] reuses a fetched VM list. Zero,
+    /// the default, fetches it on every call.
+    pub fn vm_list_cache_ttl(mut self, vm_list_cache_ttl: Duration) -> Self {
+        self.vm_list_cache_ttl = vm_list_cache_ttl;
+        self
+    }
+
+    /// Caps each Proxmox HTTP request; an expired request fails with
+    /// [`ProxmoxError::Timeout`]. Task and guest agent waits poll, so they are
+    /// not bound by it.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<ProxmoxClient, ProxmoxError> {
+        let http_settings = (
+            self.insecure_ssl,
+            self.cert_fingerprint.clone(),
+            self.request_timeout,
+        );
+        let client = match self.existing {
+            Some((existing, client)) if existing == http_settings => {
+                debug!(base_url = %self.base_url, "Reusing Proxmox HTTP client");
+                client
+            }
+            _ => {
+                info!(
+                    base_url = %self.base_url,
+                    insecure_ssl = self.insecure_ssl,
+                    cert_pinned = self.cert_fingerprint.is_some(),
+                    "Creating Proxmox HTTP client"
+                );
+                let builder = reqwest::Client::builder().timeout(self.request_timeout);
+                match &self.cert_fingerprint {
+                    Some(fingerprint) if !self.insecure_ssl => {
+                        builder.use_preconfigured_tls(fingerprint::pinned_tls_config(fingerprint)?)
+                    }
+                    _ => builder.danger_accept_invalid_certs(self.insecure_ssl),
+                }
+                .build()?
+            }
+        };
+        Ok(ProxmoxClient {
+            token: format!("PVEAPIToken={}={}", self.token_id, self.token_secret),
+            base_url: self.base_url,
+            token_id: self.token_id,
+            token_secret: self.token_secret,
+            insecure_ssl: self.insecure_ssl,
+            cert_fingerprint: self.cert_fingerprint,
+            node_hint: self.node_hint,
+            agent_exec_timeout: self.agent_exec_timeout,
+            request_timeout: self.request_timeout,
+            vm_list_cache_ttl: self.vm_list_cache_ttl,
+            vm_list_cache: Arc::default(),
+            client,
+        })
+    }
+}
+
+/// Privileges checked at startup; without them listing or powering VMs fails.
+pub const REQUIRED_PRIVILEGES: &[&str] = &["VM.Audit", "VM.PowerMgmt"];
+
+/// [`REQUIRED_PRIVILEGES`] granted on none of the paths in `permissions`.
+fn missing_privileges(permissions: &HashMap<String, Vec<String>>) -> Vec<&'static str> {
+    REQUIRED_PRIVILEGES
+        .iter()
+        .copied()
+        .filter(|required| {
+            !permissions
+                .values()
+                .any(|privileges| privileges.iter().any(|granted| granted == required))
+        })
+        .collect()
+}
+
+/// Clones copy whole disks, so allow generously for slow storage.
+const TASK_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const AGENT_EXEC_TIMEOUT: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const AGENT_EXEC_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+struct AgentExecResponse {
+    pid: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AgentExecStatusResponse {
+    /// The guest agent reports this as `0`/`1`; accept booleans as well.
+    #[serde(deserialize_with = "int_or_bool")]
+    exited: bool,
+    exitcode: Option<i32>,
+    #[serde(rename = "out-data")]
+    out_data: Option<String>,
+    #[serde(rename = "err-data")]
+    err_data: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VncProxyResponse {
+    ticket: String,
+    #[serde(deserialize_with = "int_or_string")]
+    port: u16,
+    user: String,
+    upid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FirewallRuleResponse {
+    pos: u32,
+    action: String,
+    #[serde(rename = "type")]
+    type_: String,
+    /// Omitted for disabled rules.
+    #[serde(default, deserialize_with = "int_or_bool")]
+    enable: bool,
+    comment: Option<String>,
+    source: Option<String>,
+    dest: Option<String>,
+}
+
+impl From<FirewallRuleResponse> for FirewallRule {
+    fn from(rule: FirewallRuleResponse) -> Self {
+        Self {
+            pos: rule.pos,
+            action: rule.action,
+            type_: rule.type_,
+            enable: rule.enable,
+            comment: rule.comment.filter(|comment| !comment.is_empty()),
+            source: rule.source,
+            dest: rule.dest,
+        }
+    }
+}
+
+/// Proxmox returns some numbers, such as the VNC port, as strings.
+fn int_or_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum IntOrString {
+        Int(u16),
+        String(String),
+    }
+    match IntOrString::deserialize(deserializer)? {
+        IntOrString::Int(value) => Ok(value),
+        IntOrString::String(value) => value.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn int_or_bool<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum IntOrBool {
+        Int(u8),
+        Bool(bool),
+    }
+    Ok(match IntOrBool::deserialize(deserializer)? {
+        IntOrBool::Int(value) => value != 0,
+        IntOrBool::Bool(value) => value,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskStatusResponse {
+    status: String,
+    exitstatus: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskLogLine {
+    t: String,
+}
+
+/// One `rrddata` sample; Proxmox leaves out the values it has no data for.
+#[derive(Debug, Deserialize)]
+struct RrdDataPoint {
+    time: u64,
+    cpu: Option<f64>,
+    mem: Option<f64>,
+    netin: Option<f64>,
+    netout: Option<f64>,
+}
+
+impl From<Vec<RrdDataPoint>> for VmRrdData {
+    fn from(points: Vec<RrdDataPoint>) -> Self {
+        let mut data = VmRrdData::default();
+        for point in points {
+            data.timestamps.push(point.time);
+            data.cpu.push(point.cpu);
+            data.mem.push(point.mem);
+            data.netin.push(point.netin);
+            data.netout.push(point.netout);
+        }
+        data
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskListEntry {
+    upid: String,
+    #[serde(rename = "type")]
+    type_: String,
+    /// Absent while the task is still running.
+    status: Option<String>,
+    starttime: u64,
+    endtime: Option<u64>,
+    user: String,
+}
+
+impl From<TaskListEntry> for TaskSummary {
+    fn from(task: TaskListEntry) -> Self {
+        Self {
+            upid: task.upid,
+            type_: task.type_,
+            status: task.status.unwrap_or_else(|| "running".to_string()),
+            starttime: task.starttime,
+            endtime: task.endtime,
+            user: task.user,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VmConfigResponse {
+    name: Option<String>,
+    description: Option<String>,
+    tags: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusResponse {
+    status: String,
+    /// Seconds since the guest started.
+    #[serde(default)]
+    uptime: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotRequest<'a> {
     snapname: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct CloneRequest<'a> {
+    newid: u64,
+    name: &'a str,
+    full: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapname: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pool: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AclResponse {
+    path: String,
+    #[serde(rename = "type")]
+    type_: String,
+    ugid: String,
+    roleid: String,
+    propagate: u8,
+}
+
+impl From<AclResponse> for AclEntry {
+    fn from(entry: AclResponse) -> Self {
+        Self {
+            path: entry.path,
+            type_: entry.type_,
+            ugid: entry.ugid,
+            roleid: entry.roleid,
+            propagate: entry.propagate != 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolResponse {
+    poolid: String,
+    comment: Option<String>,
+}
+
+impl From<PoolResponse> for PoolInfo {
+    fn from(pool: PoolResponse) -> Self {
+        Self {
+            poolid: pool.poolid,
+            comment: pool.comment.filter(|comment| !comment.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolDetailResponse {
+    #[serde(default)]
+    members: Vec<PoolMemberResponse>,
+}
+
+/// Pools also hold storages, which carry no vmid.
+#[derive(Debug, Deserialize)]
+struct PoolMemberResponse {
+    #[serde(rename = "type")]
+    type_: String,
+    vmid: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PoolUpdateRequest<'a> {
+    vms: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ResizeDiskRequest<'a> {
+    disk: &'a str,
+    size: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct DownloadUrlRequest<'a> {
+    content: &'a str,
+    filename: &'a str,
+    url: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct VzdumpRequest<'a> {
+    vmid: u64,
+    storage: &'a str,
+    mode: &'a str,
+    compress: &'a str,
+    #[serde(rename = "notes-template", skip_serializing_if = "Option::is_none")]
+    notes_template: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackupContentResponse {
+    volid: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    ctime: u64,
+    #[serde(default)]
+    format: String,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+impl From<BackupContentResponse> for BackupInfo {
+    fn from(entry: BackupContentResponse) -> Self {
+        Self {
+            volid: entry.volid,
+            size: entry.size,
+            ctime: entry.ctime,
+            format: entry.format,
+            notes: entry.notes,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct VmConfigUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sockets: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cores: Option<u32>,
+    /// In MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ide2: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ciuser: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cipassword: Option<&'a str>,
+    /// Newline-separated keys, percent-encoded as Proxmox requires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sshkeys: Option<&'a str>,
+}
+
+/// Proxmox expects `sshkeys` percent-encoded on top of the form encoding,
+/// with spaces as `%20` rather than `+`.
+fn encode_ssh_keys(keys: &[String]) -> String {
+    keys.join("\n")
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proxmox_dummy::{spawn_dummy_server, DummyHandle, StorageEntry, VmEntry};
+
+    async fn dummy_client() -> (DummyHandle, ProxmoxClient) {
+        std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+        let handle = DummyHandle::new("pve");
+        handle
+            .insert_vm(VmEntry {
+                vmid: 100,
+                name: "alpha".to_string(),
+                tags: vec![],
+                status: proxmox_dummy::VmStatus::Running,
+                notes: None,
+            })
+            .await;
+        let (addr, _task) = spawn_dummy_server(handle.clone()).await.unwrap();
+        let client =
+            ProxmoxClient::new(format!("http://{addr}"), "token-id", "token-secret", false)
+                .unwrap();
+        (handle, client)
+    }
+
+    async fn resource_lookups(handle: &DummyHandle) -> usize {
+        handle
+            .requests()
+            .await
+            .iter()
+            .filter(|request| request.path == "/api2/json/cluster/resources")
+            .count()
+    }
+
+    async fn caching_client(client: &ProxmoxClient, ttl: Duration) -> ProxmoxClient {
+        client
+            .reconfigure()
+            .vm_list_cache_ttl(ttl)
+            .build()
+            .unwrap()
+            .with_node_hint("pve".to_string())
+    }
+
+    #[tokio::test]
+    async fn vm_list_is_cached_until_the_ttl_expires() {
+        let (handle, client) = dummy_client().await;
+        client.list_vms().await.unwrap();
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 2, "no TTL, no cache");
+
+        let client = caching_client(&client, Duration::from_millis(200)).await;
+        let vms = client.list_vms().await.unwrap();
+        assert_eq!(client.list_vms().await.unwrap(), vms);
+        assert_eq!(resource_lookups(&handle).await, 3);
+
+        client.list_vms_fresh().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 4);
+        client.clone().list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 4, "clones share the cache");
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 5);
+    }
+
+    #[tokio::test]
+    async fn power_actions_invalidate_the_vm_list_cache() {
+        let (handle, client) = dummy_client().await;
+        let client = caching_client(&client, Duration::from_secs(60)).await;
+        client.list_vms().await.unwrap();
+        client.invalidate_vm_cache();
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 2);
+
+        client.stop_vm(100).await.unwrap();
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 3);
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 3);
+
+        let err = client.start_vm(999).await.unwrap_err();
+        assert!(matches!(err, ProxmoxError::MissingNode(999)), "{err:?}");
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 5);
+    }
+
+    #[tokio::test]
+    async fn config_changes_invalidate_the_vm_list_cache() {
+        let (_handle, client) = dummy_client().await;
+        let client = caching_client(&client, Duration::from_secs(60)).await;
+        let vm = |vms: Vec<VmInfo>| vms.into_iter().find(|vm| vm.vmid == 100).unwrap();
+        assert!(vm(client.list_vms().await.unwrap()).notes.is_none());
+
+        client.set_vm_tags(100, &["lab"]).await.unwrap();
+        assert_eq!(vm(client.list_vms().await.unwrap()).tags, ["lab"]);
+        client.set_vm_notes(100, "scratch box").await.unwrap();
+        assert_eq!(
+            vm(client.list_vms().await.unwrap()).notes.as_deref(),
+            Some("scratch box")
+        );
+    }
+
+    #[tokio::test]
+    async fn listing_in_flight_across_an_invalidation_is_not_cached() {
+        let (handle, client) = dummy_client().await;
+        let client = caching_client(&client, Duration::from_secs(60)).await;
+        handle
+            .set_error_on_route(
+                "/api2/json/cluster/resources",
+                proxmox_dummy::InjectedError::Delay(Duration::from_millis(200)),
+            )
+            .await;
+
+        let listing = tokio::spawn({
+            let client = client.clone();
+            async move { client.list_vms_fresh().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.invalidate_vm_cache();
+        listing.await.unwrap().unwrap();
+
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 2);
+        client.list_vms().await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 2);
+    }
+
+    #[tokio::test]
+    async fn without_node_hint_resolves_node_per_call() {
+        let (handle, client) = dummy_client().await;
+        assert_eq!(client.vm_status(100).await.unwrap(), VmStatus::Running);
+        assert_eq!(resource_lookups(&handle).await, 1);
+    }
+
+    #[tokio::test]
+    async fn matching_node_hint_skips_lookup() {
+        let (handle, client) = dummy_client().await;
+        let client = client.with_node_hint("pve".to_string());
+        assert_eq!(client.vm_status(100).await.unwrap(), VmStatus::Running);
+        client.terminate_vm(100).await.unwrap();
+        assert_eq!(client.vm_status(100).await.unwrap(), VmStatus::Stopped);
+        assert_eq!(resource_lookups(&handle).await, 0);
+    }
+
+    #[tokio::test]
+    async fn mismatched_node_hint_falls_back_to_resolved_node() {
+        let (handle, client) = dummy_client().await;
+        let client = client.with_node_hint("other-node".to_string());
+        assert_eq!(client.vm_status(100).await.unwrap(), VmStatus::Running);
+        assert_eq!(resource_lookups(&handle).await, 1);
+        assert!(matches!(
+            client.vm_status(999).await,
+            Err(ProxmoxError::MissingNode(999))
+        ));
+    }
+
+    #[tokio::test]
+    async fn listed_node_is_used_without_a_second_lookup() {
+        let (handle, client) = dummy_client().await;
+        let vm = client
+            .list_vms()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|vm| vm.vmid == 100)
+            .unwrap();
+        let node = vm.node.expect("listing reports the node");
+        assert_eq!(resource_lookups(&handle).await, 1);
+
+        client
+            .terminate_vm_with_node_hint(100, &node)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.vm_status_with_node_hint(100, &node).await.unwrap(),
+            VmStatus::Stopped
+        );
+        client.start_vm_with_node_hint(100, &node).await.unwrap();
+        assert_eq!(resource_lookups(&handle).await, 1);
+    }
+
+    #[tokio::test]
+    async fn firewall_rules_can_be_toggled() {
+        let (handle, client) = dummy_client().await;
+        handle
+            .add_firewall_rule(
+                100,
+                proxmox_dummy::FirewallRuleEntry {
+                    action: "ACCEPT".to_string(),
+                    rule_type: "in".to_string(),
+                    enable: 1,
+                    comment: Some("ssh".to_string()),
+                    source: None,
+                    dest: None,
+                },
+            )
+            .await;
+        let rules = client.get_firewall_rules(100).await.unwrap();
+        assert_eq!(rules.len(), 1);
+        assert!(rules[0].enable);
+
+        client
+            .set_firewall_rule_enable(100, 0, false)
+            .await
+            .unwrap();
+        assert_eq!(handle.firewall_rules(100).await[0].enable, 0);
+        assert!(!client.get_firewall_rules(100).await.unwrap()[0].enable);
+        assert!(client.set_firewall_rule_enable(100, 1, true).await.is_err());
+    }
+
+    #[test]
+    fn ssh_keys_are_percent_encoded_one_per_line() {
+        let keys = [
+            "ssh-ed25519 AAAA+/= a@b".to_string(),
+            "ssh-rsa BBBB c".to_string(),
+        ];
+        assert_eq!(
+            encode_ssh_keys(&keys),
+            "ssh-ed25519%20AAAA%2B%2F%3D%20a%40b%0Assh-rsa%20BBBB%20c"
+        );
+    }
+
+    #[tokio::test]
+    async fn provision_clone_writes_cloudinit_config() {
+        let (handle, client) = dummy_client().await;
+        let options = ProvisionOptions {
+            cloudinit_storage: Some("local-lvm".to_string()),
+            user: Some(crate::proxmox::types::CloudInitUser {
+                username: "deploy".to_string(),
+                password_hash: None,
+                ssh_keys: vec!["ssh-ed25519 AAAA deploy".to_string()],
+            }),
+            ipconfigs: vec![crate::proxmox::types::IpConfig {
+                interface_index: 0,
+                ip: "10.0.0.5/24".to_string(),
+                gw: Some("10.0.0.1".to_string()),
+            }],
+        };
+        client.provision_clone(100, &options).await.unwrap();
+        let config = handle.cloudinit(100).await;
+        assert_eq!(config["ide2"], "local-lvm:cloudinit");
+        assert_eq!(config["ciuser"], "deploy");
+        assert_eq!(config["sshkeys"], "ssh-ed25519%20AAAA%20deploy");
+        assert_eq!(config["ipconfig0"], "ip=10.0.0.5/24,gw=10.0.0.1");
+        assert!(!config.contains_key("cipassword"));
+
+        assert!(client
+            .set_vm_ipconfig(100, 1, "10.0.0.5", None)
+            .await
+            .is_err());
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn client_calls_are_logged_in_spans() {
+        let (_handle, client) = dummy_client().await;
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        client.start_vm(100).await.unwrap();
+        client.list_vms().await.unwrap();
+        client.vm_status_with_node_hint(100, "pve").await.unwrap();
+        client.shutdown_vm_with_node_hint(100, "pve").await.unwrap();
+        client.reboot_vm(100).await.unwrap();
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let closed = |name: &str| {
+            output
+                .lines()
+                .find(|line| line.contains(&format!(" {name}{{")) && line.contains("close"))
+                .unwrap_or_else(|| panic!("no closed {name} span in:\n{output}"))
+                .to_string()
+        };
+        let start = closed("start_vm");
+        for field in [
+            "vmid=100",
+            "method=\"POST\"",
+            "/qemu/100/status/start",
+            "result=200",
+            "duration_ms=",
+        ] {
+            assert!(start.contains(field), "{field} missing from {start}");
+        }
+        assert!(closed("list_vms").contains("/cluster/resources"));
+        assert!(closed("reboot_vm").contains("/qemu/100/status/reboot"));
+
+        // Launch flows pass the node from the listing; those calls are traced too.
+        let status = closed("vm_status");
+        assert!(status.contains("node=\"pve\""), "{status}");
+        let shutdown = closed("shutdown_vm");
+        for field in [
+            "node=\"pve\"",
+            "/nodes/pve/qemu/100/status/shutdown",
+            "result=200",
+        ] {
+            assert!(shutdown.contains(field), "{field} missing from {shutdown}");
+        }
+    }
+
+    #[tokio::test]
+    async fn network_partition_fails_calls_until_it_ends() {
+        let (handle, client) = dummy_client().await;
+        handle.simulate_permanent_partition().await;
+        let err = client.list_vms().await.unwrap_err();
+        assert!(matches!(err, ProxmoxError::Unavailable), "{err:?}");
+        assert!(err.is_transient());
+
+        handle.end_partition().await;
+        assert_eq!(client.list_vms().await.unwrap().len(), 1);
+
+        handle
+            .simulate_network_partition(Duration::from_millis(200))
+            .await;
+        assert!(client.vm_status(100).await.is_err());
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(client.vm_status(100).await.unwrap(), VmStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn acl_and_token_permissions_are_read() {
+        let (handle, client) = dummy_client().await;
+        let permissions = client.get_current_permissions().await.unwrap();
+        assert!(permissions["/"].iter().any(|p| p == "VM.PowerMgmt"));
+        assert!(missing_privileges(&permissions).is_empty());
+
+        handle
+            .add_acl_entry(proxmox_dummy::AclEntry {
+                path: "/vms".to_string(),
+                type_: "token".to_string(),
+                ugid: "agent@pve!risky".to_string(),
+                roleid: "PVEVMUser".to_string(),
+                propagate: 1,
+            })
+            .await;
+        handle
+            .set_permissions("/vms", &["VM.PowerMgmt", "VM.Console"])
+            .await;
+        assert_eq!(
+            client.get_access_control_list().await.unwrap(),
+            vec![AclEntry {
+                path: "/vms".to_string(),
+                type_: "token".to_string(),
+                ugid: "agent@pve!risky".to_string(),
+                roleid: "PVEVMUser".to_string(),
+                propagate: true,
+            }]
+        );
+        let permissions = client.get_current_permissions().await.unwrap();
+        assert_eq!(
+            permissions,
+            HashMap::from([(
+                "/vms".to_string(),
+                vec!["VM.Console".to_string(), "VM.PowerMgmt".to_string()]
+            )])
+        );
+        assert_eq!(missing_privileges(&permissions), ["VM.Audit"]);
+        client.probe().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pools_are_listed_and_joined() {
+        let (handle, client) = dummy_client().await;
+        handle.add_pool("billing", Some("Team A")).await;
+        handle.add_pool("lab", None).await;
+
+        let mut pools = client.list_pools().await.unwrap();
+        pools.sort_by(|a, b| a.poolid.cmp(&b.poolid));
+        assert_eq!(
+            pools,
+            vec![
+                PoolInfo {
+                    poolid: "billing".to_string(),
+                    comment: Some("Team A".to_string()),
+                },
+                PoolInfo {
+                    poolid: "lab".to_string(),
+                    comment: None,
+                },
+            ]
+        );
+
+        client.add_vm_to_pool(100, "billing").await.unwrap();
+        let fork = client
+            .fork_vm(
+                100,
+                ForkOptions {
+                    target_pool: Some("billing".to_string()),
+                    ..ForkOptions::new("alpha-copy")
+                },
+            )
+            .await
+            .unwrap();
+        let pool = client.get_pool("billing").await.unwrap();
+        assert_eq!(pool.poolid, "billing");
+        assert_eq!(pool.members, vec![100, fork]);
+        assert!(matches!(
+            client.get_pool("missing").await,
+            Err(ProxmoxError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn cpu_and_memory_changes_are_validated_and_stored() {
+        let (handle, client) = dummy_client().await;
+        for (sockets, cores) in [(0, 4), (2, 0), (16, 33)] {
+            let err = client.set_vm_cpu(100, sockets, cores).await.unwrap_err();
+            assert!(
+                matches!(err, ProxmoxError::Api(ref message) if message == "invalid cpu/memory config")
+            );
+        }
+        let err = client.set_vm_memory(100, 15).await.unwrap_err();
+        assert!(matches!(err, ProxmoxError::Api(_)));
+        assert!(handle.requests().await.is_empty());
+
+        client.set_vm_cpu(100, 2, 4).await.unwrap();
+        client.set_vm_memory(100, 8192).await.unwrap();
+        let hardware = handle.hardware(100).await.unwrap();
+        assert_eq!(
+            (hardware.sockets, hardware.cores, hardware.memory),
+            (2, 4, 8192)
+        );
+    }
+
+    #[tokio::test]
+    async fn resize_disk_rejects_bad_sizes_without_a_request() {
+        let (handle, client) = dummy_client().await;
+        let err = client.resize_disk(100, "scsi0", "10M").await.unwrap_err();
+        assert!(matches!(err, ProxmoxError::Api(ref message) if message == "invalid size format"));
+        assert!(handle.requests().await.is_empty());
+
+        client.resize_disk(100, "scsi0", "+10G").await.unwrap();
+        let resizes = handle.disk_resizes().await;
+        assert_eq!(resizes.len(), 1);
+        assert_eq!(
+            (resizes[0].disk.as_str(), resizes[0].size.as_str()),
+            ("scsi0", "+10G")
+        );
+    }
+
+    #[tokio::test]
+    async fn import_vm_disk_downloads_then_attaches_the_image() {
+        let (handle, client) = dummy_client().await;
+        for url in [
+            "file:///var/lib/vz/images/disk.qcow2",
+            "https://example.com/",
+        ] {
+            let err = client.import_vm_disk(100, "local", url).await.unwrap_err();
+            assert!(matches!(err, ProxmoxError::Api(ref message) if message == "invalid URL"));
+        }
+        assert!(handle.requests().await.is_empty());
+
+        handle
+            .add_storage(StorageEntry {
+                storage: "local".to_string(),
+                storage_type: "dir".to_string(),
+                content: "images,import".to_string(),
+                avail: 1,
+                total: 1,
+                used: 0,
+                enabled: 1,
+                active: 1,
+                shared: 0,
+            })
+            .await;
+        let url = "https://images.example.com/noble.qcow2?mirror=1";
+        let upid = client.import_vm_disk(100, "local", url).await.unwrap();
+        assert!(upid.as_str().contains(":download:"), "{upid}");
+        let downloads = handle.downloads().await;
+        assert_eq!(downloads.len(), 1);
+        assert_eq!(
+            (
+                downloads[0].content.as_str(),
+                downloads[0].filename.as_str()
+            ),
+            ("import", "noble.qcow2")
+        );
+        assert_eq!(downloads[0].url, url);
+
+        client.import_vm_disk(100, "local", url).await.unwrap();
+        let disks = handle.disks(100).await;
+        assert_eq!(
+            disks.get("scsi0").map(String::as_str),
+            Some("local:0,import-from=local:import/noble.qcow2")
+        );
+        assert!(disks.contains_key("scsi1"), "{disks:?}");
+    }
+
+    #[test]
+    fn lowest_free_vmid_skips_used_ids() {
+        let dense: HashSet<u64> = (200..=298).collect();
+        assert_eq!(lowest_free_vmid(&dense, 200, 299), Some(299));
+        assert_eq!(lowest_free_vmid(&dense, 200, 298), None);
+
+        let sparse: HashSet<u64> = [100, 201, 250, 1000].into();
+        assert_eq!(lowest_free_vmid(&sparse, 200, 299), Some(200));
+        assert_eq!(lowest_free_vmid(&sparse, 201, 299), Some(202));
+        assert_eq!(lowest_free_vmid(&sparse, 250, 250), None);
+        assert_eq!(lowest_free_vmid(&HashSet::new(), 300, 200), None);
+    }
+
+    #[test]
+    fn rrd_response_deserializes_into_series() {
+        let body = r#"{"data":[
+            {"time":1700000000,"cpu":0.0123,"maxcpu":2,"mem":536870912,"maxmem":2147483648,
+             "netin":1024.5,"netout":2048.25,"diskread":0,"diskwrite":512,"disk":0,"maxdisk":34359738368},
+            {"time":1700000060,"cpu":0.05,"maxcpu":2,"mem":612368384,"maxmem":2147483648,
+             "netin":0,"netout":12.75,"diskread":0,"diskwrite":0,"disk":0,"maxdisk":34359738368},
+            {"time":1700000120}
+        ]}"#;
+        let response: ApiResponse<Vec<RrdDataPoint>> = serde_json::from_str(body).unwrap();
+        let data = VmRrdData::from(response.data);
+        assert_eq!(data.timestamps, vec![1700000000, 1700000060, 1700000120]);
+        assert_eq!(data.cpu, vec![Some(0.0123), Some(0.05), None]);
+        assert_eq!(data.mem, vec![Some(536870912.0), Some(612368384.0), None]);
+        assert_eq!(data.netin, vec![Some(1024.5), Some(0.0), None]);
+        assert_eq!(data.netout, vec![Some(2048.25), Some(12.75), None]);
+    }
+
+    #[tokio::test]
+    async fn fork_with_vmid_range_uses_lowest_free_id() {
+        let (handle, client) = dummy_client().await;
+        for vmid in [200, 201] {
+            handle
+                .insert_vm(VmEntry {
+                    vmid,
+                    name: format!("taken-{vmid}"),
+                    tags: vec![],
+                    status: proxmox_dummy::VmStatus::Stopped,
+                    notes: None,
+                })
+                .await;
+        }
+        assert_eq!(client.get_next_vmid_in_range(200, 299).await.unwrap(), 202);
+
+        let opts = ForkOptions {
+            vmid_range: Some((200, 299)),
+            ..ForkOptions::new("ranged")
+        };
+        assert_eq!(client.fork_vm(100, opts).await.unwrap(), 202);
+        assert!(handle
+            .requests()
+            .await
+            .iter()
+            .all(|request| request.path != "/api2/json/cluster/nextid"));
+
+        let err = client.get_next_vmid_in_range(200, 202).await.unwrap_err();
+        assert!(
+            matches!(err, ProxmoxError::Api(ref message) if message == "no free vmid in range")
+        );
+    }
 }
@@ -1,32 +1,840 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VmStatus {
     Running,
     Stopped,
+    Paused,
     Unknown,
 }
 
 impl VmStatus {
     pub fn normalize(raw: Option<&str>) -> Self {
-        match raw.unwrap_or("").to_lowercase().as_str() {
-            "running" => Self::Running,
-            "stopped" => Self::Stopped,
-            _ => Self::Unknown,
+        raw.unwrap_or("").parse().unwrap_or(Self::Unknown)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Stopped => "stopped",
+            Self::Paused => "paused",
+            Self::Unknown => "unknown",
         }
     }
 }
 
+impl fmt::Display for VmStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for VmStatus {
+    type Err = ParseVmStatusError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_lowercase().as_str() {
+            "running" => Ok(Self::Running),
+            "stopped" => Ok(Self::Stopped),
+            "paused" => Ok(Self::Paused),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(ParseVmStatusError(raw.to_string())),
+        }
+    }
+}
+
+impl Serialize for VmStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for VmStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVmStatusError(String);
+
+impl fmt::Display for ParseVmStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown VM status: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseVmStatusError {}
+
+/// Status changes listed per VM by [`status_transitions`]; older ones are dropped.
+pub const MAX_STATUS_TRANSITIONS: usize = 10;
+
+/// A VM moving from one status to another, `transitioned_at` in Unix
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VmStatusTransition {
+    pub from: VmStatus,
+    pub to: VmStatus,
+    pub transitioned_at: u64,
+}
+
+/// The last [`MAX_STATUS_TRANSITIONS`] status changes made by the successful
+/// power tasks in `tasks`, oldest first. `tasks` is a task list as Proxmox
+/// returns it, newest first; other task types are skipped.
+pub fn status_transitions(tasks: &[TaskSummary]) -> Vec<VmStatusTransition> {
+    let mut transitions: Vec<VmStatusTransition> = tasks
+        .iter()
+        .filter_map(TaskSummary::status_transition)
+        .take(MAX_STATUS_TRANSITIONS)
+        .collect();
+    transitions.reverse();
+    transitions
+}
+
+/// Smallest vmid Proxmox accepts; lower ids are reserved.
+pub const MIN_VMID: u64 = 100;
+pub const MAX_VMID: u64 = 999_999_999;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmIdError {
+    TooLow(u64),
+    TooHigh(u64),
+    NotNumeric(String),
+}
+
+impl fmt::Display for VmIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLow(id) => write!(f, "VM ID {id} is below the minimum of {MIN_VMID}"),
+            Self::TooHigh(id) => write!(f, "VM ID {id} is above the maximum of {MAX_VMID}"),
+            Self::NotNumeric(raw) => write!(f, "VM ID '{raw}' is not a number"),
+        }
+    }
+}
+
+impl std::error::Error for VmIdError {}
+
+pub fn validate_vmid(id: u64) -> Result<(), VmIdError> {
+    if id < MIN_VMID {
+        Err(VmIdError::TooLow(id))
+    } else if id > MAX_VMID {
+        Err(VmIdError::TooHigh(id))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn parse_vmid(raw: &str) -> Result<u64, VmIdError> {
+    let id = raw
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| VmIdError::NotNumeric(raw.to_string()))?;
+    validate_vmid(id)?;
+    Ok(id)
+}
+
+/// Proxmox's limit on vCPUs (sockets × cores) per VM.
+pub const MAX_VCPUS: u32 = 512;
+/// Smallest memory size Proxmox accepts for a VM.
+pub const MIN_MEMORY_MIB: u64 = 16;
+
+/// At least one socket and core, and no more than [`MAX_VCPUS`] in total.
+pub fn is_valid_cpu_topology(sockets: u32, cores: u32) -> bool {
+    sockets >= 1
+        && cores >= 1
+        && sockets
+            .checked_mul(cores)
+            .is_some_and(|vcpus| vcpus <= MAX_VCPUS)
+}
+
+pub fn is_valid_memory_mib(memory_mib: u64) -> bool {
+    memory_mib >= MIN_MEMORY_MIB
+}
+
+/// Disk resize sizes accepted by the API: `+{N}G` or `+{N}M` to grow by that
+/// much, or `{N}G` for an absolute size. Proxmox cannot shrink disks.
+pub fn is_valid_disk_size(size: &str) -> bool {
+    let (relative, amount) = match size.strip_prefix('+') {
+        Some(amount) => (true, amount),
+        None => (false, size),
+    };
+    let digits = match (amount.strip_suffix('G'), amount.strip_suffix('M')) {
+        (Some(digits), _) => digits,
+        (None, Some(digits)) if relative => digits,
+        _ => return false,
+    };
+    !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// QEMU key names the API may send to a VM: recovery combinations plus the
+/// keys needed to get past a login or boot prompt.
+pub const ALLOWED_KEYSYMS: &[&str] = &[
+    "ctrl-alt-delete",
+    "ctrl-alt-backspace",
+    "ctrl-c",
+    "ctrl-d",
+    "ctrl-z",
+    "alt-f4",
+    "alt-tab",
+    "ret",
+    "esc",
+    "tab",
+    "spc",
+    "backspace",
+    "up",
+    "down",
+    "left",
+    "right",
+    "f1",
+    "f2",
+    "f8",
+    "f10",
+    "f12",
+];
+
+pub fn is_allowed_keysym(keysym: &str) -> bool {
+    ALLOWED_KEYSYMS.contains(&keysym)
+}
+
+/// Highest NIC index Proxmox accepts for `ipconfig{N}`.
+pub const MAX_IPCONFIG_INDEX: u8 = 31;
+
+/// Proxmox storage IDs: a letter, then letters, digits, `-`, `_` or `.`.
+pub fn is_valid_storage_id(storage: &str) -> bool {
+    let mut bytes = storage.bytes();
+    bytes.next().is_some_and(|byte| byte.is_ascii_alphabetic())
+        && bytes.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
+
+/// Login names cloud-init can create: lowercase letters, digits, `_` and
+/// `-`, not starting with a digit or `-`.
+pub fn is_valid_cloudinit_user(username: &str) -> bool {
+    let mut bytes = username.bytes();
+    bytes
+        .next()
+        .is_some_and(|byte| byte.is_ascii_lowercase() || byte == b'_')
+        && bytes.all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || matches!(byte, b'_' | b'-')
+        })
+        && username.len() <= 32
+}
+
+/// `dhcp`, or an address with prefix length such as `10.0.0.5/24`. A gateway
+/// is only accepted with a static address of the same IP version.
+pub fn is_valid_ipconfig(ip: &str, gw: Option<&str>) -> bool {
+    if ip == "dhcp" {
+        return gw.is_none();
+    }
+    let Some((address, prefix)) = ip.split_once('/') else {
+        return false;
+    };
+    let (Ok(address), Ok(prefix)) = (address.parse::<IpAddr>(), prefix.parse::<u8>()) else {
+        return false;
+    };
+    let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+    prefix <= max_prefix
+        && gw.is_none_or(|gw| {
+            gw.parse::<IpAddr>()
+                .is_ok_and(|gw| gw.is_ipv4() == address.is_ipv4())
+        })
+}
+
+/// A single-line OpenSSH public key: key type, base64 blob and optional comment.
+pub fn is_valid_ssh_public_key(key: &str) -> bool {
+    !key.contains(['\n', '\r']) && key.split_whitespace().count() >= 2
+}
+
+/// Highest index Proxmox accepts for `scsi{N}` disks.
+pub const MAX_SCSI_INDEX: u8 = 30;
+
+/// Disk image URLs Proxmox can download: `http://` or `https://` with a host.
+pub fn is_valid_import_url(url: &str) -> bool {
+    url.strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+}
+
+/// Storage file name for an image downloaded from `url`: the last path
+/// segment, without query or fragment, if it is a plain file name.
+pub fn import_disk_filename(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let (_, filename) = path.split_once("://")?.1.split_once('/')?;
+    let filename = filename.rsplit('/').next().unwrap_or_default();
+    let mut bytes = filename.bytes();
+    (bytes
+        .next()
+        .is_some_and(|byte| byte.is_ascii_alphanumeric())
+        && bytes.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.')))
+    .then_some(filename)
+}
+
+/// Login cloud-init sets up on first boot. `password_hash` is a crypt(3)
+/// hash such as `$6$...`, so the plain password never reaches Proxmox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudInitUser {
+    pub username: String,
+    pub password_hash: Option<String>,
+    pub ssh_keys: Vec<String>,
+}
+
+/// Cloud-init network settings for the VM's `interface_index`th NIC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpConfig {
+    pub interface_index: u8,
+    /// `dhcp` or an address in CIDR notation.
+    pub ip: String,
+    pub gw: Option<String>,
+}
+
+/// Cloud-init setup applied by `provision_clone`, typically to a fresh clone
+/// of a template. Parts left unset keep the VM's current settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvisionOptions {
+    /// Storage to create the cloud-init drive on; none is added when unset.
+    pub cloudinit_storage: Option<String>,
+    pub user: Option<CloudInitUser>,
+    pub ipconfigs: Vec<IpConfig>,
+}
+
+impl ProvisionOptions {
+    /// Describes the first invalid setting, so callers can reject the
+    /// options before any of them are applied.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(storage) = &self.cloudinit_storage {
+            if !is_valid_storage_id(storage) {
+                return Err(format!("Invalid storage '{storage}'"));
+            }
+        }
+        if let Some(user) = &self.user {
+            if !is_valid_cloudinit_user(&user.username) {
+                return Err(format!("Invalid username '{}'", user.username));
+            }
+            if let Some(key) = user
+                .ssh_keys
+                .iter()
+                .find(|key| !is_valid_ssh_public_key(key))
+            {
+                return Err(format!("Invalid SSH public key '{key}'"));
+            }
+        }
+        let mut seen = HashSet::new();
+        for ipconfig in &self.ipconfigs {
+            if ipconfig.interface_index > MAX_IPCONFIG_INDEX {
+                return Err(format!(
+                    "Invalid interface {}; expected 0 to {MAX_IPCONFIG_INDEX}",
+                    ipconfig.interface_index
+                ));
+            }
+            if !seen.insert(ipconfig.interface_index) {
+                return Err(format!(
+                    "Interface {} is configured twice",
+                    ipconfig.interface_index
+                ));
+            }
+            if !is_valid_ipconfig(&ipconfig.ip, ipconfig.gw.as_deref()) {
+                return Err(format!(
+                    "Invalid IP config for interface {}; expected dhcp or an address like 10.0.0.5/24 with a matching gateway",
+                    ipconfig.interface_index
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct VmInfo {
     pub vmid: u64,
     pub name: String,
     pub tags: Vec<String>,
     pub status: VmStatus,
     pub notes: Option<String>,
+    pub node: Option<String>,
+    pub maxmem: Option<u64>,
+    pub maxcpu: Option<f64>,
+    pub disk: Option<u64>,
+    pub uptime: Option<u64>,
+}
+
+impl VmInfo {
+    /// A stopped VM without tags, notes, node or resource figures.
+    pub fn builder(vmid: u64, name: &str) -> VmInfoBuilder {
+        VmInfoBuilder {
+            vm: VmInfo {
+                vmid,
+                name: name.to_string(),
+                tags: Vec::new(),
+                status: VmStatus::Stopped,
+                notes: None,
+                node: None,
+                maxmem: None,
+                maxcpu: None,
+                disk: None,
+                uptime: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VmInfoBuilder {
+    vm: VmInfo,
+}
+
+impl VmInfoBuilder {
+    pub fn vmid(mut self, vmid: u64) -> Self {
+        self.vm.vmid = vmid;
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.vm.name = name.to_string();
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.vm.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    pub fn status(mut self, status: VmStatus) -> Self {
+        self.vm.status = status;
+        self
+    }
+
+    pub fn notes(mut self, notes: Option<&str>) -> Self {
+        self.vm.notes = notes.map(String::from);
+        self
+    }
+
+    pub fn node(mut self, node: Option<&str>) -> Self {
+        self.vm.node = node.map(String::from);
+        self
+    }
+
+    pub fn build(self) -> VmInfo {
+        self.vm
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub snaptime: Option<u64>,
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+/// Where and how `fork_vm` clones the source VM. Unset targets keep Proxmox's
+/// defaults (same node, storage and pool as the source).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkOptions {
+    pub name: String,
+    pub target_storage: Option<String>,
+    pub target_node: Option<String>,
+    /// Resource pool the clone joins, e.g. the caller's billing pool.
+    pub target_pool: Option<String>,
+    pub full_clone: bool,
+    /// Inclusive range to pick the new vmid from instead of `/cluster/nextid`.
+    pub vmid_range: Option<(u64, u64)>,
+}
+
+impl ForkOptions {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target_storage: None,
+            target_node: None,
+            target_pool: None,
+            full_clone: true,
+            vmid_range: None,
+        }
+    }
+}
+
+/// The subset of `/nodes/{node}/qemu/{vmid}/config` the agent cares about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmConfig {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeInfo {
+    pub node: String,
+    pub status: String,
+    pub cpu: f64,
+    pub mem: u64,
+    pub maxmem: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStatus {
+    pub cpu: f64,
+    pub mem: u64,
+    pub maxmem: u64,
+    pub uptime: u64,
+    pub kernel_version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageInfo {
+    pub storage: String,
+    pub type_: String,
+    pub avail: u64,
+    pub total: u64,
+    pub used: u64,
+    pub enabled: bool,
+}
+
+/// Detailed status of a single storage pool; a superset of [`StorageInfo`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageStatus {
+    pub storage: String,
+    pub type_: String,
+    pub avail: u64,
+    pub total: u64,
+    pub used: u64,
+    pub enabled: bool,
+    pub active: bool,
+    pub shared: bool,
+    pub content: Vec<String>,
+}
+
+/// A resource pool as listed by `/pools`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolInfo {
+    pub poolid: String,
+    pub comment: Option<String>,
+}
+
+/// An entry from `/access/acl`: `roleid` granted to `ugid` on `path`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AclEntry {
+    pub path: String,
+    /// `user`, `group` or `token`.
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// User, group or token id, e.g. `root@pam!agent`.
+    pub ugid: String,
+    pub roleid: String,
+    /// Whether the role also applies below `path`.
+    pub propagate: bool,
+}
+
+/// A resource pool with the vmids of its member VMs; storage members are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolDetail {
+    pub poolid: String,
+    pub members: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ProxmoxVersion {
+    pub release: String,
+    pub version: String,
+    pub repoid: String,
+}
+
+/// Span of RRD history to fetch; Proxmox picks the sample resolution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RrdTimeframe {
+    #[default]
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl RrdTimeframe {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        }
+    }
+}
+
+impl FromStr for RrdTimeframe {
+    type Err = ParseRrdTimeframeError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_lowercase().as_str() {
+            "hour" => Ok(Self::Hour),
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            _ => Err(ParseRrdTimeframeError(raw.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRrdTimeframeError(String);
+
+impl fmt::Display for ParseRrdTimeframeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown RRD timeframe: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRrdTimeframeError {}
+
+/// Averaged VM metrics as parallel series, one entry per sample. Samples
+/// without data (e.g. while the VM was stopped) are `None`. `cpu` is a
+/// fraction of the VM's cores, `mem` is in bytes and `netin`/`netout` are
+/// bytes per second.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VmRrdData {
+    pub timestamps: Vec<u64>,
+    pub cpu: Vec<Option<f64>>,
+    pub mem: Vec<Option<f64>>,
+    pub netin: Vec<Option<f64>>,
+    pub netout: Vec<Option<f64>>,
+}
+
+/// An entry from a node's task list. `status` is the exit status once the
+/// task has finished and `running` before that.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskSummary {
+    pub upid: String,
+    pub type_: String,
+    pub status: String,
+    pub starttime: u64,
+    pub endtime: Option<u64>,
+    pub user: String,
+}
+
+impl TaskSummary {
+    /// The status change this task made, if it is a power task that succeeded.
+    fn status_transition(&self) -> Option<VmStatusTransition> {
+        if self.status != "OK" {
+            return None;
+        }
+        let (from, to) = match self.type_.as_str() {
+            "qmstart" => (VmStatus::Stopped, VmStatus::Running),
+            "qmstop" | "qmshutdown" => (VmStatus::Running, VmStatus::Stopped),
+            "qmpause" => (VmStatus::Running, VmStatus::Paused),
+            // Hibernation: the RAM is saved to disk and the VM stops.
+            "qmsuspend" => (VmStatus::Running, VmStatus::Stopped),
+            "qmresume" => (VmStatus::Paused, VmStatus::Running),
+            _ => return None,
+        };
+        Some(VmStatusTransition {
+            from,
+            to,
+            transitioned_at: self.endtime.unwrap_or(self.starttime) * 1000,
+        })
+    }
+}
+
+/// UPID of an asynchronous Proxmox task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct TaskId(pub String);
+
+impl TaskId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Unix start time encoded in the UPID
+    /// (`UPID:node:pid:pstart:starttime:type:id:user:`), if it is well formed.
+    pub fn starttime(&self) -> Option<u64> {
+        let mut fields = self.0.strip_prefix("UPID:")?.split(':');
+        u64::from_str_radix(fields.nth(3)?, 16).ok()
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A disk image download into `storage`, still to be attached to the VM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskImport {
+    pub node: String,
+    pub upid: TaskId,
+    pub storage: String,
+    pub filename: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Live backup; the guest keeps running.
+    #[default]
+    Snapshot,
+    Suspend,
+    Stop,
+}
+
+impl BackupMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Snapshot => "snapshot",
+            Self::Suspend => "suspend",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupCompression {
+    None,
+    Lzo,
+    Gzip,
+    #[default]
+    Zstd,
+}
+
+impl BackupCompression {
+    /// Value of the vzdump `compress` parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "0",
+            Self::Lzo => "lzo",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupOptions {
+    pub storage: String,
+    pub mode: BackupMode,
+    pub compress: BackupCompression,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    pub volid: String,
+    pub size: u64,
+    pub ctime: u64,
+    pub format: String,
+    pub notes: Option<String>,
+}
+
+/// Outcome of a command run inside a VM by the QEMU guest agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentExecResult {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// VNC proxy ticket for a VM's console. Proxmox only accepts it for a short
+/// while after issuing it, so it must be handed to noVNC right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleTicket {
+    /// Node hosting the VM, needed by the noVNC URL.
+    pub node: String,
+    pub ticket: String,
+    pub port: u16,
+    pub user: String,
+    pub upid: String,
+}
+
+/// A rule from a VM's Proxmox firewall. `pos` is the rule's index in the VM's
+/// rule list, which Proxmox uses to address it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallRule {
+    pub pos: u32,
+    /// `ACCEPT`, `DROP`, `REJECT` or a security group name.
+    pub action: String,
+    /// `in`, `out` or `group`.
+    pub type_: String,
+    pub enable: bool,
+    pub comment: Option<String>,
+    pub source: Option<String>,
+    pub dest: Option<String>,
+}
+
+/// Cluster health from `/cluster/status`. A standalone node has no cluster
+/// entry; it is reported under its own name and counts as quorate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterStatus {
+    pub name: String,
+    pub quorate: bool,
+    pub nodes: Vec<ClusterNodeStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNodeStatus {
+    pub name: String,
+    pub online: bool,
+    pub local: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClusterStatusEntry {
+    #[serde(rename = "type")]
+    type_: String,
+    name: String,
+    #[serde(default)]
+    quorate: Option<u8>,
+    #[serde(default)]
+    online: Option<u8>,
+    #[serde(default)]
+    local: Option<u8>,
+}
+
+impl<'de> Deserialize<'de> for ClusterStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<ClusterStatusEntry>::deserialize(deserializer)?;
+        let mut cluster = None;
+        let mut nodes = Vec::new();
+        for entry in entries {
+            match entry.type_.as_str() {
+                "cluster" => cluster = Some((entry.name, entry.quorate == Some(1))),
+                "node" => nodes.push(ClusterNodeStatus {
+                    name: entry.name,
+                    online: entry.online == Some(1),
+                    local: entry.local == Some(1),
+                }),
+                _ => {}
+            }
+        }
+        let (name, quorate) = cluster.unwrap_or_else(|| {
+            let local = nodes
+                .iter()
+                .find(|node| node.local)
+                .or(nodes.first())
+                .map(|node| node.name.clone())
+                .unwrap_or_default();
+            (local, true)
+        });
+        Ok(Self {
+            name,
+            quorate,
+            nodes,
+        })
+    }
 }
 
 pub fn parse_tags(raw: Option<&str>) -> Vec<String> {
     raw.unwrap_or("")
-        .split(|ch| ch == ';' || ch == ',')
+        .split([';', ','])
         .map(str::trim)
         .filter(|tag| !tag.is_empty())
         .map(String::from)
@@ -37,6 +845,98 @@ pub fn parse_tags(raw: Option<&str>) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn vm_info_builder_defaults_to_a_bare_stopped_vm() {
+        let vm = VmInfo::builder(101, "desktop").build();
+        assert_eq!(vm.vmid, 101);
+        assert_eq!(vm.name, "desktop");
+        assert_eq!(vm.status, VmStatus::Stopped);
+        assert!(vm.tags.is_empty());
+        assert_eq!((vm.notes, vm.node, vm.maxmem), (None, None, None));
+
+        let vm = VmInfo::builder(101, "desktop")
+            .vmid(102)
+            .name("laptop")
+            .tags(&["fallback", "gpu"])
+            .status(VmStatus::Running)
+            .notes(Some("spare"))
+            .node(Some("pve2"))
+            .build();
+        assert_eq!(vm.vmid, 102);
+        assert_eq!(vm.name, "laptop");
+        assert_eq!(vm.tags, ["fallback", "gpu"]);
+        assert_eq!(vm.status, VmStatus::Running);
+        assert_eq!(vm.notes.as_deref(), Some("spare"));
+        assert_eq!(vm.node.as_deref(), Some("pve2"));
+    }
+
+    #[test]
+    fn status_transitions_keep_the_latest_power_changes() {
+        let task = |type_: &str, status: &str, starttime: u64| TaskSummary {
+            upid: format!("UPID:pve:00000001:00000000:{starttime:08X}:{type_}:101:root@pam:"),
+            type_: type_.to_string(),
+            status: status.to_string(),
+            starttime,
+            endtime: Some(starttime + 1),
+            user: "root@pam".to_string(),
+        };
+        let mut tasks = vec![
+            task("qmpause", "OK", 200),
+            task("vzdump", "OK", 150),
+            task("qmstart", "TASK ERROR: start failed", 120),
+        ];
+        tasks.extend((0..MAX_STATUS_TRANSITIONS as u64).map(|n| task("qmstart", "OK", 100 - n)));
+
+        let transitions = status_transitions(&tasks);
+        assert_eq!(transitions.len(), MAX_STATUS_TRANSITIONS);
+        let last = transitions.last().unwrap();
+        assert_eq!(
+            (&last.from, &last.to, last.transitioned_at),
+            (&VmStatus::Running, &VmStatus::Paused, 201_000)
+        );
+        assert_eq!(transitions[0].transitioned_at, 93_000);
+    }
+
+    #[test]
+    fn status_transitions_map_each_power_task() {
+        let cases = [
+            ("qmstart", VmStatus::Stopped, VmStatus::Running),
+            ("qmstop", VmStatus::Running, VmStatus::Stopped),
+            ("qmshutdown", VmStatus::Running, VmStatus::Stopped),
+            ("qmpause", VmStatus::Running, VmStatus::Paused),
+            ("qmsuspend", VmStatus::Running, VmStatus::Stopped),
+            ("qmresume", VmStatus::Paused, VmStatus::Running),
+        ];
+        for (type_, from, to) in cases {
+            let task = TaskSummary {
+                upid: format!("UPID:pve:00000001:00000000:00000064:{type_}:101:root@pam:"),
+                type_: type_.to_string(),
+                status: "OK".to_string(),
+                starttime: 100,
+                endtime: None,
+                user: "root@pam".to_string(),
+            };
+            let transitions = status_transitions(&[task]);
+            assert_eq!(
+                transitions,
+                [VmStatusTransition {
+                    from,
+                    to,
+                    transitioned_at: 100_000
+                }],
+                "{type_}"
+            );
+        }
+    }
+
+    #[test]
+    fn task_id_starttime_is_read_from_the_upid() {
+        let upid = TaskId("UPID:pve:0000ABCD:00000000:6500A1B2:qmstart:101:root@pam:".into());
+        assert_eq!(upid.starttime(), Some(0x6500A1B2));
+        assert_eq!(TaskId("UPID:pve:1".into()).starttime(), None);
+        assert_eq!(TaskId("qmstart".into()).starttime(), None);
+    }
+
     #[test]
     fn parse_tags_splits_on_semicolons() {
         let tags = parse_tags(Some("alpha;beta; gamma "));
@@ -60,7 +960,234 @@ mod tests {
     fn normalize_status_handles_known_states() {
         assert_eq!(VmStatus::normalize(Some("running")), VmStatus::Running);
         assert_eq!(VmStatus::normalize(Some("stopped")), VmStatus::Stopped);
-        assert_eq!(VmStatus::normalize(Some("paused")), VmStatus::Unknown);
+        assert_eq!(VmStatus::normalize(Some("paused")), VmStatus::Paused);
+        assert_eq!(VmStatus::normalize(Some("prelaunch")), VmStatus::Unknown);
         assert_eq!(VmStatus::normalize(None), VmStatus::Unknown);
     }
+
+    #[test]
+    fn from_str_is_case_insensitive() {
+        assert_eq!("RUNNING".parse(), Ok(VmStatus::Running));
+        assert_eq!(" Stopped ".parse(), Ok(VmStatus::Stopped));
+        assert_eq!("unknown".parse(), Ok(VmStatus::Unknown));
+    }
+
+    #[test]
+    fn from_str_rejects_unknown_values() {
+        assert!("".parse::<VmStatus>().is_err());
+        assert!("runningx".parse::<VmStatus>().is_err());
+    }
+
+    #[test]
+    fn status_round_trips_through_serde() {
+        for status in [
+            VmStatus::Running,
+            VmStatus::Stopped,
+            VmStatus::Paused,
+            VmStatus::Unknown,
+        ] {
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(json, format!("\"{status}\""));
+            assert_eq!(serde_json::from_str::<VmStatus>(&json).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn cluster_status_parses_proxmox_response() {
+        let body = r#"[
+            {"type":"cluster","id":"cluster","name":"homelab","nodes":2,"quorate":1,"version":4},
+            {"type":"node","id":"node/pve1","name":"pve1","nodeid":1,"online":1,"local":1,"ip":"10.0.0.1","level":""},
+            {"type":"node","id":"node/pve2","name":"pve2","nodeid":2,"online":0,"local":0,"ip":"10.0.0.2","level":""}
+        ]"#;
+        let status: ClusterStatus = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            status,
+            ClusterStatus {
+                name: "homelab".to_string(),
+                quorate: true,
+                nodes: vec![
+                    ClusterNodeStatus {
+                        name: "pve1".to_string(),
+                        online: true,
+                        local: true,
+                    },
+                    ClusterNodeStatus {
+                        name: "pve2".to_string(),
+                        online: false,
+                        local: false,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn cluster_status_treats_standalone_node_as_quorate() {
+        let body = r#"[{"type":"node","id":"node/pve","name":"pve","online":1,"local":1}]"#;
+        let status: ClusterStatus = serde_json::from_str(body).unwrap();
+        assert_eq!(status.name, "pve");
+        assert!(status.quorate);
+        assert_eq!(status.nodes.len(), 1);
+    }
+
+    #[test]
+    fn validate_vmid_checks_proxmox_range() {
+        assert_eq!(validate_vmid(99), Err(VmIdError::TooLow(99)));
+        assert_eq!(validate_vmid(100), Ok(()));
+        assert_eq!(validate_vmid(999_999_999), Ok(()));
+        assert_eq!(
+            validate_vmid(1_000_000_000),
+            Err(VmIdError::TooHigh(1_000_000_000))
+        );
+    }
+
+    #[test]
+    fn parse_vmid_rejects_non_numeric_and_out_of_range() {
+        assert_eq!(parse_vmid(" 100 "), Ok(100));
+        assert_eq!(parse_vmid("999999999"), Ok(999_999_999));
+        assert_eq!(parse_vmid("99"), Err(VmIdError::TooLow(99)));
+        assert_eq!(
+            parse_vmid("1000000000"),
+            Err(VmIdError::TooHigh(1_000_000_000))
+        );
+        assert_eq!(
+            parse_vmid("abc"),
+            Err(VmIdError::NotNumeric("abc".to_string()))
+        );
+        assert!(matches!(parse_vmid("-5"), Err(VmIdError::NotNumeric(_))));
+    }
+
+    #[test]
+    fn keysym_allowlist_accepts_only_known_key_names() {
+        for keysym in ["ctrl-alt-delete", "ctrl-c", "ret", "esc", "f8"] {
+            assert!(is_allowed_keysym(keysym), "{keysym}");
+        }
+        for keysym in [
+            "",
+            "CTRL-ALT-DELETE",
+            " ret",
+            "ctrl-alt-del",
+            "a",
+            "ctrl-alt-delete;ret",
+            "shift-ctrl-alt-delete",
+        ] {
+            assert!(!is_allowed_keysym(keysym), "{keysym}");
+        }
+    }
+
+    #[test]
+    fn ipconfig_accepts_dhcp_or_cidr_with_matching_gateway() {
+        for (ip, gw) in [
+            ("dhcp", None),
+            ("10.0.0.5/24", None),
+            ("10.0.0.5/24", Some("10.0.0.1")),
+            ("fd00::5/64", Some("fd00::1")),
+        ] {
+            assert!(is_valid_ipconfig(ip, gw), "{ip} {gw:?}");
+        }
+        for (ip, gw) in [
+            ("dhcp", Some("10.0.0.1")),
+            ("10.0.0.5", None),
+            ("10.0.0.5/33", None),
+            ("10.0.0.5/24", Some("fd00::1")),
+            ("10.0.0.5/24,gw=10.0.0.1", None),
+            ("", None),
+        ] {
+            assert!(!is_valid_ipconfig(ip, gw), "{ip} {gw:?}");
+        }
+    }
+
+    #[test]
+    fn provision_options_reject_the_first_invalid_setting() {
+        let user = CloudInitUser {
+            username: "deploy".to_string(),
+            password_hash: Some("$6$salt$hash".to_string()),
+            ssh_keys: vec!["ssh-ed25519 AAAAC3Nza deploy@laptop".to_string()],
+        };
+        let ipconfig = |interface_index, ip: &str| IpConfig {
+            interface_index,
+            ip: ip.to_string(),
+            gw: None,
+        };
+        let options = ProvisionOptions {
+            cloudinit_storage: Some("local-lvm".to_string()),
+            user: Some(user.clone()),
+            ipconfigs: vec![ipconfig(0, "dhcp"), ipconfig(1, "192.168.1.2/24")],
+        };
+        assert_eq!(options.validate(), Ok(()));
+
+        let invalid = [
+            ProvisionOptions {
+                cloudinit_storage: Some("local lvm".to_string()),
+                ..options.clone()
+            },
+            ProvisionOptions {
+                user: Some(CloudInitUser {
+                    username: "Root".to_string(),
+                    ..user.clone()
+                }),
+                ..options.clone()
+            },
+            ProvisionOptions {
+                user: Some(CloudInitUser {
+                    ssh_keys: vec!["ssh-ed25519 AAAA\nssh-rsa BBBB".to_string()],
+                    ..user
+                }),
+                ..options.clone()
+            },
+            ProvisionOptions {
+                ipconfigs: vec![ipconfig(MAX_IPCONFIG_INDEX + 1, "dhcp")],
+                ..options.clone()
+            },
+            ProvisionOptions {
+                ipconfigs: vec![ipconfig(0, "dhcp"), ipconfig(0, "10.0.0.5/24")],
+                ..options
+            },
+        ];
+        for options in invalid {
+            assert!(options.validate().is_err(), "{options:?}");
+        }
+    }
+
+    #[test]
+    fn disk_size_accepts_relative_and_absolute_sizes() {
+        for size in ["+10G", "+512M", "32G"] {
+            assert!(is_valid_disk_size(size), "{size}");
+        }
+        for size in [
+            "", "+", "G", "10", "512M", "+10T", "-1G", "+1.5G", "+ 1G", "10g",
+        ] {
+            assert!(!is_valid_disk_size(size), "{size}");
+        }
+    }
+
+    #[test]
+    fn import_url_must_be_http_and_name_a_file() {
+        for (url, filename) in [
+            (
+                "https://cloud-images.ubuntu.com/noble/current/noble-server-cloudimg-amd64.img",
+                Some("noble-server-cloudimg-amd64.img"),
+            ),
+            (
+                "http://10.0.0.2:8080/disk.qcow2?token=x#top",
+                Some("disk.qcow2"),
+            ),
+            ("https://example.com/images/", None),
+            ("https://example.com", None),
+            ("https://example.com/.hidden.raw", None),
+            ("https://example.com/disk%20one.raw", None),
+        ] {
+            assert!(is_valid_import_url(url), "{url}");
+            assert_eq!(import_disk_filename(url), filename, "{url}");
+        }
+        for url in [
+            "file:///var/lib/vz/images/disk.qcow2",
+            "ftp://example.com/disk.qcow2",
+            "https:///disk.qcow2",
+            "example.com/disk.qcow2",
+            "",
+        ] {
+            assert!(!is_valid_import_url(url), "{url}");
+        }
+    }
 }
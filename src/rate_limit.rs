@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::server::ApiError;
+
+/// Paths that are never limited so probes and scrapers keep working under load.
+const EXEMPT_PATHS: &[&str] = &["/health", "/metrics"];
+
+/// Buckets are pruned once this many client IPs are tracked.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+/// Per-client-IP token bucket: each IP may burst up to `burst` requests and
+/// then gets `requests_per_second` new tokens.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Decision {
+    allowed: bool,
+    remaining: u32,
+    /// Seconds until the bucket is full again.
+    reset_secs: u64,
+    /// Seconds until the next request would be allowed.
+    retry_after_secs: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: RateLimitConfig {
+                requests_per_second: config.requests_per_second.max(f64::MIN_POSITIVE),
+                burst: config.burst.max(1),
+            },
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, ip: IpAddr, now: Instant) -> Decision {
+        let RateLimitConfig {
+            requests_per_second,
+            burst,
+        } = self.config;
+        let capacity = f64::from(burst);
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.saturating_duration_since(bucket.updated_at);
+                bucket.tokens + elapsed.as_secs_f64() * requests_per_second < capacity
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * requests_per_second).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Decision {
+            allowed,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: ((capacity - bucket.tokens) / requests_per_second).ceil() as u64,
+            retry_after_secs: ((1.0 - bucket.tokens).max(0.0) / requests_per_second)
+                .ceil()
+                .max(1.0) as u64,
+        }
+    }
+}
+
+/// Middleware applying the optional [`RateLimiter`]; passes everything through when `None`.
+pub async fn rate_limit(
+    State(limiter): State<Option<Arc<RateLimiter>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = limiter else {
+        return next.run(request).await;
+    };
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let decision = limiter.check(ip, Instant::now());
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        tracing::warn!(client_ip = %ip, "Rate limit exceeded");
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ApiError {
+                error: "Too many requests".to_string(),
+            }),
+        )
+            .into_response();
+        response.headers_mut().insert(
+            axum::http::header::RETRY_AFTER,
+            HeaderValue::from(decision.retry_after_secs),
+        );
+        response
+    };
+    insert_rate_limit_headers(response.headers_mut(), limiter.config.burst, decision);
+    response
+}
+
+/// Seconds clients are told to wait when Proxmox itself is rate limiting.
+const UPSTREAM_RETRY_AFTER_SECS: u64 = 5;
+
+/// Adds `Retry-After` to 429 responses passed on from Proxmox; those from
+/// [`rate_limit`] already carry a precise value.
+pub async fn upstream_retry_after(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        response
+            .headers_mut()
+            .entry(axum::http::header::RETRY_AFTER)
+            .or_insert(HeaderValue::from(UPSTREAM_RETRY_AFTER_SECS));
+    }
+    response
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, limit: u32, decision: Decision) {
+    headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(limit));
+    headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(X_RATELIMIT_RESET, HeaderValue::from(decision.reset_secs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bucket_refills_at_configured_rate() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 2.0,
+            burst: 3,
+        });
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let start = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check(ip, start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+        let denied = limiter.check(ip, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after_secs, 1);
+        assert_eq!(denied.reset_secs, 2);
+
+        let later = limiter.check(ip, start + Duration::from_millis(500));
+        assert!(later.allowed);
+
+        let other = limiter.check(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), start);
+        assert!(other.allowed);
+        assert_eq!(other.remaining, 2);
+    }
+}
@@ -1,11 +1,14 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde_json::Value;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 use tracing_subscriber::fmt::MakeWriter;
 
 use crate::config::RemoteLogConfig;
@@ -18,6 +21,10 @@ pub struct RemoteLogHandle {
     max_pending_bytes: usize,
     max_upload_bytes: usize,
     upload_delay: Duration,
+    immediate_on_error: bool,
+    dedup_window: Duration,
+    /// Wakes the upload loop early when an urgent entry is queued.
+    upload_now: Arc<Notify>,
     hostname: Arc<str>,
     client: reqwest::Client,
 }
@@ -25,6 +32,52 @@ pub struct RemoteLogHandle {
 struct RemoteLogState {
     entries: VecDeque<Vec<u8>>,
     pending_bytes: usize,
+    /// Message → (window start in ms, repeats suppressed since then).
+    recent_messages: HashMap<String, (u64, u32)>,
+}
+
+impl RemoteLogState {
+    fn push(&mut self, entry: Vec<u8>, max_pending_bytes: usize) -> bool {
+        if self.pending_bytes + entry.len() > max_pending_bytes {
+            eprintln!(
+                "[remote-log] dropped entry ({} bytes) because buffer is full",
+                entry.len()
+            );
+            return false;
+        }
+
+        self.pending_bytes += entry.len();
+        self.entries.push_back(entry);
+        true
+    }
+
+    /// True when `message` has an open window, in which case the repeat is
+    /// counted; otherwise opens a new window for it. Expire windows first.
+    fn is_duplicate(&mut self, message: &str, now_ms: u64) -> bool {
+        if let Some((_, count)) = self.recent_messages.get_mut(message) {
+            *count += 1;
+            return true;
+        }
+        self.recent_messages
+            .insert(message.to_string(), (now_ms, 0));
+        false
+    }
+
+    /// Closes windows older than `window_ms`, returning the messages that had
+    /// repeats suppressed along with their counts.
+    fn expire_duplicates(&mut self, now_ms: u64, window_ms: u64) -> Vec<(String, u32)> {
+        let mut expired = Vec::new();
+        self.recent_messages.retain(|message, (started_ms, count)| {
+            if now_ms.saturating_sub(*started_ms) < window_ms {
+                return true;
+            }
+            if *count > 0 {
+                expired.push((message.clone(), *count));
+            }
+            false
+        });
+        expired
+    }
 }
 
 impl RemoteLogHandle {
@@ -38,12 +91,16 @@ impl RemoteLogHandle {
             state: Arc::new(Mutex::new(RemoteLogState {
                 entries: VecDeque::new(),
                 pending_bytes: 0,
+                recent_messages: HashMap::new(),
             })),
             upload_url: Arc::from(config.upload_url),
             authorization_secret: Arc::from(config.authorization_secret),
             max_pending_bytes: config.max_pending_bytes,
             max_upload_bytes: config.max_upload_bytes,
             upload_delay: Duration::from_secs_f64(config.upload_delay_secs.max(0.1)),
+            immediate_on_error: config.immediate_on_error,
+            dedup_window: Duration::from_secs(config.dedup_window_secs),
+            upload_now: Arc::new(Notify::new()),
             hostname: Arc::from(hostname),
             client: reqwest::Client::new(),
         }
@@ -56,13 +113,54 @@ impl RemoteLogHandle {
         };
         runtime.spawn(async move {
             loop {
-                tokio::time::sleep(this.upload_delay).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(this.upload_delay) => {}
+                    _ = this.upload_now.notified() => {}
+                }
                 this.do_upload().await;
             }
         });
     }
 
+    pub async fn pending_count(&self) -> usize {
+        self.state.lock().await.entries.len()
+    }
+
+    pub async fn pending_bytes(&self) -> usize {
+        self.state.lock().await.pending_bytes
+    }
+
+    /// Removes and returns every buffered entry without uploading it.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn drain(&self) -> Vec<Vec<u8>> {
+        let mut state = self.state.lock().await;
+        state.pending_bytes = 0;
+        state.entries.drain(..).collect()
+    }
+
+    /// Copies of the buffered entries, leaving the buffer untouched.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn peek(&self) -> Vec<Vec<u8>> {
+        self.state.lock().await.entries.iter().cloned().collect()
+    }
+
+    /// True once the buffer is at 90% of `max_pending_bytes`; new entries will soon be dropped.
+    pub fn is_buffer_full(&self, pending_bytes: usize) -> bool {
+        pending_bytes as f64 >= self.max_pending_bytes as f64 * 0.9
+    }
+
+    /// Uploads every pending entry immediately, ignoring the upload delay.
+    pub async fn flush(&self) {
+        while !self.state.lock().await.entries.is_empty() {
+            self.do_upload().await;
+        }
+    }
+
     async fn do_upload(&self) {
+        {
+            let mut state = self.state.lock().await;
+            self.queue_dedup_summaries(&mut state, current_timestamp_ms());
+        }
         let next_batch = self.take_next_batch().await;
         if next_batch.is_empty() {
             return;
@@ -124,20 +222,77 @@ impl RemoteLogHandle {
         };
         let timestamp_ms = current_timestamp_ms();
         runtime.spawn(async move {
+            let urgent = this.immediate_on_error && is_urgent(&data);
+            let message = log_message(&data);
             let normalized = normalize_line(data, &hostname, timestamp_ms);
-            let mut state = this.state.lock().await;
-            if state.pending_bytes + normalized.len() > this.max_pending_bytes {
-                eprintln!(
-                    "[remote-log] dropped entry ({} bytes) because buffer is full",
-                    normalized.len()
-                );
-                return;
+            let queued = this
+                .enqueue_deduplicated(normalized, message.as_deref(), timestamp_ms)
+                .await;
+            if urgent && queued {
+                this.upload_now.notify_one();
             }
-
-            state.pending_bytes += normalized.len();
-            state.entries.push_back(normalized);
         });
     }
+
+    /// Queues `entry` unless its message was already logged within the dedup
+    /// window. Returns whether it was queued.
+    async fn enqueue_deduplicated(
+        &self,
+        entry: Vec<u8>,
+        message: Option<&str>,
+        timestamp_ms: u64,
+    ) -> bool {
+        let mut state = self.state.lock().await;
+        self.queue_dedup_summaries(&mut state, timestamp_ms);
+        let window_ms = self.dedup_window.as_millis() as u64;
+        if let Some(message) = message.filter(|_| window_ms > 0) {
+            if state.is_duplicate(message, timestamp_ms) {
+                return false;
+            }
+        }
+        state.push(entry, self.max_pending_bytes)
+    }
+
+    /// Queues one `"deduplicated": true` entry per expired window that
+    /// suppressed repeats, carrying how many were dropped.
+    fn queue_dedup_summaries(&self, state: &mut RemoteLogState, now_ms: u64) {
+        let window_ms = self.dedup_window.as_millis() as u64;
+        for (message, count) in state.expire_duplicates(now_ms, window_ms) {
+            let summary = serde_json::to_vec(&serde_json::json!({
+                "hostname": self.hostname.as_ref(),
+                "timestamp_ms": now_ms,
+                "message": message,
+                "deduplicated": true,
+                "count": count
+            }))
+            .unwrap_or_default();
+            state.push(summary, self.max_pending_bytes);
+        }
+    }
+}
+
+/// The `message` of a tracing JSON line, or the raw text for non-JSON lines.
+fn log_message(data: &[u8]) -> Option<String> {
+    match serde_json::from_slice::<Value>(data) {
+        Ok(line) => line
+            .pointer("/fields/message")
+            .or_else(|| line.get("message"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        Err(_) => Some(String::from_utf8_lossy(data).into_owned()),
+    }
+}
+
+/// True for tracing JSON lines at ERROR or WARN level.
+fn is_urgent(data: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(data)
+        .ok()
+        .and_then(|line| {
+            line.get("level").and_then(Value::as_str).map(|level| {
+                level.eq_ignore_ascii_case("ERROR") || level.eq_ignore_ascii_case("WARN")
+            })
+        })
+        .unwrap_or(false)
 }
 
 fn normalize_line(data: Vec<u8>, hostname: &str, timestamp_ms: u64) -> Vec<u8> {
@@ -171,6 +326,123 @@ fn normalize_line(data: Vec<u8>, hostname: &str, timestamp_ms: u64) -> Vec<u8> {
     }
 }
 
+/// Syslog facility used in the PRI part of each message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFacility {
+    User,
+    Daemon,
+    Auth,
+    Syslog,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl SyslogFacility {
+    fn code(self) -> u8 {
+        match self {
+            Self::User => 1,
+            Self::Daemon => 3,
+            Self::Auth => 4,
+            Self::Syslog => 5,
+            Self::Local0 => 16,
+            Self::Local1 => 17,
+            Self::Local2 => 18,
+            Self::Local3 => 19,
+            Self::Local4 => 20,
+            Self::Local5 => 21,
+            Self::Local6 => 22,
+            Self::Local7 => 23,
+        }
+    }
+}
+
+impl FromStr for SyslogFacility {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "user" => Ok(Self::User),
+            "daemon" => Ok(Self::Daemon),
+            "auth" => Ok(Self::Auth),
+            "syslog" => Ok(Self::Syslog),
+            "local0" => Ok(Self::Local0),
+            "local1" => Ok(Self::Local1),
+            "local2" => Ok(Self::Local2),
+            "local3" => Ok(Self::Local3),
+            "local4" => Ok(Self::Local4),
+            "local5" => Ok(Self::Local5),
+            "local6" => Ok(Self::Local6),
+            "local7" => Ok(Self::Local7),
+            other => Err(format!("Invalid syslog facility: {other}")),
+        }
+    }
+}
+
+/// Writes each log entry straight to a local syslog socket such as `/dev/log`.
+/// Unlike [`RemoteLogHandle`] nothing is buffered; a failed send drops the entry.
+pub struct SyslogWriter {
+    socket: UnixDatagram,
+    facility: SyslogFacility,
+    hostname: String,
+}
+
+impl SyslogWriter {
+    pub fn connect(socket_path: &Path, facility: SyslogFacility) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(socket_path)?;
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "-".to_string());
+        Ok(Self {
+            socket,
+            facility,
+            hostname,
+        })
+    }
+
+    fn send(&self, line: &[u8]) {
+        let message = format_rfc5424(self.facility, &self.hostname, std::process::id(), line);
+        if let Err(err) = self.socket.send(&message) {
+            eprintln!("[remote-log] syslog write failed: {err}");
+        }
+    }
+}
+
+/// Formats a tracing JSON line as an RFC 5424 message carrying the line as MSG.
+/// Severity comes from the `level` field and the timestamp from `timestamp`.
+fn format_rfc5424(facility: SyslogFacility, hostname: &str, pid: u32, line: &[u8]) -> Vec<u8> {
+    let parsed = serde_json::from_slice::<Value>(line).ok();
+    let field = |name: &str| {
+        parsed
+            .as_ref()
+            .and_then(|line| line.get(name))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let severity: u8 = match field("level").map(|level| level.to_uppercase()).as_deref() {
+        Some("ERROR") => 3,
+        Some("WARN") => 4,
+        Some("DEBUG") | Some("TRACE") => 7,
+        _ => 6,
+    };
+    let timestamp = field("timestamp").unwrap_or_else(|| "-".to_string());
+    let mut message = format!(
+        "<{}>1 {timestamp} {hostname} {} {pid} - - ",
+        facility.code() * 8 + severity,
+        env!("CARGO_PKG_NAME"),
+    )
+    .into_bytes();
+    message.extend_from_slice(line);
+    message
+}
+
 fn current_timestamp_ms() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -180,12 +452,26 @@ fn current_timestamp_ms() -> u64 {
 
 #[derive(Clone)]
 pub struct RemoteLogMakeWriter {
-    handle: RemoteLogHandle,
+    sink: RemoteLogSink,
+}
+
+#[derive(Clone)]
+enum RemoteLogSink {
+    Http(RemoteLogHandle),
+    Syslog(Arc<SyslogWriter>),
 }
 
 impl RemoteLogMakeWriter {
     pub fn new(handle: RemoteLogHandle) -> Self {
-        Self { handle }
+        Self {
+            sink: RemoteLogSink::Http(handle),
+        }
+    }
+
+    pub fn syslog(writer: SyslogWriter) -> Self {
+        Self {
+            sink: RemoteLogSink::Syslog(Arc::new(writer)),
+        }
     }
 }
 
@@ -194,14 +480,14 @@ impl<'a> MakeWriter<'a> for RemoteLogMakeWriter {
 
     fn make_writer(&'a self) -> Self::Writer {
         RemoteLogWriter {
-            handle: self.handle.clone(),
+            sink: self.sink.clone(),
             buffer: Vec::new(),
         }
     }
 }
 
 pub struct RemoteLogWriter {
-    handle: RemoteLogHandle,
+    sink: RemoteLogSink,
     buffer: Vec<u8>,
 }
 
@@ -218,7 +504,10 @@ impl Write for RemoteLogWriter {
 
         let data = std::mem::take(&mut self.buffer);
         for line in data.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
-            self.handle.log(line.to_vec());
+            match &self.sink {
+                RemoteLogSink::Http(handle) => handle.log(line.to_vec()),
+                RemoteLogSink::Syslog(writer) => writer.send(line),
+            }
         }
 
         Ok(())
@@ -230,3 +519,206 @@ impl Drop for RemoteLogWriter {
         let _ = self.flush();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle_with_capacity(max_pending_bytes: usize) -> RemoteLogHandle {
+        RemoteLogHandle::new(RemoteLogConfig {
+            upload_url: "http://127.0.0.1:9/logs".to_string(),
+            authorization_secret: "secret".to_string(),
+            max_pending_bytes,
+            max_upload_bytes: max_pending_bytes,
+            upload_delay_secs: 60.0,
+            immediate_on_error: true,
+            dedup_window_secs: 60,
+        })
+    }
+
+    async fn pending_entries(handle: &RemoteLogHandle) -> Vec<Value> {
+        handle
+            .peek()
+            .await
+            .iter()
+            .map(|entry| serde_json::from_slice(entry).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn drain_returns_and_clears_buffered_entries() {
+        let handle = handle_with_capacity(1024);
+        let written = [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+        for entry in &written {
+            handle.enqueue_deduplicated(entry.clone(), None, 0).await;
+        }
+
+        assert_eq!(handle.drain().await, written);
+        assert_eq!(handle.pending_count().await, 0);
+        assert_eq!(handle.pending_bytes().await, 0);
+        assert!(handle.drain().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn peek_leaves_the_buffer_unchanged() {
+        let handle = handle_with_capacity(1024);
+        handle
+            .enqueue_deduplicated(b"first".to_vec(), None, 0)
+            .await;
+        handle
+            .enqueue_deduplicated(b"second".to_vec(), None, 0)
+            .await;
+
+        let peeked = handle.peek().await;
+        assert_eq!(peeked, [b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(handle.peek().await, peeked);
+        assert_eq!(handle.pending_count().await, 2);
+        assert_eq!(handle.pending_bytes().await, 11);
+    }
+
+    #[tokio::test]
+    async fn first_message_opens_a_dedup_window() {
+        let handle = handle_with_capacity(1024 * 1024);
+        assert!(
+            handle
+                .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 1_000)
+                .await
+        );
+        assert!(
+            handle
+                .enqueue_deduplicated(b"{}".to_vec(), Some("other"), 1_000)
+                .await
+        );
+
+        assert_eq!(handle.pending_count().await, 2);
+        let state = handle.state.lock().await;
+        assert_eq!(state.recent_messages["down"], (1_000, 0));
+    }
+
+    #[tokio::test]
+    async fn repeats_within_the_window_are_counted_not_queued() {
+        let handle = handle_with_capacity(1024 * 1024);
+        handle
+            .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 1_000)
+            .await;
+        for offset in [2_000, 4_000, 59_999] {
+            assert!(
+                !handle
+                    .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 1_000 + offset)
+                    .await
+            );
+        }
+
+        assert_eq!(handle.pending_count().await, 1);
+        let state = handle.state.lock().await;
+        assert_eq!(state.recent_messages["down"], (1_000, 3));
+    }
+
+    #[tokio::test]
+    async fn expired_window_emits_one_summary_entry() {
+        let handle = handle_with_capacity(1024 * 1024);
+        handle
+            .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 1_000)
+            .await;
+        handle
+            .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 3_000)
+            .await;
+        handle
+            .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 5_000)
+            .await;
+
+        assert!(
+            handle
+                .enqueue_deduplicated(b"{}".to_vec(), Some("down"), 61_000)
+                .await
+        );
+        let entries = pending_entries(&handle).await;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1]["message"], "down");
+        assert_eq!(entries[1]["deduplicated"], true);
+        assert_eq!(entries[1]["count"], 2);
+        assert_eq!(entries[1]["timestamp_ms"], 61_000);
+
+        // A window without repeats closes silently.
+        handle
+            .enqueue_deduplicated(b"{}".to_vec(), Some("up"), 200_000)
+            .await;
+        assert_eq!(handle.pending_count().await, 4);
+    }
+
+    #[tokio::test]
+    async fn buffer_reports_full_past_ninety_percent() {
+        let handle = handle_with_capacity(100);
+        for _ in 0..8 {
+            handle.enqueue_deduplicated(vec![b'x'; 10], None, 0).await;
+        }
+        assert_eq!(handle.pending_count().await, 8);
+        assert_eq!(handle.pending_bytes().await, 80);
+        assert!(!handle.is_buffer_full(handle.pending_bytes().await));
+
+        handle.enqueue_deduplicated(vec![b'x'; 15], None, 0).await;
+        assert_eq!(handle.pending_count().await, 9);
+        assert_eq!(handle.pending_bytes().await, 95);
+        assert!(handle.is_buffer_full(handle.pending_bytes().await));
+    }
+
+    #[tokio::test]
+    async fn error_entries_are_uploaded_before_the_delay() {
+        let (uploads_tx, mut uploads) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let app = axum::Router::new().route(
+            "/logs",
+            axum::routing::post(move |body: String| {
+                let uploads_tx = uploads_tx.clone();
+                async move {
+                    let _ = uploads_tx.send(body);
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let handle = RemoteLogHandle::new(RemoteLogConfig {
+            upload_url: format!("http://{addr}/logs"),
+            authorization_secret: "secret".to_string(),
+            max_pending_bytes: 1024 * 1024,
+            max_upload_bytes: 1024 * 1024,
+            upload_delay_secs: 60.0,
+            immediate_on_error: true,
+            dedup_window_secs: 60,
+        });
+        handle.spawn_upload_loop();
+        handle.log(br#"{"level":"ERROR","fields":{"message":"boom"}}"#.to_vec());
+
+        let body = tokio::time::timeout(Duration::from_secs(5), uploads.recv())
+            .await
+            .expect("ERROR entry was not uploaded before the delay")
+            .unwrap();
+        assert!(body.contains("boom"), "{body}");
+    }
+
+    #[test]
+    fn only_error_and_warn_levels_are_urgent() {
+        assert!(is_urgent(br#"{"level":"ERROR"}"#));
+        assert!(is_urgent(br#"{"level":"WARN"}"#));
+        assert!(!is_urgent(br#"{"level":"INFO"}"#));
+        assert!(!is_urgent(b"ERROR plain text"));
+    }
+
+    #[test]
+    fn syslog_messages_follow_rfc5424() {
+        let line = br#"{"timestamp":"2024-05-01T12:00:00.000001Z","level":"WARN","fields":{"message":"slow"}}"#;
+        let message = format_rfc5424(SyslogFacility::Daemon, "pve", 42, line);
+        let expected = format!(
+            "<28>1 2024-05-01T12:00:00.000001Z pve {} 42 - - {}",
+            env!("CARGO_PKG_NAME"),
+            std::str::from_utf8(line).unwrap()
+        );
+        assert_eq!(String::from_utf8(message).unwrap(), expected);
+
+        let error = format_rfc5424(SyslogFacility::Local0, "pve", 1, br#"{"level":"ERROR"}"#);
+        assert!(error.starts_with(b"<131>1 - pve "));
+        let plain = format_rfc5424(SyslogFacility::User, "pve", 1, b"not json");
+        assert!(plain.starts_with(b"<14>1 - pve "));
+    }
+}
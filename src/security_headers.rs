@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::{
+    InvalidHeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'";
+
+/// Headers keeping the UI out of foreign frames and limiting what it may load.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    content_security_policy: HeaderValue,
+}
+
+impl SecurityHeaders {
+    pub fn new(content_security_policy: &str) -> Result<Self, InvalidHeaderValue> {
+        Ok(Self {
+            content_security_policy: HeaderValue::from_str(content_security_policy)?,
+        })
+    }
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_security_policy: HeaderValue::from_static(DEFAULT_CONTENT_SECURITY_POLICY),
+        }
+    }
+}
+
+/// Middleware adding the optional [`SecurityHeaders`]; passes responses through
+/// untouched when `None`. A handler that sets its own CSP keeps it.
+pub async fn security_headers(
+    State(config): State<Option<Arc<SecurityHeaders>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let Some(config) = config else {
+        return response;
+    };
+    let headers = response.headers_mut();
+    headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(REFERRER_POLICY, HeaderValue::from_static("same-origin"));
+    headers
+        .entry(CONTENT_SECURITY_POLICY)
+        .or_insert_with(|| config.content_security_policy.clone());
+    response
+}
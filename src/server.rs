@@ -1,62 +1,391 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::{Arc, Mutex as StdMutex, MutexGuard};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use axum::{
-    extract::{MatchedPath, State},
-    http::{Request, StatusCode},
-    response::{Html, IntoResponse},
-    routing::{get, post},
+    body::{Body, Bytes},
+    extract::rejection::JsonRejection,
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    extract::{FromRequest, MatchedPath, Path, Query, State},
+    http::header,
+    http::{HeaderMap, Request, StatusCode},
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
+use futures_util::future::join_all;
+use futures_util::stream::{self, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::process::Command;
-use tokio::sync::Mutex;
-use tokio::time::sleep;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{watch, Mutex, Semaphore};
+use tokio::time::{sleep, timeout};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{debug, error, info, warn, Span};
+use uuid::Uuid;
 
+use crate::api_version::negotiate_api_version;
+use crate::assets::{StaticAsset, StaticAssets};
+use crate::connection_limit::{connection_limit, ConnectionLimiter};
+use crate::fallback::FallbackHandle;
+use crate::metrics::{label_route, observe_http_request};
 use crate::proxmox::error::ProxmoxError;
-use crate::proxmox::types::{VmInfo, VmStatus};
+use crate::proxmox::types::{
+    import_disk_filename, is_allowed_keysym, is_valid_cpu_topology, is_valid_disk_size,
+    is_valid_import_url, is_valid_memory_mib, is_valid_storage_id, status_transitions,
+    validate_vmid, AgentExecResult, BackupCompression, BackupInfo, BackupMode, BackupOptions,
+    CloudInitUser, ClusterStatus, ConsoleTicket, FirewallRule, ForkOptions, IpConfig, NodeInfo,
+    NodeStatus, ProvisionOptions, RrdTimeframe, SnapshotInfo, StorageInfo, TaskId, TaskSummary,
+    VmInfo, VmRrdData, VmStatus, VmStatusTransition, ALLOWED_KEYSYMS, MAX_VCPUS, MIN_MEMORY_MIB,
+};
 use crate::proxmox::ProxmoxClient;
-
-const INDEX_HTML: &str = include_str!("../assets/index.html");
-const APP_JS: &str = include_str!("../assets/app.js");
-const BACKGROUND_JPG: &[u8] = include_bytes!("../assets/background.jpg");
+use crate::rate_limit::{rate_limit, upstream_retry_after, RateLimitConfig, RateLimiter};
+use crate::remote_log::RemoteLogHandle;
+use crate::security_headers::{security_headers, SecurityHeaders};
+use crate::vm_events::{VmEvents, VmStatusChange, DEFAULT_VM_EVENTS_POLL_INTERVAL};
 
 #[derive(Clone)]
 pub struct AppState {
     client: ProxmoxClient,
     launch_manager: Arc<LaunchManager>,
     shutdown_manager: Arc<ShutdownManager>,
+    fork_jobs: Arc<ForkJobs>,
+    fork_wait: ForkWait,
+    assets: StaticAssets,
+    fallback: Option<FallbackHandle>,
+    remote_log: Option<RemoteLogHandle>,
+    compression: CompressionConfig,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    connection_limiter: Option<Arc<ConnectionLimiter>>,
+    security_headers: Option<Arc<SecurityHeaders>>,
+    admin_api_key: Option<Arc<str>>,
+    protected_vmids: Arc<HashSet<u64>>,
+    /// When set, the only VMs the agent lists or acts on.
+    allowed_vmids: Arc<Option<HashSet<u64>>>,
+    launch_target_vmid: Option<u64>,
+    vm_events: VmEvents,
+    started_at: Instant,
+    cancel: CancellationToken,
+    flows: TaskTracker,
 }
 
 impl AppState {
     pub fn new(client: ProxmoxClient) -> Self {
+        let cancel = CancellationToken::new();
+        let flows = TaskTracker::new();
+        let vm_events = VmEvents::new(
+            client.clone(),
+            DEFAULT_VM_EVENTS_POLL_INTERVAL,
+            cancel.clone(),
+        );
+        let protected_vmids = Arc::new(HashSet::new());
+        let allowed_vmids = Arc::new(None);
         Self {
             client,
-            launch_manager: Arc::new(LaunchManager::default()),
-            shutdown_manager: Arc::new(ShutdownManager::default()),
+            launch_manager: Arc::new(LaunchManager::new(
+                LaunchConfig::default(),
+                cancel.clone(),
+                flows.clone(),
+                Arc::clone(&protected_vmids),
+                Arc::clone(&allowed_vmids),
+            )),
+            shutdown_manager: Arc::new(ShutdownManager::new(
+                ShutdownConfig::default(),
+                cancel.clone(),
+                flows.clone(),
+                Arc::clone(&protected_vmids),
+                Arc::clone(&allowed_vmids),
+            )),
+            fork_jobs: Arc::new(ForkJobs::default()),
+            fork_wait: ForkWait::default(),
+            assets: StaticAssets::embedded(),
+            fallback: None,
+            remote_log: None,
+            compression: CompressionConfig::default(),
+            rate_limiter: None,
+            connection_limiter: None,
+            security_headers: Some(Arc::default()),
+            admin_api_key: None,
+            protected_vmids,
+            allowed_vmids,
+            launch_target_vmid: None,
+            vm_events,
+            started_at: Instant::now(),
+            cancel,
+            flows,
+        }
+    }
+
+    pub fn with_static_assets(mut self, assets: StaticAssets) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn with_fallback(mut self, fallback: FallbackHandle) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Limits API requests per client IP; `/health` is never limited.
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(config)));
+        self
+    }
+
+    /// Answers 503 once `max_concurrent` requests are in flight; `/health` is
+    /// never limited.
+    pub fn with_connection_limit(mut self, max_concurrent: usize) -> Self {
+        self.connection_limiter = Some(Arc::new(ConnectionLimiter::new(max_concurrent)));
+        self
+    }
+
+    /// Replaces the default security headers (`Content-Security-Policy:
+    /// default-src 'self'`, `X-Frame-Options: DENY`, ...).
+    pub fn with_security_headers(mut self, headers: SecurityHeaders) -> Self {
+        self.security_headers = Some(Arc::new(headers));
+        self
+    }
+
+    /// Sends no security headers, e.g. to embed the UI in a dashboard.
+    pub fn without_security_headers(mut self) -> Self {
+        self.security_headers = None;
+        self
+    }
+
+    /// Enables the `/admin` routes for requests bearing this key. Without it
+    /// they reject every request.
+    pub fn with_admin_api_key(mut self, key: impl Into<Arc<str>>) -> Self {
+        self.admin_api_key = Some(key.into());
+        self
+    }
+
+    /// How often `/api/ws/vms` polls Proxmox for status changes.
+    pub fn with_vm_events_poll_interval(mut self, interval: Duration) -> Self {
+        self.vm_events = VmEvents::new(self.client.clone(), interval, self.cancel.clone());
+        self
+    }
+
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_remote_log(mut self, remote_log: RemoteLogHandle) -> Self {
+        self.remote_log = Some(remote_log);
+        self
+    }
+
+    pub fn with_shutdown_config(mut self, config: ShutdownConfig) -> Self {
+        self.shutdown_manager = Arc::new(ShutdownManager::new(
+            config,
+            self.cancel.clone(),
+            self.flows.clone(),
+            Arc::clone(&self.protected_vmids),
+            Arc::clone(&self.allowed_vmids),
+        ));
+        self
+    }
+
+    pub fn with_launch_config(mut self, config: LaunchConfig) -> Self {
+        self.launch_manager = Arc::new(LaunchManager::new(
+            config,
+            self.cancel.clone(),
+            self.flows.clone(),
+            Arc::clone(&self.protected_vmids),
+            Arc::clone(&self.allowed_vmids),
+        ));
+        self
+    }
+
+    /// VMs the agent must never stop or delete: launch and host-shutdown flows
+    /// refuse to act on them, as do the delete, terminate and stop endpoints.
+    pub fn with_protected_vmids(mut self, vmids: impl IntoIterator<Item = u64>) -> Self {
+        self.protected_vmids = Arc::new(vmids.into_iter().collect());
+        let launch_config = self.launch_manager.config;
+        let shutdown_config = self.shutdown_manager.config;
+        self.with_launch_config(launch_config)
+            .with_shutdown_config(shutdown_config)
+    }
+
+    /// Restricts the agent to these VMs: others are left out of VM lists and
+    /// requests naming them are refused with 403, as are launch and
+    /// host-shutdown flows that would have to stop one of them.
+    pub fn with_allowed_vmids(mut self, vmids: impl IntoIterator<Item = u64>) -> Self {
+        self.allowed_vmids = Arc::new(Some(vmids.into_iter().collect()));
+        let launch_config = self.launch_manager.config;
+        let shutdown_config = self.shutdown_manager.config;
+        self.with_launch_config(launch_config)
+            .with_shutdown_config(shutdown_config)
+    }
+
+    fn is_allowed(&self, vmid: u64) -> bool {
+        is_allowed_vmid(&self.allowed_vmids, vmid)
+    }
+
+    /// VM launched by `POST /api/launch` requests that leave out `vmid`.
+    pub fn with_launch_target_vmid(mut self, vmid: u64) -> Self {
+        self.launch_target_vmid = Some(vmid);
+        self
+    }
+
+    /// Overrides how long forks wait for the cloned VM to show up in the inventory.
+    pub fn with_fork_wait(mut self, attempts: u32, interval: Duration) -> Self {
+        self.fork_wait = ForkWait { attempts, interval };
+        self
+    }
+
+    /// Notified whenever a launch flow starts or finishes. Subscribe after the
+    /// last `with_*` call: `with_launch_config`, `with_protected_vmids` and
+    /// `with_allowed_vmids` replace the launch manager and with it the channel.
+    pub fn subscribe_launch_status(&self) -> watch::Receiver<LaunchStateSnapshot> {
+        self.launch_manager.subscribe_status()
+    }
+
+    /// Rejects new launch/shutdown flows and ends requests still waiting on
+    /// Proxmox, so open connections can close. Call as soon as the shutdown
+    /// signal arrives.
+    pub fn begin_shutdown(&self) {
+        self.cancel.cancel();
+        self.flows.close();
+    }
+
+    /// Future that completes once [`Self::begin_shutdown`] has been called.
+    pub fn shutdown_started(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.cancel.clone().cancelled_owned()
+    }
+
+    /// Rejects new launch/shutdown flows and waits for in-flight ones to finish.
+    /// Returns `false` if the grace period elapsed first.
+    pub async fn drain(&self, grace_period: Duration) -> bool {
+        self.begin_shutdown();
+        info!(
+            in_flight = self.flows.len(),
+            grace_period_secs = grace_period.as_secs(),
+            "Waiting for in-flight flows to complete"
+        );
+        timeout(grace_period, self.flows.wait()).await.is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Responses smaller than this are sent uncompressed.
+    pub min_size_bytes: u16,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size_bytes: 1024,
         }
     }
 }
 
+/// Gzip or brotli (per `Accept-Encoding`) for JSON and HTML bodies only;
+/// SSE streams, images and scripts pass through untouched.
+fn compression_layer(config: CompressionConfig) -> CompressionLayer<impl Predicate> {
+    let enabled = config.enabled;
+    let compressible = move |_: StatusCode,
+                             _: axum::http::Version,
+                             headers: &HeaderMap,
+                             _: &axum::http::Extensions| {
+        enabled
+            && headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|content_type| {
+                    content_type.starts_with("application/json")
+                        || content_type.starts_with("application/vnd.risky-agent.")
+                        || content_type.starts_with("text/html")
+                })
+    };
+    CompressionLayer::new().compress_when(SizeAbove::new(config.min_size_bytes).and(compressible))
+}
+
 pub fn router(state: AppState) -> Router {
+    let compression = compression_layer(state.compression);
     Router::new()
         .route("/", get(index))
         .route("/assets/app.js", get(app_js))
+        .route("/assets/app.css", get(app_css))
+        .route("/assets/background.jpg", get(background_jpg))
+        .route("/health", get(health))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/api/status", get(system_status))
+        .route("/api/vms", get(list_vms))
+        .route("/api/ws/vms", get(vm_events_socket))
+        .route("/api/vms/:vmid/tags", patch(set_vm_tags))
         .route(
-            "/assets/background.jpg",
-            get(|| async {
-                (
-                    [(axum::http::header::CONTENT_TYPE, "image/jpeg")],
-                    BACKGROUND_JPG,
-                )
-            }),
+            "/api/vms/:vmid/notes",
+            get(get_vm_notes).post(set_vm_notes).patch(set_vm_notes),
         )
-        .route("/api/vms", get(list_vms))
+        .route("/api/vms/:vmid/uptime", get(vm_uptime))
+        .route("/api/vms/:vmid/tasks", get(list_vm_tasks))
+        .route("/api/vms/:vmid/history", get(vm_status_history))
+        .route("/api/vms/:vmid/metrics", get(vm_metrics))
+        .route("/api/vms/:vmid/console-url", get(vm_console_url))
+        .route("/api/vms/:vmid/firewall", get(list_firewall_rules))
+        .route("/api/vms/:vmid/disk/resize", post(resize_vm_disk))
+        .route("/api/vms/:vmid/cpu", patch(set_vm_cpu))
+        .route("/api/vms/:vmid/memory", patch(set_vm_memory))
+        .route("/api/vms/:vmid/fork", post(start_fork_job))
+        .route("/api/vms/:vmid/clone", post(clone_vm))
+        .route(
+            "/api/vms/:vmid/backup",
+            get(list_vm_backups).post(start_vm_backup),
+        )
+        .route("/api/fork/jobs/:id", get(fork_job_status))
+        .route("/api/fork/jobs/:id/events", get(fork_job_events))
+        .route(
+            "/api/vms/:vmid/snapshots",
+            get(list_snapshots).post(create_snapshot),
+        )
+        .route("/api/cluster/status", get(cluster_status))
+        .route("/api/nodes", get(list_nodes))
+        .route("/api/nodes/:node/status", get(node_status))
+        .route("/api/nodes/:node/storages", get(list_storages))
+        .route("/api/nodes/:node/vms", get(list_node_vms))
         .route("/api/launch", post(launch))
+        .route("/api/launch/by-tag", post(launch_by_tag))
+        .route("/api/launch/in-progress", get(launch_in_progress))
+        .route("/api/launch/estimate", get(launch_estimate))
         .route("/api/fork", post(fork_vm))
         .route("/api/host-shutdown", post(host_shutdown))
+        .route(
+            "/api/host-shutdown/in-progress",
+            get(host_shutdown_in_progress),
+        )
+        .route("/api/config/fallback", get(get_fallback_config))
+        .route("/api/openapi.json", get(openapi_spec))
+        .route("/api/docs", get(api_docs))
+        .nest("/admin", admin_router(&state))
+        .merge(admin_api_router(&state))
+        .layer(middleware::from_fn(upstream_retry_after))
+        .layer(middleware::from_fn_with_state(
+            state.rate_limiter.clone(),
+            rate_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.connection_limiter.clone(),
+            connection_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.security_headers.clone(),
+            security_headers,
+        ))
+        .layer(middleware::from_fn(negotiate_api_version))
+        .layer(middleware::from_fn(label_route))
+        .layer(compression)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {
@@ -82,6 +411,7 @@ pub fn router(state: AppState) -> Router {
                 })
                 .on_response(
                     |response: &axum::http::Response<_>, latency: Duration, _span: &Span| {
+                        observe_http_request(response, latency);
                         info!(
                             status = %response.status(),
                             latency_ms = latency.as_millis(),
@@ -104,104 +434,2259 @@ pub fn router(state: AppState) -> Router {
         .with_state(Arc::new(state))
 }
 
-async fn index() -> Html<&'static str> {
-    debug!("Serving index page");
-    Html(INDEX_HTML)
+/// Operations too sensitive for the public API, mounted at `/admin` and
+/// guarded by `ADMIN_API_KEY`.
+fn admin_router(state: &AppState) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/vms/:vmid", delete(delete_vm))
+        .route("/vms/:vmid/agent/exec", post(agent_exec))
+        .route("/fallback/inhibit", post(inhibit_fallback))
+        .route("/fallback/enable", post(enable_fallback))
+        .route("/fallback/trigger", post(trigger_fallback))
+        .route_layer(middleware::from_fn_with_state(
+            state.admin_api_key.clone(),
+            require_admin_key,
+        ))
+}
+
+/// Routes under `/api` that need the admin key like the `/admin` routes:
+/// direct power controls that skip the launch and host-shutdown flows, and
+/// runtime configuration changes.
+fn admin_api_router(state: &AppState) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/vms/:vmid/start", post(start_vm))
+        .route("/api/vms/:vmid/stop", post(stop_vm))
+        .route("/api/vms/:vmid/shutdown", post(shutdown_vm))
+        .route("/api/vms/:vmid/suspend", post(suspend_vm))
+        .route("/api/vms/:vmid/resume", post(resume_vm))
+        .route("/api/vms/:vmid/action", post(vm_action))
+        .route("/api/vms/bulk-action", post(bulk_vm_action))
+        .route("/api/config/fallback", put(set_fallback_config))
+        .route("/api/access/permissions", get(token_permissions))
+        .route("/api/vms/:vmid/sendkey", post(send_vm_key))
+        .route("/api/vms/:vmid/provision", post(provision_vm))
+        .route("/api/vms/:vmid/import-disk", post(import_vm_disk))
+        .route("/api/vms/:vmid/snapshots/:name", delete(delete_snapshot))
+        .route(
+            "/api/vms/:vmid/snapshots/:name/rollback",
+            post(rollback_snapshot),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.admin_api_key.clone(),
+            require_admin_key,
+        ))
+}
+
+/// Expects `Authorization: Bearer <ADMIN_API_KEY>`: 401 without a bearer
+/// token, 403 for any other key or when no admin key is configured.
+async fn require_admin_key(
+    State(admin_api_key): State<Option<Arc<str>>>,
+    request: Request<Body>,
+    next: middleware::Next,
+) -> Response {
+    let Some(expected) = admin_api_key else {
+        warn!(path = %request.uri().path(), "Rejected admin request; ADMIN_API_KEY is not set");
+        return admin_error(StatusCode::FORBIDDEN, "Admin API is disabled");
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(provided) = provided else {
+        return admin_error(StatusCode::UNAUTHORIZED, "Missing admin API key");
+    };
+    // Comparing digests keeps the comparison time independent of the key.
+    let digest = |key: &str| ring::digest::digest(&ring::digest::SHA256, key.as_bytes());
+    if digest(provided).as_ref() != digest(&expected).as_ref() {
+        warn!(path = %request.uri().path(), "Rejected admin request with wrong key");
+        return admin_error(StatusCode::FORBIDDEN, "Invalid admin API key");
+    }
+    next.run(request).await
+}
+
+fn admin_error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ApiError {
+            error: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("Serving index page");
+    serve_asset(&state.assets.index_html, "text/html; charset=utf-8")
+}
+
+async fn app_js(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("Serving app JavaScript");
+    serve_asset(&state.assets.app_js, "application/javascript")
+}
+
+async fn app_css(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    serve_asset(&state.assets.app_css, "text/css")
+}
+
+async fn background_jpg(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    serve_asset(&state.assets.background_jpg, "image/jpeg")
+}
+
+fn serve_asset(asset: &StaticAsset, content_type: &'static str) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, asset.cache_control()),
+        ],
+        Body::from(Bytes::from_owner(asset.bytes.clone())),
+    )
+}
+
+/// Hand-maintained OpenAPI 3.0 description of the HTTP API.
+const OPENAPI_SPEC: &str = include_str!("../assets/openapi.json");
+
+/// Swagger UI page that renders [`OPENAPI_SPEC`].
+const API_DOCS_HTML: &str = include_str!("../assets/api-docs.html");
+
+async fn openapi_spec() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI_SPEC)
+}
+
+/// Swagger UI comes from unpkg and bootstraps with an inline script, which the
+/// default `Content-Security-Policy` would block.
+const API_DOCS_CSP: &str =
+    "default-src 'self'; script-src 'self' 'unsafe-inline' https://unpkg.com; \
+    style-src 'self' 'unsafe-inline' https://unpkg.com; img-src 'self' data:";
+
+async fn api_docs() -> impl IntoResponse {
+    (
+        [(header::CONTENT_SECURITY_POLICY, API_DOCS_CSP)],
+        Html(API_DOCS_HTML),
+    )
+}
+
+async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, crate::metrics::CONTENT_TYPE)],
+        crate::metrics::render(),
+    )
+}
+
+/// How long `/health` waits for Proxmox before reporting it unreachable.
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+async fn health(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<HealthResponse>, (StatusCode, Json<ApiError>)> {
+    let error = match timeout(HEALTH_PROBE_TIMEOUT, state.client.probe()).await {
+        Ok(Ok(())) => {
+            let cluster_quorate =
+                match timeout(HEALTH_PROBE_TIMEOUT, state.client.get_cluster_status()).await {
+                    Ok(Ok(status)) => Some(status.quorate),
+                    Ok(Err(err)) => {
+                        warn!("Health check could not read cluster status: {err}");
+                        None
+                    }
+                    Err(_) => {
+                        warn!("Health check timed out reading cluster status");
+                        None
+                    }
+                };
+            let remote_log = match state.remote_log.as_ref() {
+                Some(remote) => Some(RemoteLogHealth::observe(remote).await),
+                None => None,
+            };
+            return Ok(Json(HealthResponse {
+                status: "ok",
+                cluster_quorate,
+                remote_log,
+            }));
+        }
+        Ok(Err(err)) => err.to_string(),
+        Err(_) => format!(
+            "Proxmox did not respond within {}s",
+            HEALTH_PROBE_TIMEOUT.as_secs()
+        ),
+    };
+    warn!(error = %error, "Health check failed");
+    Err((
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ApiError {
+            error: format!("Proxmox unreachable: {error}"),
+        }),
+    ))
+}
+
+async fn system_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SystemStatus>, (StatusCode, Json<ApiError>)> {
+    debug!("Building combined system status");
+    let vms = state.client.list_vms().await.map_err(map_proxmox_error)?;
+    Ok(Json(SystemStatus {
+        vms: vms
+            .into_iter()
+            .filter(|vm| state.is_allowed(vm.vmid))
+            .map(ApiVm::from)
+            .collect(),
+        launch_in_progress: state.launch_manager.is_in_progress().await,
+        shutdown_in_progress: state.shutdown_manager.is_in_progress().await,
+        fallback_inhibited: state
+            .fallback
+            .as_ref()
+            .is_some_and(FallbackHandle::is_inhibited),
+        agent_uptime_secs: state.started_at.elapsed().as_secs(),
+    }))
+}
+
+async fn list_vms(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<VmListQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    info!(
+        tag = ?query.tag,
+        status = ?query.status,
+        name_contains = ?query.name_contains,
+        "Listing VMs"
+    );
+    let status = query
+        .status
+        .as_deref()
+        .map(|status| {
+            status.parse::<VmStatus>().map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiError {
+                        error: format!(
+                            "Invalid status '{status}'; expected running, stopped, paused or unknown"
+                        ),
+                    }),
+                )
+            })
+        })
+        .transpose()?;
+    let vms = match query.tag.as_deref() {
+        Some(tag) => state.client.list_vms_by_tag(tag).await,
+        None => state.client.list_vms().await,
+    }
+    .map_err(map_proxmox_error)?;
+    let name_contains = query.name_contains.map(|name| name.to_lowercase());
+    let mut vms: Vec<VmInfo> = vms
+        .into_iter()
+        .filter(|vm| state.is_allowed(vm.vmid))
+        .filter(|vm| status.as_ref().is_none_or(|status| vm.status == *status))
+        .filter(|vm| {
+            name_contains
+                .as_deref()
+                .is_none_or(|name| vm.name.to_lowercase().contains(name))
+        })
+        .collect();
+    info!(vm_count = vms.len(), "VM list retrieved");
+    let Some(cursor) = query.cursor else {
+        if query.limit.is_some() {
+            let limit = page_size(query.limit)?;
+            vms.sort_by_key(|vm| vm.vmid);
+            vms.truncate(limit as usize);
+        }
+        let response: Vec<ApiVm> = vms.into_iter().map(ApiVm::from).collect();
+        let version: Vec<_> = response.iter().map(ApiVm::stable_fields).collect();
+        return Ok(json_with_etag(&headers, &response, &version));
+    };
+    let limit = page_size(query.limit)?;
+    let after = (!cursor.is_empty())
+        .then(|| cursor.parse::<u64>().map_err(|_| invalid_cursor(&cursor)))
+        .transpose()?;
+    let total = vms.len() as u64;
+    vms.sort_by_key(|vm| vm.vmid);
+    let vms = vms
+        .into_iter()
+        .filter(|vm| after.is_none_or(|after| vm.vmid > after))
+        .take(limit as usize + 1)
+        .map(ApiVm::from)
+        .collect();
+    let page = PaginatedResponse::from_lookahead(vms, limit, Some(total), |vm: &ApiVm| {
+        vm.vmid.to_string()
+    });
+    let version = (
+        page.data
+            .iter()
+            .map(ApiVm::stable_fields)
+            .collect::<Vec<_>>(),
+        &page.next_cursor,
+        page.total,
+    );
+    Ok(json_with_etag(&headers, &page, &version))
+}
+
+/// Serialises `body` with an `ETag` hashed from `version`, answering
+/// `304 Not Modified` instead when `If-None-Match` already names that tag.
+/// `version` should leave out fields that change on every poll, so clients
+/// are not sent an unchanged list just because a counter moved.
+fn json_with_etag<T: Serialize, V: Hash>(headers: &HeaderMap, body: &T, version: &V) -> Response {
+    let body = serde_json::to_vec(body).expect("API responses serialise to JSON");
+    let mut hasher = DefaultHasher::new();
+    version.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| if_none_match_matches(value, &etag));
+    if cached {
+        debug!(%etag, "Client copy is current");
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::ETAG, etag),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Weak comparison, as RFC 9110 requires for `If-None-Match`: `W/` prefixes
+/// are ignored and `*` matches any tag.
+fn if_none_match_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+async fn set_vm_tags(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<TagsRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, tags = ?payload.tags, "Tag update request received");
+    let tags: Vec<&str> = payload.tags.iter().map(String::as_str).collect();
+    state
+        .client
+        .set_vm_tags(vmid, &tags)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Rejects vmids outside the Proxmox range before they reach the API.
+fn check_vmid(vmid: u64) -> Result<(), (StatusCode, Json<ApiError>)> {
+    validate_vmid(vmid).map_err(|err| {
+        warn!(vmid, "Rejected out-of-range VM ID");
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: err.to_string(),
+            }),
+        )
+    })
+}
+
+/// VMs carrying this tag cannot be deleted through the API.
+const PROTECTED_TAG: &str = "protected";
+
+/// Rejects with 403 when `vmid` is listed in `PVE_PROTECTED_VMIDS`.
+fn check_not_protected(state: &AppState, vmid: u64) -> Result<(), (StatusCode, Json<ApiError>)> {
+    if state.protected_vmids.contains(&vmid) {
+        warn!(vmid, "Refused to act on protected VM");
+        return Err(protected_vm_error(vmid));
+    }
+    Ok(())
+}
+
+/// `true` unless `PVE_ALLOWED_VMIDS` is set and leaves out `vmid`.
+fn is_allowed_vmid(allowed_vmids: &Option<HashSet<u64>>, vmid: u64) -> bool {
+    allowed_vmids
+        .as_ref()
+        .is_none_or(|allowed| allowed.contains(&vmid))
+}
+
+fn check_allowed(state: &AppState, vmid: u64) -> Result<(), (StatusCode, Json<ApiError>)> {
+    if !state.is_allowed(vmid) {
+        warn!(vmid, "Refused to act on VM outside PVE_ALLOWED_VMIDS");
+        return Err(not_allowed_error(vmid));
+    }
+    Ok(())
+}
+
+async fn delete_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    Query(query): Query<DeleteVmQuery>,
+) -> Result<(StatusCode, Json<DeleteVmStarted>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, purge = query.purge, "VM deletion requested");
+    check_not_protected(&state, vmid)?;
+    // Uncached: the guards below must see the VM's current tags and status.
+    let vms = state
+        .client
+        .list_vms_fresh()
+        .await
+        .map_err(map_proxmox_error)?;
+    let vm = vms.iter().find(|vm| vm.vmid == vmid).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: format!("VM {vmid} not found"),
+            }),
+        )
+    })?;
+
+    let is_fallback = state
+        .fallback
+        .as_ref()
+        .and_then(|fallback| fallback.selector().select(&vms))
+        .is_some_and(|fallback| fallback.vmid == vmid);
+    let protected = vm
+        .tags
+        .iter()
+        .any(|tag| tag.eq_ignore_ascii_case(PROTECTED_TAG));
+    if is_fallback || protected {
+        warn!(
+            vmid,
+            is_fallback, protected, "Refused to delete protected VM"
+        );
+        let reason = if is_fallback {
+            "is the fallback VM"
+        } else {
+            "is tagged protected"
+        };
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiError {
+                error: format!("VM {vmid} {reason} and cannot be deleted"),
+            }),
+        ));
+    }
+    if vm.status != VmStatus::Stopped {
+        warn!(vmid, status = %vm.status, "Refused to delete VM that is not stopped");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiError {
+                error: format!("VM {vmid} is {}; stop it before deleting", vm.status),
+            }),
+        ));
+    }
+
+    let task = state
+        .client
+        .delete_vm(vmid, query.purge)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(DeleteVmStarted {
+            task_id: task.to_string(),
+        }),
+    ))
+}
+
+/// Combined length of a guest agent command and its arguments.
+const MAX_AGENT_COMMAND_BYTES: usize = 4096;
+
+/// Runs a command inside a VM via the QEMU guest agent. Admin-only, since it
+/// amounts to remote code execution in the guest.
+async fn agent_exec(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<AgentExecRequest>,
+) -> Result<Json<ApiAgentExecResult>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let command_bytes = payload.command.len() + payload.args.iter().map(String::len).sum::<usize>();
+    if payload.command.is_empty() || command_bytes > MAX_AGENT_COMMAND_BYTES {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!(
+                    "Command must be non-empty and at most {MAX_AGENT_COMMAND_BYTES} bytes including arguments"
+                ),
+            }),
+        ));
+    }
+    let args: Vec<&str> = payload.args.iter().map(String::as_str).collect();
+    let result = state
+        .client
+        .execute_command_via_agent(vmid, &payload.command, &args)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(result.into()))
+}
+
+async fn vm_uptime(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<UptimeResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let uptime = state
+        .client
+        .vm_uptime(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(UptimeResponse {
+        uptime_secs: uptime.as_secs(),
+    }))
+}
+
+/// Proxmox rejects VNC tickets this long after issuing them.
+const CONSOLE_TICKET_TTL_SECS: u64 = 30;
+
+async fn vm_console_url(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<ConsoleUrlResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let config = state
+        .client
+        .get_vm_config(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    let ticket = state
+        .client
+        .create_console_ticket(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    let url = console_url(
+        state.client.base_url(),
+        vmid,
+        config.name.as_deref().unwrap_or_default(),
+        &ticket,
+    )
+    .map_err(|err| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(ApiError {
+                error: format!("Invalid Proxmox host URL: {err}"),
+            }),
+        )
+    })?;
+    info!(vmid, node = %ticket.node, "Issued VM console URL");
+    Ok(Json(ConsoleUrlResponse {
+        url,
+        expires_in_secs: CONSOLE_TICKET_TTL_SECS,
+    }))
+}
+
+/// noVNC page of the Proxmox web UI, logged in through the ticket.
+fn console_url(
+    pve_host: &str,
+    vmid: u64,
+    name: &str,
+    ticket: &ConsoleTicket,
+) -> Result<String, String> {
+    let mut url = reqwest::Url::parse(pve_host).map_err(|err| err.to_string())?;
+    url.set_path("/");
+    url.query_pairs_mut()
+        .append_pair("console", "kvm")
+        .append_pair("novnc", "1")
+        .append_pair("vmid", &vmid.to_string())
+        .append_pair("vmname", name)
+        .append_pair("node", &ticket.node)
+        .append_pair("resize", "off")
+        .append_pair("ticket", &ticket.ticket);
+    Ok(url.into())
+}
+
+async fn list_vm_tasks(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    Query(query): Query<TaskListQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    debug!(vmid, limit = ?query.limit, cursor = ?query.cursor, "Listing VM tasks");
+    let Some(cursor) = query.cursor else {
+        let tasks = state
+            .client
+            .list_vm_tasks(vmid, query.limit)
+            .await
+            .map_err(map_proxmox_error)?;
+        let tasks: Vec<ApiTask> = tasks.into_iter().map(ApiTask::from).collect();
+        return Ok(Json(tasks).into_response());
+    };
+    let limit = page_size(query.limit)?;
+    let lookahead = limit.saturating_add(1);
+    let tasks = if cursor.is_empty() {
+        state.client.list_vm_tasks(vmid, Some(lookahead)).await
+    } else {
+        let cursor = TaskId(cursor);
+        if cursor.starttime().is_none() {
+            return Err(invalid_cursor(cursor.as_str()));
+        }
+        state
+            .client
+            .list_vm_tasks_before(vmid, &cursor, lookahead)
+            .await
+    }
+    .map_err(map_proxmox_error)?;
+    let tasks = tasks.into_iter().map(ApiTask::from).collect();
+    let page =
+        PaginatedResponse::from_lookahead(tasks, limit, None, |task: &ApiTask| task.upid.clone());
+    Ok(Json(page).into_response())
+}
+
+async fn vm_metrics(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<ApiVmMetrics>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let timeframe = match query.timeframe.as_deref() {
+        None => RrdTimeframe::default(),
+        Some(raw) => raw.parse::<RrdTimeframe>().map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiError {
+                    error: format!(
+                        "Invalid timeframe '{raw}'; expected hour, day, week, month or year"
+                    ),
+                }),
+            )
+        })?,
+    };
+    debug!(vmid, timeframe = timeframe.as_str(), "Fetching VM metrics");
+    let data = state
+        .client
+        .get_vm_rrd_data(vmid, timeframe)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(ApiVmMetrics::new(timeframe, data)))
+}
+
+/// Proxmox rejects VM descriptions longer than this.
+const MAX_NOTES_BYTES: usize = 65535;
+
+async fn get_vm_notes(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<NotesResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    debug!(vmid, "Fetching VM notes");
+    let config = state
+        .client
+        .get_vm_config(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(NotesResponse {
+        notes: config.description.unwrap_or_default(),
+    }))
+}
+
+async fn set_vm_notes(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<NotesRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Notes update request received");
+    if payload.notes.len() > MAX_NOTES_BYTES {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!("Notes exceed the Proxmox limit of {MAX_NOTES_BYTES} bytes"),
+            }),
+        ));
+    }
+    state
+        .client
+        .set_vm_notes(vmid, &payload.notes)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn resize_vm_disk(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<ResizeDiskRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, disk = %payload.disk, size = %payload.size, "Disk resize request received");
+    let invalid = |error: String| (StatusCode::UNPROCESSABLE_ENTITY, Json(ApiError { error }));
+    if payload.disk.is_empty()
+        || !payload
+            .disk
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric())
+    {
+        return Err(invalid(format!("Invalid disk name '{}'", payload.disk)));
+    }
+    if !is_valid_disk_size(&payload.size) {
+        return Err(invalid(format!(
+            "Invalid size '{}'; expected +<N>G, +<N>M or <N>G",
+            payload.size
+        )));
+    }
+    state
+        .client
+        .resize_disk(vmid, &payload.disk, &payload.size)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_vm_cpu(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<CpuRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(
+        vmid,
+        sockets = payload.sockets,
+        cores = payload.cores,
+        "CPU change request received"
+    );
+    if !is_valid_cpu_topology(payload.sockets, payload.cores) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!(
+                    "Invalid CPU topology; sockets and cores must be at least 1 and their product at most {MAX_VCPUS}"
+                ),
+            }),
+        ));
+    }
+    state
+        .client
+        .set_vm_cpu(vmid, payload.sockets, payload.cores)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_vm_memory(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<MemoryRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(
+        vmid,
+        memory_mib = payload.memory_mib,
+        "Memory change request received"
+    );
+    if !is_valid_memory_mib(payload.memory_mib) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!("Invalid memory size; at least {MIN_MEMORY_MIB} MiB is required"),
+            }),
+        ));
+    }
+    state
+        .client
+        .set_vm_memory(vmid, payload.memory_mib)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn send_vm_key(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<SendKeyRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, key = %payload.key, "Send key request received");
+    if !is_allowed_keysym(&payload.key) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!(
+                    "Key '{}' is not allowed; expected one of: {}",
+                    payload.key,
+                    ALLOWED_KEYSYMS.join(", ")
+                ),
+            }),
+        ));
+    }
+    state
+        .client
+        .send_vm_keyboard_input(vmid, &payload.key)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admin-only, as it installs login credentials in the guest.
+async fn provision_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<ProvisionRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let options = ProvisionOptions::from(payload);
+    info!(
+        vmid,
+        cloudinit_storage = ?options.cloudinit_storage,
+        user = ?options.user.as_ref().map(|user| &user.username),
+        ipconfig_count = options.ipconfigs.len(),
+        "Provision request received"
+    );
+    let invalid = |error: String| (StatusCode::UNPROCESSABLE_ENTITY, Json(ApiError { error }));
+    if options == ProvisionOptions::default() {
+        return Err(invalid("Nothing to provision".to_string()));
+    }
+    options.validate().map_err(invalid)?;
+    state
+        .client
+        .provision_clone(vmid, &options)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admin-only, as Proxmox fetches the URL from the host's network.
+async fn import_vm_disk(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<ImportDiskRequest>,
+) -> Result<(StatusCode, Json<DiskImported>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, storage = %payload.storage, url = %payload.url, "Disk import request received");
+    let invalid = |error: String| (StatusCode::UNPROCESSABLE_ENTITY, Json(ApiError { error }));
+    if !is_valid_import_url(&payload.url) || import_disk_filename(&payload.url).is_none() {
+        return Err(invalid(format!(
+            "Invalid URL '{}'; expected an http:// or https:// link to an image file",
+            payload.url
+        )));
+    }
+    if !is_valid_storage_id(&payload.storage) {
+        return Err(invalid(format!("Invalid storage '{}'", payload.storage)));
+    }
+    if state.cancel.is_cancelled() {
+        return Err(shutting_down_error());
+    }
+    let import = state
+        .client
+        .start_disk_import(vmid, &payload.storage, &payload.url)
+        .await
+        .map_err(map_proxmox_error)?;
+    let upid = import.upid.to_string();
+
+    // The download can take far longer than a request should, so the image is
+    // attached in the background once it completes.
+    let client = state.client.clone();
+    state.flows.spawn(async move {
+        match client.finish_disk_import(vmid, &import).await {
+            Ok(slot) => info!(vmid, upid = %import.upid, disk = %slot, "Imported disk attached"),
+            Err(err) => warn!(vmid, upid = %import.upid, error = %err, "Disk import failed"),
+        }
+    });
+
+    info!(vmid, %upid, "Disk import detached from request lifecycle");
+    Ok((StatusCode::ACCEPTED, Json(DiskImported { upid })))
+}
+
+async fn vm_action(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<VmActionRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, action = ?payload.action, "VM action request received");
+    if payload.action != VmAction::Start {
+        check_not_protected(&state, vmid)?;
+    }
+    let client = &state.client;
+    match payload.action {
+        VmAction::Start => client.start_vm(vmid).await,
+        VmAction::Shutdown => client.shutdown_vm(vmid).await,
+        VmAction::Hibernate => client.hibernate_vm(vmid).await,
+        VmAction::Terminate => client.terminate_vm(vmid).await,
+        VmAction::Reboot => client.reboot_vm(vmid).await,
+        VmAction::Reset => client.reset_vm(vmid).await,
+    }
+    .map_err(map_proxmox_error)?;
+    info!(vmid, action = ?payload.action, "VM action command sent");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn start_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<PowerActionResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Direct VM start requested");
+    check_not_already(&state, vmid, VmStatus::Running).await?;
+    state
+        .client
+        .start_vm(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(PowerActionResponse { ok: true }))
+}
+
+async fn stop_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<PowerActionResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Direct VM stop requested");
+    check_not_protected(&state, vmid)?;
+    check_not_already(&state, vmid, VmStatus::Stopped).await?;
+    state
+        .client
+        .stop_vm(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(PowerActionResponse { ok: true }))
+}
+
+async fn shutdown_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<PowerActionResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Direct VM shutdown requested");
+    check_not_protected(&state, vmid)?;
+    check_not_already(&state, vmid, VmStatus::Stopped).await?;
+    state
+        .client
+        .shutdown_vm(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(PowerActionResponse { ok: true }))
+}
+
+async fn suspend_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<PowerTransitionResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Direct VM suspend requested");
+    check_currently(&state, vmid, VmStatus::Running).await?;
+    state
+        .client
+        .suspend_vm(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(PowerTransitionResponse {
+        status: "suspending",
+    }))
+}
+
+async fn resume_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<PowerTransitionResponse>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Direct VM resume requested");
+    check_currently(&state, vmid, VmStatus::Paused).await?;
+    state
+        .client
+        .resume_vm(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(PowerTransitionResponse { status: "resuming" }))
+}
+
+/// 409 unless the VM is in `expected`, e.g. resuming a VM that is not paused.
+async fn check_currently(
+    state: &AppState,
+    vmid: u64,
+    expected: VmStatus,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let status = state
+        .client
+        .vm_status(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    if status != expected {
+        warn!(vmid, %status, %expected, "VM not in the state the action needs");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiError {
+                error: format!("VM {vmid} is {status}, not {expected}"),
+            }),
+        ));
+    }
+    Ok(())
+}
+
+/// 409 when the VM is already in `target`, so a repeated call is visible.
+async fn check_not_already(
+    state: &AppState,
+    vmid: u64,
+    target: VmStatus,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let status = state
+        .client
+        .vm_status(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    if status == target {
+        warn!(vmid, %status, "VM already in requested state");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiError {
+                error: format!("VM {vmid} is already {status}"),
+            }),
+        ));
+    }
+    Ok(())
+}
+
+/// Tasks scanned for [`vm_status_history`]; power changes further back are not listed.
+const HISTORY_TASK_WINDOW: u32 = 100;
+
+/// Status changes of the VM, oldest first, read from its Proxmox task log so
+/// launches, shutdowns, fallback starts and changes made in the Proxmox UI
+/// are all included.
+async fn vm_status_history(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<Vec<VmStatusTransition>>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Listing VM status history");
+    // Confirms the VM exists, so unknown vmids answer 404 like other routes.
+    state
+        .client
+        .vm_status(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    let tasks = state
+        .client
+        .list_vm_tasks(vmid, Some(HISTORY_TASK_WINDOW))
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(status_transitions(&tasks)))
+}
+
+const MAX_BULK_SIZE: usize = 20;
+/// Cap on simultaneous Proxmox calls made by one bulk action.
+const BULK_CONCURRENCY: usize = 5;
+
+async fn bulk_vm_action(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<BulkActionRequest>,
+) -> Result<(StatusCode, Json<BulkActionResponse>), (StatusCode, Json<ApiError>)> {
+    info!(vmids = ?payload.vmids, action = ?payload.action, "Bulk VM action request received");
+    if payload.vmids.len() > MAX_BULK_SIZE {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiError {
+                error: format!("At most {MAX_BULK_SIZE} VMs can be targeted at once"),
+            }),
+        ));
+    }
+
+    let permits = Semaphore::new(BULK_CONCURRENCY);
+    let action = payload.action;
+    let results = join_all(payload.vmids.iter().map(|&vmid| {
+        let client = &state.client;
+        let protected_vmids = &state.protected_vmids;
+        let permits = &permits;
+        let allowed = state.is_allowed(vmid);
+        async move {
+            let _permit = permits.acquire().await.expect("bulk semaphore closed");
+            if !allowed {
+                warn!(vmid, action = ?action, "Bulk VM action skipped VM outside PVE_ALLOWED_VMIDS");
+                return BulkActionResult {
+                    vmid,
+                    ok: false,
+                    error: Some(format!("VM {vmid} is not managed by this agent")),
+                };
+            }
+            if action != BulkAction::Start && protected_vmids.contains(&vmid) {
+                warn!(vmid, action = ?action, "Bulk VM action skipped protected VM");
+                return BulkActionResult {
+                    vmid,
+                    ok: false,
+                    error: Some(format!("VM {vmid} is protected")),
+                };
+            }
+            let result = match action {
+                BulkAction::Start => client.start_vm(vmid).await,
+                BulkAction::Stop => client.stop_vm(vmid).await,
+                BulkAction::Shutdown => client.shutdown_vm(vmid).await,
+                BulkAction::Terminate => client.terminate_vm(vmid).await,
+            };
+            match result {
+                Ok(()) => BulkActionResult {
+                    vmid,
+                    ok: true,
+                    error: None,
+                },
+                Err(err) => {
+                    warn!(vmid, action = ?action, "Bulk VM action failed: {err}");
+                    BulkActionResult {
+                        vmid,
+                        ok: false,
+                        error: Some(err.to_string()),
+                    }
+                }
+            }
+        }
+    }))
+    .await;
+
+    let status = if results.iter().all(|result| result.ok) {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    info!(action = ?action, status = %status, "Bulk VM action finished");
+    Ok((status, Json(BulkActionResponse { results })))
+}
+
+async fn start_vm_backup(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<BackupRequest>,
+) -> Result<(StatusCode, Json<BackupStarted>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, storage = %payload.storage, mode = ?payload.mode, "Backup request received");
+    let upid = state
+        .client
+        .backup_vm(
+            vmid,
+            BackupOptions {
+                storage: payload.storage,
+                mode: payload.mode,
+                compress: payload.compress,
+                notes: payload.notes,
+            },
+        )
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(BackupStarted {
+            upid: upid.to_string(),
+        }),
+    ))
+}
+
+async fn list_vm_backups(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    Query(query): Query<BackupListQuery>,
+) -> Result<Json<Vec<ApiBackup>>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    debug!(vmid, storage = %query.storage, "Listing VM backups");
+    let node = state
+        .client
+        .vm_node(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    let backups = state
+        .client
+        .list_backups(&node, vmid, &query.storage)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(backups.into_iter().map(ApiBackup::from).collect()))
+}
+
+async fn list_snapshots(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<Vec<ApiSnapshot>>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Listing VM snapshots");
+    let snapshots = state
+        .client
+        .list_snapshots(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(snapshots.into_iter().map(ApiSnapshot::from).collect()))
+}
+
+async fn list_firewall_rules(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+) -> Result<Json<Vec<ApiFirewallRule>>, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, "Listing VM firewall rules");
+    let rules = state
+        .client
+        .get_firewall_rules(vmid)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(rules.into_iter().map(ApiFirewallRule::from).collect()))
+}
+
+async fn cluster_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiClusterStatus>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching cluster status");
+    let status = state
+        .client
+        .get_cluster_status()
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(ApiClusterStatus::from(status)))
+}
+
+/// Privileges the agent's Proxmox token holds, by path.
+async fn token_permissions(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BTreeMap<String, Vec<String>>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching API token permissions");
+    let permissions = state
+        .client
+        .get_current_permissions()
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(permissions.into_iter().collect()))
+}
+
+async fn list_nodes(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ApiNode>>, (StatusCode, Json<ApiError>)> {
+    info!("Listing cluster nodes");
+    let nodes = state.client.list_nodes().await.map_err(map_proxmox_error)?;
+    Ok(Json(nodes.into_iter().map(ApiNode::from).collect()))
+}
+
+async fn node_status(
+    State(state): State<Arc<AppState>>,
+    Path(node): Path<String>,
+) -> Result<Json<ApiNodeStatus>, (StatusCode, Json<ApiError>)> {
+    info!(node = %node, "Fetching node status");
+    let nodes = state.client.list_nodes().await.map_err(map_proxmox_error)?;
+    let info = nodes
+        .into_iter()
+        .find(|candidate| candidate.node == node)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: format!("Node {node} not found"),
+                }),
+            )
+        })?;
+    let status = state
+        .client
+        .node_status(&node)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(ApiNodeStatus::new(info, status)))
+}
+
+async fn list_storages(
+    State(state): State<Arc<AppState>>,
+    Path(node): Path<String>,
+) -> Result<Json<Vec<ApiStorage>>, (StatusCode, Json<ApiError>)> {
+    info!(node = %node, "Listing storage pools");
+    let storages = state
+        .client
+        .list_storages(&node)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(storages.into_iter().map(ApiStorage::from).collect()))
+}
+
+async fn list_node_vms(
+    State(state): State<Arc<AppState>>,
+    Path(node): Path<String>,
+) -> Result<Json<Vec<ApiVm>>, (StatusCode, Json<ApiError>)> {
+    info!(node = %node, "Listing VMs on node");
+    let vms = state
+        .client
+        .list_vms_on_node(&node)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(Json(
+        vms.into_iter()
+            .filter(|vm| state.is_allowed(vm.vmid))
+            .map(ApiVm::from)
+            .collect(),
+    ))
+}
+
+async fn create_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<SnapshotRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, snapshot = %payload.name, "Snapshot creation request received");
+    state
+        .client
+        .create_snapshot_named(vmid, &payload.name, payload.description.as_deref())
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn delete_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path((vmid, name)): Path<(u64, String)>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, snapshot = %name, "Snapshot deletion request received");
+    state
+        .client
+        .delete_snapshot(vmid, &name)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Only for stopped VMs: Proxmox would stop a running VM to roll it back.
+async fn rollback_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path((vmid, name)): Path<(u64, String)>,
+) -> Result<(StatusCode, Json<RollbackStarted>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    info!(vmid, snapshot = %name, "Snapshot rollback request received");
+    check_currently(&state, vmid, VmStatus::Stopped).await?;
+    let upid = state
+        .client
+        .rollback_snapshot(vmid, &name)
+        .await
+        .map_err(map_proxmox_error)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(RollbackStarted {
+            task_id: upid.to_string(),
+        }),
+    ))
+}
+
+/// The requested launch target, else `PVE_LAUNCH_TARGET_VMID`.
+fn launch_target(state: &AppState, vmid: Option<u64>) -> Result<u64, (StatusCode, Json<ApiError>)> {
+    vmid.or(state.launch_target_vmid).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "vmid required; no default configured".to_string(),
+            }),
+        )
+    })
+}
+
+async fn launch(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<LaunchRequest>,
+) -> Result<Json<LaunchResponse>, (StatusCode, Json<ApiError>)> {
+    let vmid = launch_target(&state, payload.vmid)?;
+    info!(target_vmid = vmid, action = ?payload.action, "Launch request received");
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let response = state
+        .launch_manager
+        .clone()
+        .launch(state.client.clone(), vmid, payload.action)
+        .await
+        .map_err(map_launch_error)?;
+    info!(target_vmid = vmid, status = ?response.status, "Launch request completed");
+    Ok(Json(response))
+}
+
+async fn launch_by_tag(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<LaunchByTagRequest>,
+) -> Result<Json<LaunchResponse>, (StatusCode, Json<ApiError>)> {
+    info!(tag = %payload.tag, action = ?payload.action, "Launch by tag request received");
+    let response = state
+        .launch_manager
+        .clone()
+        .launch_by_tag(state.client.clone(), &payload.tag, payload.action)
+        .await
+        .map_err(map_launch_error)?;
+    info!(tag = %payload.tag, status = ?response.status, "Launch by tag request completed");
+    Ok(Json(response))
+}
+
+/// Deprecated: blocks until the fork is visible. Prefer `POST /api/vms/:vmid/fork`.
+async fn fork_vm(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<ForkRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    info!(source_vmid = payload.vmid, new_name = %payload.name, "Fork request received");
+    warn!("/api/fork is deprecated; use /api/vms/:vmid/fork");
+    check_vmid(payload.vmid)?;
+    check_allowed(&state, payload.vmid)?;
+    payload.target.check_vmid_range()?;
+    let opts = payload.target.into_options(payload.name);
+    let new_vmid = unless_shutting_down(&state, async {
+        let new_vmid = state.client.fork_vm(payload.vmid, opts).await?;
+        wait_for_vm(&state.client, new_vmid, state.fork_wait).await?;
+        Ok(new_vmid)
+    })
+    .await?;
+    info!(new_vmid, "Fork request completed");
+    let (status, response) = if payload.start == Some(true) {
+        match state.client.start_vm(new_vmid).await {
+            Ok(()) => (StatusCode::OK, ForkResponse::started(new_vmid)),
+            Err(err) => {
+                warn!(new_vmid, error = %err, "Forked VM failed to start");
+                (
+                    StatusCode::MULTI_STATUS,
+                    ForkResponse::start_failed(new_vmid, err.to_string()),
+                )
+            }
+        }
+    } else {
+        (StatusCode::OK, ForkResponse::created(new_vmid))
+    };
+    Ok((
+        status,
+        [(header::HeaderName::from_static("deprecation"), "true")],
+        Json(response),
+    ))
+}
+
+/// Clones a VM, by default as a linked clone of its current state. Unlike
+/// forks, an existing snapshot can be cloned from directly.
+async fn clone_vm(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<CloneVmRequest>,
+) -> Result<(StatusCode, Json<ClonedVm>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let full_clone = payload.full.unwrap_or(false);
+    info!(
+        source_vmid = vmid,
+        new_name = %payload.name,
+        snapshot = ?payload.snapshot,
+        full_clone,
+        "Clone request received"
+    );
+    let opts = ForkOptions {
+        target_storage: payload.target_storage,
+        full_clone,
+        ..ForkOptions::new(payload.name)
+    };
+    let new_vmid = unless_shutting_down(&state, async {
+        let new_vmid = state
+            .client
+            .clone_vm_from(vmid, opts, payload.snapshot.as_deref())
+            .await?;
+        wait_for_vm(&state.client, new_vmid, state.fork_wait).await?;
+        Ok(new_vmid)
+    })
+    .await?;
+    info!(new_vmid, "Clone request completed");
+    Ok((StatusCode::CREATED, Json(ClonedVm { vmid: new_vmid })))
+}
+
+async fn start_fork_job(
+    State(state): State<Arc<AppState>>,
+    Path(vmid): Path<u64>,
+    ValidatedJson(payload): ValidatedJson<ForkJobRequest>,
+) -> Result<(StatusCode, Json<ForkJobCreated>), (StatusCode, Json<ApiError>)> {
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    payload.target.check_vmid_range()?;
+    info!(source_vmid = vmid, new_name = %payload.name, "Fork job request received");
+    if state.cancel.is_cancelled() {
+        return Err(shutting_down_error());
+    }
+    let (job_id, progress) = state.fork_jobs.create(vmid, &payload.name);
+
+    let client = state.client.clone();
+    let fork_wait = state.fork_wait;
+    let opts = payload.target.into_options(payload.name);
+    state.flows.spawn(async move {
+        run_fork_job(&client, vmid, opts, fork_wait, &progress).await;
+    });
+
+    info!(%job_id, source_vmid = vmid, "Fork job detached from request lifecycle");
+    Ok((StatusCode::ACCEPTED, Json(ForkJobCreated { job_id })))
+}
+
+async fn fork_job_status(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ForkJob>, (StatusCode, Json<ApiError>)> {
+    let progress = state
+        .fork_jobs
+        .get(id)
+        .ok_or_else(|| fork_job_not_found(id))?;
+    let job = progress.borrow().clone();
+    Ok(Json(job))
+}
+
+async fn vm_events_socket(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| stream_vm_events(state, socket))
+}
+
+/// Sends a `snapshot` of all VMs, then a `delta` for every batch of status
+/// changes until the client disconnects or the agent shuts down. A client
+/// that falls behind gets a fresh snapshot instead of the missed deltas.
+async fn stream_vm_events(state: Arc<AppState>, mut socket: WebSocket) {
+    let mut changes = state.vm_events.subscribe();
+    info!("VM status WebSocket connected");
+    if send_vm_snapshot(&state, &mut socket).await.is_err() {
+        return;
+    }
+    loop {
+        let message = tokio::select! {
+            _ = state.cancel.cancelled() => {
+                let _ = close_vm_socket(&mut socket, close_code::AWAY, "agent shutting down").await;
+                break;
+            }
+            received = socket.recv() => match received {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            change = changes.recv() => match change {
+                Ok(changes) => VmSocketMessage::Delta {
+                    changes: changes
+                        .iter()
+                        .filter(|change| state.is_allowed(change.vmid))
+                        .map(ApiVmStatusChange::from)
+                        .collect(),
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "VM status WebSocket lagged; resending snapshot");
+                    if send_vm_snapshot(&state, &mut socket).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    let _ = close_vm_socket(&mut socket, close_code::AWAY, "agent shutting down").await;
+                    break;
+                }
+            },
+        };
+        if send_vm_message(&mut socket, &message).await.is_err() {
+            break;
+        }
+    }
+    info!("VM status WebSocket disconnected");
+}
+
+async fn send_vm_snapshot(state: &AppState, socket: &mut WebSocket) -> Result<(), axum::Error> {
+    match state.client.list_vms().await {
+        Ok(vms) => {
+            let message = VmSocketMessage::Snapshot {
+                vms: vms
+                    .into_iter()
+                    .filter(|vm| state.is_allowed(vm.vmid))
+                    .map(ApiVm::from)
+                    .collect(),
+            };
+            send_vm_message(socket, &message).await
+        }
+        Err(err) => {
+            warn!(error = %err, "Failed to list VMs for WebSocket snapshot");
+            let _ = close_vm_socket(socket, close_code::ERROR, "failed to list VMs").await;
+            Err(axum::Error::new(err))
+        }
+    }
+}
+
+async fn send_vm_message(
+    socket: &mut WebSocket,
+    message: &VmSocketMessage,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text)).await
+}
+
+async fn close_vm_socket(
+    socket: &mut WebSocket,
+    code: u16,
+    reason: &'static str,
+) -> Result<(), axum::Error> {
+    socket
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })))
+        .await
+}
+
+async fn fork_job_events(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, (StatusCode, Json<ApiError>)> {
+    let progress = state
+        .fork_jobs
+        .get(id)
+        .ok_or_else(|| fork_job_not_found(id))?;
+    let receiver = progress.subscribe();
+    // Emit the current state first, then one event per change until the job
+    // finishes or the agent starts shutting down.
+    let events = stream::unfold(
+        (receiver, true, false),
+        |(mut receiver, first, finished)| async move {
+            if finished || (!first && receiver.changed().await.is_err()) {
+                return None;
+            }
+            let job = receiver.borrow_and_update().clone();
+            let finished = job.state.is_terminal();
+            let event = Event::default().event("progress").json_data(&job);
+            Some((event, (receiver, false, finished)))
+        },
+    )
+    .take_until(state.shutdown_started());
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn fork_job_not_found(id: Uuid) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiError {
+            error: format!("Fork job {id} not found"),
+        }),
+    )
+}
+
+async fn run_fork_job(
+    client: &ProxmoxClient,
+    vmid: u64,
+    opts: ForkOptions,
+    fork_wait: ForkWait,
+    progress: &watch::Sender<ForkJob>,
+) {
+    progress.send_modify(|job| job.state = ForkState::Cloning);
+    let outcome = match client.fork_vm(vmid, opts).await {
+        Ok(new_vmid) => {
+            progress.send_modify(|job| {
+                job.state = ForkState::WaitingForVm;
+                job.new_vmid = Some(new_vmid);
+            });
+            wait_for_vm(client, new_vmid, fork_wait).await
+        }
+        Err(err) => Err(err),
+    };
+    progress.send_modify(|job| {
+        job.finished_at_ms = Some(current_timestamp_ms());
+        match outcome {
+            Ok(()) => {
+                info!(job_id = %job.id, new_vmid = ?job.new_vmid, "Fork job completed");
+                job.state = ForkState::Completed;
+            }
+            Err(err) => {
+                warn!(job_id = %job.id, error = %err, "Fork job failed");
+                job.state = ForkState::Failed;
+                job.error = Some(err.to_string());
+            }
+        }
+    });
+}
+
+fn current_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+async fn launch_estimate(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LaunchEstimateQuery>,
+) -> Result<Json<LaunchEstimateResponse>, (StatusCode, Json<ApiError>)> {
+    let vmid = launch_target(&state, query.vmid)?;
+    check_vmid(vmid)?;
+    check_allowed(&state, vmid)?;
+    let estimate = state
+        .launch_manager
+        .estimate_launch_time(&state.client, vmid)
+        .await
+        .map_err(map_launch_error)?;
+    debug!(target_vmid = vmid, estimated_secs = estimate.duration.as_secs(), reason = %estimate.reason, "Estimated launch time");
+    Ok(Json(LaunchEstimateResponse {
+        estimated_secs: estimate.duration.as_secs(),
+        reason: estimate.reason,
+    }))
+}
+
+async fn launch_in_progress(State(state): State<Arc<AppState>>) -> Json<LaunchStateSummary> {
+    Json(state.launch_manager.current_state().await)
+}
+
+async fn host_shutdown_in_progress(
+    State(state): State<Arc<AppState>>,
+) -> Json<ShutdownStateSummary> {
+    Json(state.shutdown_manager.current_state().await)
+}
+
+async fn inhibit_fallback(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FallbackStateResponse>, (StatusCode, Json<ApiError>)> {
+    let fallback = require_fallback(&state)?;
+    fallback.inhibit();
+    Ok(Json(FallbackStateResponse { inhibited: true }))
+}
+
+async fn enable_fallback(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FallbackStateResponse>, (StatusCode, Json<ApiError>)> {
+    let fallback = require_fallback(&state)?;
+    fallback.enable();
+    Ok(Json(FallbackStateResponse { inhibited: false }))
+}
+
+async fn trigger_fallback(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FallbackTriggered>, (StatusCode, Json<ApiError>)> {
+    let fallback = require_fallback(&state)?;
+    if fallback.is_inhibited() {
+        warn!("Rejected fallback trigger while inhibited");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiError {
+                error: "fallback inhibited".to_string(),
+            }),
+        ));
+    }
+    fallback.trigger_now();
+    Ok(Json(FallbackTriggered { triggered: true }))
+}
+
+async fn get_fallback_config(State(state): State<Arc<AppState>>) -> Json<FallbackConfig> {
+    Json(FallbackConfig {
+        name: state.fallback.as_ref().and_then(FallbackHandle::name),
+    })
+}
+
+/// Renames the fallback VM; `null` disables a name-only fallback.
+async fn set_fallback_config(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<FallbackConfig>,
+) -> Result<Json<FallbackConfig>, (StatusCode, Json<ApiError>)> {
+    let fallback = require_fallback(&state)?;
+    let name = payload
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    fallback.set_name(name.clone());
+    Ok(Json(FallbackConfig { name }))
+}
+
+fn require_fallback(state: &AppState) -> Result<&FallbackHandle, (StatusCode, Json<ApiError>)> {
+    state.fallback.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Fallback monitoring is not running".to_string(),
+            }),
+        )
+    })
+}
+
+async fn host_shutdown(
+    State(state): State<Arc<AppState>>,
+    ValidatedJson(payload): ValidatedJson<ShutdownRequest>,
+) -> Result<Json<ShutdownResponse>, (StatusCode, Json<ApiError>)> {
+    info!(action = ?payload.action, "Host shutdown request received");
+    let response = state
+        .shutdown_manager
+        .clone()
+        .shutdown(state.client.clone(), payload.action)
+        .await
+        .map_err(map_shutdown_error)?;
+    info!(status = ?response.status, "Host shutdown request completed");
+    Ok(Json(response))
+}
+
+#[derive(Debug, Serialize)]
+struct SystemStatus {
+    vms: Vec<ApiVm>,
+    launch_in_progress: bool,
+    shutdown_in_progress: bool,
+    fallback_inhibited: bool,
+    agent_uptime_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct FallbackStateResponse {
+    inhibited: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct FallbackConfig {
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FallbackTriggered {
+    triggered: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmListQuery {
+    tag: Option<String>,
+    status: Option<String>,
+    /// Case-insensitive substring of the VM name.
+    name_contains: Option<String>,
+    limit: Option<u32>,
+    /// vmid of the last VM of the previous page, or empty for the first
+    /// page; pages are ordered by vmid.
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiVm {
+    vmid: u64,
+    name: String,
+    tags: Vec<String>,
+    status: String,
+    notes: Option<String>,
+    node: Option<String>,
+    maxmem: Option<u64>,
+    maxcpu: Option<f64>,
+    disk: Option<u64>,
+    uptime: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum VmSocketMessage {
+    Snapshot { vms: Vec<ApiVm> },
+    Delta { changes: Vec<ApiVmStatusChange> },
+}
+
+#[derive(Debug, Serialize)]
+struct ApiVmStatusChange {
+    vmid: u64,
+    status: String,
+}
+
+impl From<&VmStatusChange> for ApiVmStatusChange {
+    fn from(change: &VmStatusChange) -> Self {
+        Self {
+            vmid: change.vmid,
+            status: change.status.to_string(),
+        }
+    }
+}
+
+impl ApiVm {
+    /// What identifies this VM's state for `ETag`s; `uptime` and `disk`
+    /// change on every poll while the VM runs.
+    fn stable_fields(&self) -> (u64, &str, &[String], &str, Option<&str>, Option<&str>) {
+        (
+            self.vmid,
+            &self.name,
+            &self.tags,
+            &self.status,
+            self.notes.as_deref(),
+            self.node.as_deref(),
+        )
+    }
+}
+
+impl From<VmInfo> for ApiVm {
+    fn from(vm: VmInfo) -> Self {
+        Self {
+            vmid: vm.vmid,
+            name: vm.name,
+            tags: vm.tags,
+            status: vm.status.to_string(),
+            notes: vm.notes,
+            node: vm.node,
+            maxmem: vm.maxmem,
+            maxcpu: vm.maxcpu,
+            disk: vm.disk,
+            uptime: vm.uptime,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteVmQuery {
+    #[serde(default)]
+    purge: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteVmStarted {
+    task_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsRequest {
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotesRequest {
+    notes: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ConsoleUrlResponse {
+    url: String,
+    expires_in_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct UptimeResponse {
+    uptime_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct NotesResponse {
+    notes: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResizeDiskRequest {
+    disk: String,
+    size: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CpuRequest {
+    sockets: u32,
+    cores: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemoryRequest {
+    memory_mib: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportDiskRequest {
+    storage: String,
+    /// `http://` or `https://` URL of a disk image, e.g. a cloud image.
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DiskImported {
+    upid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProvisionRequest {
+    cloudinit_storage: Option<String>,
+    user: Option<ProvisionUser>,
+    #[serde(default)]
+    ipconfigs: Vec<ProvisionIpConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProvisionUser {
+    username: String,
+    /// crypt(3) hash such as `$6$...`; plain passwords are not accepted.
+    password_hash: Option<String>,
+    #[serde(default)]
+    ssh_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProvisionIpConfig {
+    interface: u8,
+    ip: String,
+    gw: Option<String>,
+}
+
+impl From<ProvisionRequest> for ProvisionOptions {
+    fn from(request: ProvisionRequest) -> Self {
+        Self {
+            cloudinit_storage: request.cloudinit_storage,
+            user: request.user.map(|user| CloudInitUser {
+                username: user.username,
+                password_hash: user.password_hash,
+                ssh_keys: user.ssh_keys,
+            }),
+            ipconfigs: request
+                .ipconfigs
+                .into_iter()
+                .map(|ipconfig| IpConfig {
+                    interface_index: ipconfig.interface,
+                    ip: ipconfig.ip,
+                    gw: ipconfig.gw,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SendKeyRequest {
+    /// QEMU key name such as `ctrl-alt-delete`.
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmActionRequest {
+    action: VmAction,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum VmAction {
+    Start,
+    Shutdown,
+    Hibernate,
+    Terminate,
+    Reboot,
+    Reset,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum BulkAction {
+    Start,
+    Stop,
+    Shutdown,
+    Terminate,
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkActionRequest {
+    vmids: Vec<u64>,
+    action: BulkAction,
 }
 
-async fn app_js() -> impl IntoResponse {
-    debug!("Serving app JavaScript");
-    (
-        [(axum::http::header::CONTENT_TYPE, "application/javascript")],
-        APP_JS,
-    )
+#[derive(Debug, Serialize)]
+struct PowerActionResponse {
+    ok: bool,
 }
 
-async fn list_vms(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<Vec<ApiVm>>, (StatusCode, Json<ApiError>)> {
-    info!("Listing VMs");
-    let vms = state.client.list_vms().await.map_err(map_proxmox_error)?;
-    info!(vm_count = vms.len(), "VM list retrieved");
-    let response = vms.into_iter().map(ApiVm::from).collect();
-    Ok(Json(response))
+/// Suspend and resume are reported as started; Proxmox changes the status
+/// asynchronously.
+#[derive(Debug, Serialize)]
+struct PowerTransitionResponse {
+    status: &'static str,
 }
 
-async fn launch(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<LaunchRequest>,
-) -> Result<Json<LaunchResponse>, (StatusCode, Json<ApiError>)> {
-    info!(target_vmid = payload.vmid, action = ?payload.action, "Launch request received");
-    let response = state
-        .launch_manager
-        .clone()
-        .launch(state.client.clone(), payload.vmid, payload.action)
-        .await
-        .map_err(map_launch_error)?;
-    info!(target_vmid = payload.vmid, status = ?response.status, "Launch request completed");
-    Ok(Json(response))
+#[derive(Debug, Serialize)]
+struct BulkActionResponse {
+    results: Vec<BulkActionResult>,
 }
 
-async fn fork_vm(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<ForkRequest>,
-) -> Result<Json<ForkResponse>, (StatusCode, Json<ApiError>)> {
-    info!(source_vmid = payload.vmid, new_name = %payload.name, "Fork request received");
-    let new_vmid = state
-        .client
-        .fork_vm(payload.vmid, &payload.name)
-        .await
-        .map_err(map_proxmox_error)?;
-    wait_for_vm(&state.client, new_vmid)
-        .await
-        .map_err(map_proxmox_error)?;
-    info!(new_vmid, "Fork request completed");
-    Ok(Json(ForkResponse::created(new_vmid)))
+#[derive(Debug, Serialize)]
+struct BulkActionResult {
+    vmid: u64,
+    ok: bool,
+    error: Option<String>,
 }
 
-async fn host_shutdown(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<ShutdownRequest>,
-) -> Result<Json<ShutdownResponse>, (StatusCode, Json<ApiError>)> {
-    info!(action = ?payload.action, "Host shutdown request received");
-    let response = state
-        .shutdown_manager
-        .clone()
-        .shutdown(state.client.clone(), payload.action)
-        .await
-        .map_err(map_shutdown_error)?;
-    info!(status = ?response.status, "Host shutdown request completed");
-    Ok(Json(response))
+#[derive(Debug, Deserialize)]
+struct SnapshotRequest {
+    name: String,
+    description: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
-struct ApiVm {
-    vmid: u64,
+struct ApiSnapshot {
     name: String,
-    tags: Vec<String>,
+    description: Option<String>,
+    creation_time: Option<u64>,
+    parent: Option<String>,
+}
+
+impl From<SnapshotInfo> for ApiSnapshot {
+    fn from(snapshot: SnapshotInfo) -> Self {
+        Self {
+            name: snapshot.name,
+            description: snapshot.description,
+            creation_time: snapshot.snaptime,
+            parent: snapshot.parent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ApiFirewallRule {
+    pos: u32,
+    action: String,
+    #[serde(rename = "type")]
+    rule_type: String,
+    enabled: bool,
+    comment: Option<String>,
+    source: Option<String>,
+    dest: Option<String>,
+}
+
+impl From<FirewallRule> for ApiFirewallRule {
+    fn from(rule: FirewallRule) -> Self {
+        Self {
+            pos: rule.pos,
+            action: rule.action,
+            rule_type: rule.type_,
+            enabled: rule.enable,
+            comment: rule.comment,
+            source: rule.source,
+            dest: rule.dest,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ApiNode {
+    node: String,
     status: String,
-    notes: Option<String>,
+    cpu: f64,
+    mem: u64,
+    maxmem: u64,
 }
 
-impl From<VmInfo> for ApiVm {
-    fn from(vm: VmInfo) -> Self {
+#[derive(Debug, Serialize)]
+struct ApiClusterStatus {
+    name: String,
+    quorate: bool,
+    nodes: Vec<ApiClusterNode>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiClusterNode {
+    name: String,
+    online: bool,
+    local: bool,
+}
+
+impl From<ClusterStatus> for ApiClusterStatus {
+    fn from(status: ClusterStatus) -> Self {
         Self {
-            vmid: vm.vmid,
-            name: vm.name,
-            tags: vm.tags,
-            status: match vm.status {
-                VmStatus::Running => "running".to_string(),
-                VmStatus::Stopped => "stopped".to_string(),
-                VmStatus::Unknown => "unknown".to_string(),
+            name: status.name,
+            quorate: status.quorate,
+            nodes: status
+                .nodes
+                .into_iter()
+                .map(|node| ApiClusterNode {
+                    name: node.name,
+                    online: node.online,
+                    local: node.local,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<NodeInfo> for ApiNode {
+    fn from(node: NodeInfo) -> Self {
+        Self {
+            node: node.node,
+            status: node.status,
+            cpu: node.cpu,
+            mem: node.mem,
+            maxmem: node.maxmem,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ApiNodeStatus {
+    #[serde(flatten)]
+    node: ApiNode,
+    uptime: u64,
+    kernel_version: String,
+}
+
+impl ApiNodeStatus {
+    /// Live figures from the status call take precedence over the cluster listing.
+    fn new(info: NodeInfo, status: NodeStatus) -> Self {
+        Self {
+            node: ApiNode {
+                node: info.node,
+                status: info.status,
+                cpu: status.cpu,
+                mem: status.mem,
+                maxmem: status.maxmem,
             },
-            notes: vm.notes,
+            uptime: status.uptime,
+            kernel_version: status.kernel_version,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ApiStorage {
+    storage: String,
+    #[serde(rename = "type")]
+    storage_type: String,
+    avail: u64,
+    total: u64,
+    used: u64,
+    enabled: bool,
+}
+
+impl From<StorageInfo> for ApiStorage {
+    fn from(storage: StorageInfo) -> Self {
+        Self {
+            storage: storage.storage,
+            storage_type: storage.type_,
+            avail: storage.avail,
+            total: storage.total,
+            used: storage.used,
+            enabled: storage.enabled,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    /// Omitted when the cluster status could not be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cluster_quorate: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_log: Option<RemoteLogHealth>,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoteLogHealth {
+    pending_entries: usize,
+    pending_bytes: usize,
+    buffer_full: bool,
+}
+
+impl RemoteLogHealth {
+    async fn observe(remote: &RemoteLogHandle) -> Self {
+        let pending_bytes = remote.pending_bytes().await;
+        Self {
+            pending_entries: remote.pending_count().await,
+            pending_bytes,
+            buffer_full: remote.is_buffer_full(pending_bytes),
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
 struct LaunchRequest {
-    vmid: u64,
+    /// Falls back to `PVE_LAUNCH_TARGET_VMID` when left out.
+    vmid: Option<u64>,
+    action: Option<LaunchAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LaunchEstimateQuery {
+    vmid: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct LaunchEstimateResponse {
+    estimated_secs: u64,
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LaunchByTagRequest {
+    tag: String,
     action: Option<LaunchAction>,
 }
 
@@ -258,6 +2743,7 @@ impl LaunchResponse {
             allowed_actions: vec![
                 LaunchAction::Shutdown,
                 LaunchAction::Hibernate,
+                LaunchAction::Suspend,
                 LaunchAction::Terminate,
                 LaunchAction::Cancel,
             ],
@@ -295,10 +2781,19 @@ enum LaunchStatus {
 enum LaunchAction {
     Shutdown,
     Hibernate,
+    Suspend,
     Terminate,
     Cancel,
 }
 
+impl LaunchAction {
+    /// Whether a VM in `status` no longer blocks the flow after this action.
+    /// A suspended VM stays paused in RAM rather than stopping.
+    fn is_settled(self, status: VmStatus) -> bool {
+        status == VmStatus::Stopped || (self == Self::Suspend && status == VmStatus::Paused)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ShutdownRequest {
     action: Option<LaunchAction>,
@@ -331,67 +2826,465 @@ impl ShutdownResponse {
         }
     }
 
-    fn needs_action(vm: &VmInfo) -> Self {
+    fn needs_action(vm: &VmInfo) -> Self {
+        Self {
+            status: ShutdownStatus::NeedsAction,
+            message: "A VM is currently running; choose an action before shutdown.".to_string(),
+            running_vm: Some(RunningVmInfo::from(vm)),
+            allowed_actions: vec![
+                LaunchAction::Shutdown,
+                LaunchAction::Hibernate,
+                LaunchAction::Terminate,
+                LaunchAction::Cancel,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ShutdownStatus {
+    Started,
+    NeedsAction,
+    Cancelled,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloneVmRequest {
+    name: String,
+    /// Existing snapshot to clone from instead of the current state.
+    snapshot: Option<String>,
+    /// Defaults to a linked clone.
+    full: Option<bool>,
+    target_storage: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ClonedVm {
+    vmid: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForkRequest {
+    vmid: u64,
+    name: String,
+    /// Start the new VM once it exists.
+    start: Option<bool>,
+    #[serde(flatten)]
+    target: ForkTarget,
+}
+
+/// Optional clone placement accepted by both fork endpoints.
+#[derive(Debug, Deserialize)]
+struct ForkTarget {
+    target_storage: Option<String>,
+    target_node: Option<String>,
+    target_pool: Option<String>,
+    #[serde(default = "full_clone_by_default")]
+    full_clone: bool,
+    /// `[start, end]`, both inclusive.
+    vmid_range: Option<(u64, u64)>,
+}
+
+fn full_clone_by_default() -> bool {
+    true
+}
+
+impl ForkTarget {
+    fn check_vmid_range(&self) -> Result<(), (StatusCode, Json<ApiError>)> {
+        let Some((start, end)) = self.vmid_range else {
+            return Ok(());
+        };
+        check_vmid(start)?;
+        check_vmid(end)?;
+        if start > end {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiError {
+                    error: format!("vmid_range start {start} is above its end {end}"),
+                }),
+            ));
+        }
+        Ok(())
+    }
+
+    fn into_options(self, name: String) -> ForkOptions {
+        ForkOptions {
+            name,
+            target_storage: self.target_storage,
+            target_node: self.target_node,
+            target_pool: self.target_pool,
+            full_clone: self.full_clone,
+            vmid_range: self.vmid_range,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ForkResponse {
+    status: ForkStatus,
+    message: String,
+    vmid: u64,
+    started: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ForkResponse {
+    fn created(vmid: u64) -> Self {
+        Self {
+            status: ForkStatus::Created,
+            message: "VM fork created.".to_string(),
+            vmid,
+            started: false,
+            error: None,
+        }
+    }
+
+    fn started(vmid: u64) -> Self {
+        Self {
+            message: "VM fork created and started.".to_string(),
+            started: true,
+            ..Self::created(vmid)
+        }
+    }
+
+    fn start_failed(vmid: u64, error: String) -> Self {
+        Self {
+            status: ForkStatus::CreatedButStartFailed,
+            message: "VM fork created but could not be started.".to_string(),
+            vmid,
+            started: false,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ForkStatus {
+    Created,
+    CreatedButStartFailed,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackupRequest {
+    storage: String,
+    #[serde(default)]
+    mode: BackupMode,
+    #[serde(default)]
+    compress: BackupCompression,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BackupStarted {
+    upid: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RollbackStarted {
+    task_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskListQuery {
+    limit: Option<u32>,
+    /// UPID of the last task of the previous page, or empty for the first page.
+    cursor: Option<String>,
+}
+
+/// Page size when a `cursor` is sent without a `limit`.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Largest `limit` a paged list accepts.
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// One page of a list, returned instead of a bare array once `cursor` is
+/// given; an empty `cursor` asks for the first page. `next_cursor` is passed
+/// back as `cursor` for the next page and is null on the last one.
+#[derive(Debug, Serialize)]
+struct PaginatedResponse<T> {
+    data: Vec<T>,
+    next_cursor: Option<String>,
+    /// Entries across all pages, when known.
+    total: Option<u64>,
+}
+
+impl<T> PaginatedResponse<T> {
+    /// Builds a page from up to `limit + 1` entries; an extra entry only shows
+    /// that another page follows.
+    fn from_lookahead(
+        mut data: Vec<T>,
+        limit: u32,
+        total: Option<u64>,
+        cursor_of: impl Fn(&T) -> String,
+    ) -> Self {
+        let next_cursor = if data.len() > limit as usize {
+            data.truncate(limit as usize);
+            data.last().map(cursor_of)
+        } else {
+            None
+        };
+        Self {
+            data,
+            next_cursor,
+            total,
+        }
+    }
+}
+
+fn page_size(limit: Option<u32>) -> Result<u32, (StatusCode, Json<ApiError>)> {
+    let error = match limit.unwrap_or(DEFAULT_PAGE_SIZE) {
+        0 => "limit must be at least 1".to_string(),
+        limit if limit > MAX_PAGE_SIZE => format!("limit must be at most {MAX_PAGE_SIZE}"),
+        limit => return Ok(limit),
+    };
+    Err((StatusCode::BAD_REQUEST, Json(ApiError { error })))
+}
+
+fn invalid_cursor(cursor: &str) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ApiError {
+            error: format!("Invalid cursor '{cursor}'"),
+        }),
+    )
+}
+
+#[derive(Debug, Serialize)]
+struct ApiTask {
+    upid: String,
+    #[serde(rename = "type")]
+    type_: String,
+    status: String,
+    starttime: u64,
+    endtime: Option<u64>,
+    user: String,
+}
+
+impl From<TaskSummary> for ApiTask {
+    fn from(task: TaskSummary) -> Self {
+        Self {
+            upid: task.upid,
+            type_: task.type_,
+            status: task.status,
+            starttime: task.starttime,
+            endtime: task.endtime,
+            user: task.user,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MetricsQuery {
+    timeframe: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiVmMetrics {
+    timeframe: &'static str,
+    timestamps: Vec<u64>,
+    cpu: Vec<Option<f64>>,
+    mem: Vec<Option<f64>>,
+    netin: Vec<Option<f64>>,
+    netout: Vec<Option<f64>>,
+}
+
+impl ApiVmMetrics {
+    fn new(timeframe: RrdTimeframe, data: VmRrdData) -> Self {
         Self {
-            status: ShutdownStatus::NeedsAction,
-            message: "A VM is currently running; choose an action before shutdown.".to_string(),
-            running_vm: Some(RunningVmInfo::from(vm)),
-            allowed_actions: vec![
-                LaunchAction::Shutdown,
-                LaunchAction::Hibernate,
-                LaunchAction::Terminate,
-                LaunchAction::Cancel,
-            ],
+            timeframe: timeframe.as_str(),
+            timestamps: data.timestamps,
+            cpu: data.cpu,
+            mem: data.mem,
+            netin: data.netin,
+            netout: data.netout,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct BackupListQuery {
+    storage: String,
+}
+
 #[derive(Debug, Serialize)]
-#[serde(rename_all = "snake_case")]
-enum ShutdownStatus {
-    Started,
-    NeedsAction,
-    Cancelled,
+struct ApiBackup {
+    volid: String,
+    size: u64,
+    ctime: u64,
+    format: String,
+    notes: Option<String>,
+}
+
+impl From<BackupInfo> for ApiBackup {
+    fn from(backup: BackupInfo) -> Self {
+        Self {
+            volid: backup.volid,
+            size: backup.size,
+            ctime: backup.ctime,
+            format: backup.format,
+            notes: backup.notes,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
-struct ForkRequest {
-    vmid: u64,
-    name: String,
+struct AgentExecRequest {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
-struct ForkResponse {
-    status: ForkStatus,
-    message: String,
-    vmid: u64,
+struct ApiAgentExecResult {
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
 }
 
-impl ForkResponse {
-    fn created(vmid: u64) -> Self {
+impl From<AgentExecResult> for ApiAgentExecResult {
+    fn from(result: AgentExecResult) -> Self {
         Self {
-            status: ForkStatus::Created,
-            message: "VM fork created.".to_string(),
-            vmid,
+            exit_code: result.exit_code,
+            stdout: result.stdout,
+            stderr: result.stderr,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct ForkJobRequest {
+    name: String,
+    #[serde(flatten)]
+    target: ForkTarget,
+}
+
 #[derive(Debug, Serialize)]
+struct ForkJobCreated {
+    job_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ForkJob {
+    id: Uuid,
+    source_vmid: u64,
+    target_name: String,
+    state: ForkState,
+    new_vmid: Option<u64>,
+    error: Option<String>,
+    started_at_ms: u64,
+    finished_at_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-enum ForkStatus {
-    Created,
+enum ForkState {
+    Queued,
+    Cloning,
+    WaitingForVm,
+    Completed,
+    Failed,
+}
+
+impl ForkState {
+    fn is_terminal(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// Finished jobs are kept around this long so clients can still poll the outcome.
+const FORK_JOB_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Default)]
+struct ForkJobs {
+    jobs: StdMutex<HashMap<Uuid, watch::Sender<ForkJob>>>,
+}
+
+impl ForkJobs {
+    fn create(&self, source_vmid: u64, target_name: &str) -> (Uuid, watch::Sender<ForkJob>) {
+        let id = Uuid::new_v4();
+        let (progress, _) = watch::channel(ForkJob {
+            id,
+            source_vmid,
+            target_name: target_name.to_string(),
+            state: ForkState::Queued,
+            new_vmid: None,
+            error: None,
+            started_at_ms: current_timestamp_ms(),
+            finished_at_ms: None,
+        });
+
+        let mut jobs = self.lock_jobs();
+        let cutoff = current_timestamp_ms().saturating_sub(FORK_JOB_RETENTION.as_millis() as u64);
+        jobs.retain(|_, job| job.borrow().finished_at_ms.is_none_or(|at| at > cutoff));
+        jobs.insert(id, progress.clone());
+        (id, progress)
+    }
+
+    fn get(&self, id: Uuid) -> Option<watch::Sender<ForkJob>> {
+        self.lock_jobs().get(&id).cloned()
+    }
+
+    fn lock_jobs(&self) -> MutexGuard<'_, HashMap<Uuid, watch::Sender<ForkJob>>> {
+        self.jobs.lock().unwrap_or_else(|err| err.into_inner())
+    }
 }
 
 #[derive(Debug, Serialize)]
-struct ApiError {
-    error: String,
+pub(crate) struct ApiError {
+    pub(crate) error: String,
+}
+
+/// `Json<T>` that reports rejected bodies as an [`ApiError`]: 422 for
+/// malformed JSON or a body that does not fit `T`, otherwise the status axum
+/// picked (e.g. 415 without a JSON content type).
+struct ValidatedJson<T>(T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let rejection = match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => return Ok(Self(value)),
+            Err(rejection) => rejection,
+        };
+        let status = match rejection {
+            JsonRejection::JsonSyntaxError(_) | JsonRejection::JsonDataError(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            _ => rejection.status(),
+        };
+        let detail = rejection.body_text();
+        warn!(%status, error = %detail, "Rejected request body");
+        Err((
+            status,
+            Json(ApiError {
+                error: format!("JSON parse error: {detail}"),
+            }),
+        ))
+    }
 }
 
 fn map_proxmox_error(err: ProxmoxError) -> (StatusCode, Json<ApiError>) {
     warn!(error = %err, "Proxmox API call failed");
+    let status = match err {
+        ProxmoxError::NotFound(_) | ProxmoxError::MissingNode(_) => StatusCode::NOT_FOUND,
+        ProxmoxError::Unauthorized => StatusCode::UNAUTHORIZED,
+        ProxmoxError::Forbidden => StatusCode::FORBIDDEN,
+        ProxmoxError::Conflict(_) => StatusCode::CONFLICT,
+        ProxmoxError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ProxmoxError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::BAD_GATEWAY,
+    };
     (
-        StatusCode::BAD_GATEWAY,
+        status,
         Json(ApiError {
             error: err.to_string(),
         }),
@@ -409,6 +3302,28 @@ fn map_launch_error(err: LaunchError) -> (StatusCode, Json<ApiError>) {
                 }),
             )
         }
+        LaunchError::ShuttingDown => shutting_down_error(),
+        LaunchError::NoMatch(tag) => {
+            warn!(tag = %tag, "No VM carries the requested launch tag");
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: format!("No VM is tagged {tag}"),
+                }),
+            )
+        }
+        LaunchError::AmbiguousTag(tag, vmids) => {
+            warn!(tag = %tag, ?vmids, "Launch tag matches several VMs");
+            let vmids: Vec<String> = vmids.iter().map(u64::to_string).collect();
+            (
+                StatusCode::CONFLICT,
+                Json(ApiError {
+                    error: format!("Tag {tag} matches several VMs: {}", vmids.join(", ")),
+                }),
+            )
+        }
+        LaunchError::ProtectedVm(vmid) => protected_vm_error(vmid),
+        LaunchError::NotAllowed(vmid) => not_allowed_error(vmid),
         LaunchError::LaunchFailed(err) => {
             warn!(error = %err, "Launch workflow failed");
             (StatusCode::BAD_GATEWAY, Json(ApiError { error: err }))
@@ -428,6 +3343,9 @@ fn map_shutdown_error(err: ShutdownError) -> (StatusCode, Json<ApiError>) {
                 }),
             )
         }
+        ShutdownError::ShuttingDown => shutting_down_error(),
+        ShutdownError::ProtectedVm(vmid) => protected_vm_error(vmid),
+        ShutdownError::NotAllowed(vmid) => not_allowed_error(vmid),
         ShutdownError::Proxmox(err) => map_proxmox_error(err),
         ShutdownError::ShutdownFailed(err) => {
             warn!(error = %err, "Host shutdown workflow failed");
@@ -436,16 +3354,77 @@ fn map_shutdown_error(err: ShutdownError) -> (StatusCode, Json<ApiError>) {
     }
 }
 
-async fn wait_for_vm(client: &ProxmoxClient, vmid: u64) -> Result<(), ProxmoxError> {
+fn not_allowed_error(vmid: u64) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ApiError {
+            error: format!("VM {vmid} is not managed by this agent"),
+        }),
+    )
+}
+
+fn protected_vm_error(vmid: u64) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ApiError {
+            error: format!("VM {vmid} is protected and cannot be stopped or deleted"),
+        }),
+    )
+}
+
+/// Awaits `work`, giving up with 503 once the agent starts shutting down so a
+/// request waiting on a long Proxmox task cannot hold up exit.
+async fn unless_shutting_down<T>(
+    state: &AppState,
+    work: impl Future<Output = Result<T, ProxmoxError>>,
+) -> Result<T, (StatusCode, Json<ApiError>)> {
+    tokio::select! {
+        result = work => result.map_err(map_proxmox_error),
+        _ = state.cancel.cancelled() => {
+            warn!("Abandoning request wait; agent is shutting down");
+            Err(shutting_down_error())
+        }
+    }
+}
+
+fn shutting_down_error() -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ApiError {
+            error: "Agent is shutting down".to_string(),
+        }),
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ForkWait {
+    attempts: u32,
+    interval: Duration,
+}
+
+impl Default for ForkWait {
+    fn default() -> Self {
+        Self {
+            attempts: 30,
+            interval: Duration::from_secs(2),
+        }
+    }
+}
+
+async fn wait_for_vm(
+    client: &ProxmoxClient,
+    vmid: u64,
+    fork_wait: ForkWait,
+) -> Result<(), ProxmoxError> {
     info!(vmid, "Waiting for forked VM to appear in Proxmox inventory");
-    for attempt in 1..=30 {
-        let vms = client.list_vms().await?;
+    for attempt in 1..=fork_wait.attempts {
+        let vms = client.list_vms_fresh().await?;
         if vms.iter().any(|vm| vm.vmid == vmid) {
             info!(vmid, attempt, "Forked VM is now visible");
             return Ok(());
         }
         debug!(vmid, attempt, "Forked VM not visible yet; retrying");
-        sleep(Duration::from_secs(2)).await;
+        sleep(fork_wait.interval).await;
     }
     warn!(vmid, "Timed out waiting for forked VM to appear");
     Err(ProxmoxError::Api(format!(
@@ -457,18 +3436,110 @@ async fn wait_for_vm(client: &ProxmoxClient, vmid: u64) -> Result<(), ProxmoxErr
 struct LaunchState {
     in_progress: bool,
     requested_action: Option<LaunchAction>,
+    target_vmid: Option<u64>,
+    started_at_ms: Option<u64>,
+}
+
+/// Launch state published to [`AppState::subscribe_launch_status`]
+/// subscribers each time a launch flow starts or finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LaunchStateSnapshot {
+    pub in_progress: bool,
+    pub target_vmid: Option<u64>,
+    pub started_at: Option<Instant>,
+}
+
+#[derive(Debug, Serialize)]
+struct LaunchStateSummary {
+    in_progress: bool,
+    target_vmid: Option<u64>,
+    current_action: Option<LaunchAction>,
+    started_at_ms: Option<u64>,
+}
+
+/// Rough durations reported by `GET /api/launch/estimate`.
+#[derive(Debug, Clone, Copy)]
+pub struct LaunchTimeEstimates {
+    /// Nothing to stop; only the target has to boot.
+    pub no_running_vm: Duration,
+    /// The running VM is tagged `easy-kill` and gets terminated.
+    pub terminate: Duration,
+    /// The running VM has to shut down gracefully first.
+    pub shutdown: Duration,
+}
+
+impl Default for LaunchTimeEstimates {
+    fn default() -> Self {
+        Self {
+            no_running_vm: Duration::from_secs(5),
+            terminate: Duration::from_secs(30),
+            shutdown: Duration::from_secs(120),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LaunchConfig {
+    pub estimates: LaunchTimeEstimates,
+}
+
+/// How long a launch of some VM is expected to take, and why.
+#[derive(Debug, Clone, PartialEq)]
+struct LaunchEstimate {
+    duration: Duration,
+    reason: String,
 }
 
 #[derive(Debug, Default)]
 struct LaunchManager {
     state: StdMutex<LaunchState>,
+    status: watch::Sender<LaunchStateSnapshot>,
+    config: LaunchConfig,
+    cancel: CancellationToken,
+    flows: TaskTracker,
+    protected_vmids: Arc<HashSet<u64>>,
+    allowed_vmids: Arc<Option<HashSet<u64>>>,
 }
 
 impl LaunchManager {
+    fn new(
+        config: LaunchConfig,
+        cancel: CancellationToken,
+        flows: TaskTracker,
+        protected_vmids: Arc<HashSet<u64>>,
+        allowed_vmids: Arc<Option<HashSet<u64>>>,
+    ) -> Self {
+        Self {
+            config,
+            cancel,
+            flows,
+            protected_vmids,
+            allowed_vmids,
+            ..Default::default()
+        }
+    }
+
+    pub async fn is_in_progress(&self) -> bool {
+        self.lock_state().in_progress
+    }
+
+    pub async fn current_state(&self) -> LaunchStateSummary {
+        let state = self.lock_state();
+        LaunchStateSummary {
+            in_progress: state.in_progress,
+            target_vmid: state.target_vmid,
+            current_action: state.requested_action,
+            started_at_ms: state.started_at_ms,
+        }
+    }
+
+    fn subscribe_status(&self) -> watch::Receiver<LaunchStateSnapshot> {
+        self.status.subscribe()
+    }
+
     fn reset_state(&self) {
-        let mut state = self.lock_state();
-        state.in_progress = false;
-        state.requested_action = None;
+        *self.lock_state() = LaunchState::default();
+        self.status.send_replace(LaunchStateSnapshot::default());
     }
 
     fn lock_state(&self) -> MutexGuard<'_, LaunchState> {
@@ -497,6 +3568,11 @@ impl LaunchManager {
             }
         }
 
+        if self.cancel.is_cancelled() {
+            warn!(target_vmid, "Rejected launch request during agent shutdown");
+            return Err(LaunchError::ShuttingDown);
+        }
+
         info!(target_vmid, action = ?action, "Evaluating launch preconditions");
         let vms = client.list_vms().await?;
         let running_vm = vms.into_iter().find(|vm| vm.status == VmStatus::Running);
@@ -506,6 +3582,14 @@ impl LaunchManager {
                 info!(target_vmid, "Launch target is already running");
                 return Ok(LaunchResponse::already_running());
             }
+            // Refused before `needs_action` would describe the VM to the client.
+            if !is_allowed_vmid(&self.allowed_vmids, running.vmid) {
+                warn!(
+                    running_vmid = running.vmid,
+                    target_vmid, "Launch would stop a VM outside PVE_ALLOWED_VMIDS"
+                );
+                return Err(LaunchError::NotAllowed(running.vmid));
+            }
 
             let easy_kill = running
                 .tags
@@ -532,6 +3616,14 @@ impl LaunchManager {
                     info!(target_vmid, "Launch cancelled by client");
                     return Ok(LaunchResponse::cancelled());
                 }
+                // Refuse up front rather than failing the detached flow.
+                Some(_) if self.protected_vmids.contains(&running.vmid) => {
+                    warn!(
+                        running_vmid = running.vmid,
+                        target_vmid, "Launch would stop a protected VM"
+                    );
+                    return Err(LaunchError::ProtectedVm(running.vmid));
+                }
                 _ => {}
             }
         } else if matches!(action, Some(LaunchAction::Cancel)) {
@@ -543,11 +3635,18 @@ impl LaunchManager {
             let mut state = self.lock_state();
             state.in_progress = true;
             state.requested_action = action;
+            state.target_vmid = Some(target_vmid);
+            state.started_at_ms = Some(current_timestamp_ms());
+            self.status.send_replace(LaunchStateSnapshot {
+                in_progress: true,
+                target_vmid: Some(target_vmid),
+                started_at: Some(Instant::now()),
+            });
             info!(target_vmid, action = ?action, "Launch flow marked in progress");
         }
 
         let manager = Arc::clone(&self);
-        tokio::spawn(async move {
+        self.flows.spawn(async move {
             let outcome = manager
                 .run_flow(&client, target_vmid, running_vm, action)
                 .await;
@@ -566,6 +3665,60 @@ impl LaunchManager {
         Ok(LaunchResponse::started())
     }
 
+    /// Predicts how long launching `target_vmid` would take from the VM that
+    /// is running now, without changing anything.
+    async fn estimate_launch_time(
+        &self,
+        client: &ProxmoxClient,
+        target_vmid: u64,
+    ) -> Result<LaunchEstimate, LaunchError> {
+        let vms = client.list_vms().await?;
+        let running_vm = vms.iter().find(|vm| vm.status == VmStatus::Running);
+        if let Some(running) = running_vm {
+            if running.vmid != target_vmid && !is_allowed_vmid(&self.allowed_vmids, running.vmid) {
+                return Err(LaunchError::NotAllowed(running.vmid));
+            }
+        }
+        estimate_launch(
+            running_vm,
+            target_vmid,
+            &self.protected_vmids,
+            &self.config.estimates,
+        )
+    }
+
+    /// Launches the single VM carrying `tag`, refusing when the tag is missing or shared.
+    async fn launch_by_tag(
+        self: Arc<Self>,
+        client: ProxmoxClient,
+        tag: &str,
+        action: Option<LaunchAction>,
+    ) -> Result<LaunchResponse, LaunchError> {
+        let vms = client.list_vms().await?;
+        let candidates: Vec<u64> = vms
+            .iter()
+            .filter(|vm| is_allowed_vmid(&self.allowed_vmids, vm.vmid))
+            .filter(|vm| {
+                vm.tags
+                    .iter()
+                    .any(|vm_tag| vm_tag.eq_ignore_ascii_case(tag))
+            })
+            .map(|vm| vm.vmid)
+            .collect();
+        match candidates.as_slice() {
+            [] => Err(LaunchError::NoMatch(tag.to_string())),
+            [vmid] => {
+                info!(
+                    tag,
+                    target_vmid = vmid,
+                    "Resolved launch tag to a single VM"
+                );
+                self.launch(client, *vmid, action).await
+            }
+            _ => Err(LaunchError::AmbiguousTag(tag.to_string(), candidates)),
+        }
+    }
+
     async fn run_flow(
         &self,
         client: &ProxmoxClient,
@@ -575,18 +3728,20 @@ impl LaunchManager {
     ) -> Result<(), LaunchError> {
         if let Some(running) = running_vm {
             let mut current_action = action.take().unwrap_or(LaunchAction::Terminate);
+            // The inventory listing already told us where the VM lives.
+            let node = running.node.as_deref();
             info!(
                 "Resolving running VM {} before launching {}",
                 running.vmid, target_vmid
             );
 
-            self.execute_action(client, running.vmid, current_action)
+            self.execute_action(client, running.vmid, node, current_action)
                 .await?;
 
             for attempt in 1..=60 {
-                let status = client.vm_status(running.vmid).await?;
+                let status = retry_transient(|| vm_status_on(client, running.vmid, node)).await?;
                 debug!(running_vmid = running.vmid, attempt, status = ?status, "Waiting for running VM to stop");
-                if status == VmStatus::Stopped {
+                if current_action.is_settled(status) {
                     info!(
                         running_vmid = running.vmid,
                         "Running VM is stopped; proceeding with launch"
@@ -606,7 +3761,7 @@ impl LaunchManager {
                         "Escalating action to terminate VM {} during launch",
                         running.vmid
                     );
-                    self.execute_action(client, running.vmid, LaunchAction::Terminate)
+                    self.execute_action(client, running.vmid, node, LaunchAction::Terminate)
                         .await?;
                     current_action = LaunchAction::Terminate;
                 }
@@ -614,9 +3769,9 @@ impl LaunchManager {
                 sleep(Duration::from_secs(2)).await;
             }
 
-            let status = client.vm_status(running.vmid).await?;
+            let status = retry_transient(|| vm_status_on(client, running.vmid, node)).await?;
             debug!(running_vmid = running.vmid, status = ?status, "Final VM status check before launch");
-            if status != VmStatus::Stopped {
+            if !current_action.is_settled(status) {
                 return Err(LaunchError::LaunchFailed(format!(
                     "Timed out waiting for VM {} to stop before launch",
                     running.vmid
@@ -625,31 +3780,162 @@ impl LaunchManager {
         }
 
         info!(target_vmid, "Starting target VM");
-        client.start_vm(target_vmid).await?;
+        retry_unhandled(|| client.start_vm(target_vmid)).await?;
         Ok(())
     }
 
+    /// Sends `action` to `vmid`, directly to `node` when it is known.
     async fn execute_action(
         &self,
         client: &ProxmoxClient,
         vmid: u64,
+        node: Option<&str>,
         action: LaunchAction,
     ) -> Result<(), LaunchError> {
-        info!(vmid, action = ?action, "Executing VM action for launch flow");
-        match action {
-            LaunchAction::Shutdown => client.shutdown_vm(vmid).await?,
-            LaunchAction::Hibernate => client.hibernate_vm(vmid).await?,
-            LaunchAction::Terminate => client.terminate_vm(vmid).await?,
-            LaunchAction::Cancel => {}
+        if self.protected_vmids.contains(&vmid) {
+            warn!(vmid, action = ?action, "Refused launch flow action on protected VM");
+            return Err(LaunchError::ProtectedVm(vmid));
         }
+        info!(vmid, node, action = ?action, "Executing VM action for launch flow");
+        retry_unhandled(|| async {
+            match (action, node) {
+                (LaunchAction::Shutdown, Some(node)) => {
+                    client.shutdown_vm_with_node_hint(vmid, node).await
+                }
+                (LaunchAction::Shutdown, None) => client.shutdown_vm(vmid).await,
+                (LaunchAction::Hibernate, Some(node)) => {
+                    client.hibernate_vm_with_node_hint(vmid, node).await
+                }
+                (LaunchAction::Hibernate, None) => client.hibernate_vm(vmid).await,
+                (LaunchAction::Suspend, Some(node)) => {
+                    client.suspend_vm_with_node_hint(vmid, node).await
+                }
+                (LaunchAction::Suspend, None) => client.suspend_vm(vmid).await,
+                (LaunchAction::Terminate, Some(node)) => {
+                    client.terminate_vm_with_node_hint(vmid, node).await
+                }
+                (LaunchAction::Terminate, None) => client.terminate_vm(vmid).await,
+                (LaunchAction::Cancel, _) => Ok(()),
+            }
+        })
+        .await?;
         info!(vmid, action = ?action, "Launch flow VM action command sent");
         Ok(())
     }
 }
 
+/// Mirrors the choices [`LaunchManager::launch`] makes about `running_vm`.
+fn estimate_launch(
+    running_vm: Option<&VmInfo>,
+    target_vmid: u64,
+    protected_vmids: &HashSet<u64>,
+    estimates: &LaunchTimeEstimates,
+) -> Result<LaunchEstimate, LaunchError> {
+    let Some(running) = running_vm else {
+        return Ok(LaunchEstimate {
+            duration: estimates.no_running_vm,
+            reason: format!("No VM is running; VM {target_vmid} only needs to start"),
+        });
+    };
+    if running.vmid == target_vmid {
+        return Ok(LaunchEstimate {
+            duration: Duration::ZERO,
+            reason: format!("VM {target_vmid} is already running"),
+        });
+    }
+    if protected_vmids.contains(&running.vmid) {
+        return Err(LaunchError::ProtectedVm(running.vmid));
+    }
+    let easy_kill = running
+        .tags
+        .iter()
+        .any(|tag| tag.eq_ignore_ascii_case("easy-kill"));
+    Ok(if easy_kill {
+        LaunchEstimate {
+            duration: estimates.terminate,
+            reason: format!(
+                "Running VM {} ({}) is tagged easy-kill and will be terminated",
+                running.vmid, running.name
+            ),
+        }
+    } else {
+        LaunchEstimate {
+            duration: estimates.shutdown,
+            reason: format!(
+                "Running VM {} ({}) has to shut down gracefully first",
+                running.vmid, running.name
+            ),
+        }
+    })
+}
+
+/// Tries of a launch flow call while Proxmox is transiently unreachable.
+const TRANSIENT_RETRY_ATTEMPTS: u32 = 10;
+const TRANSIENT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs `call`, sending it again after transient failures such as a 503 or a
+/// dropped connection, so a brief outage does not abort a detached flow.
+async fn retry_transient<T, F, Fut>(call: F) -> Result<T, ProxmoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProxmoxError>>,
+{
+    retry_while(call, ProxmoxError::is_transient).await
+}
+
+/// Like [`retry_transient`] for power actions, which are only sent again when
+/// Proxmox never acted on them: a start that timed out may still be running
+/// and a second one would queue a duplicate task.
+async fn retry_unhandled<T, F, Fut>(call: F) -> Result<T, ProxmoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProxmoxError>>,
+{
+    retry_while(call, ProxmoxError::was_not_handled).await
+}
+
+async fn retry_while<T, F, Fut>(
+    mut call: F,
+    retryable: fn(&ProxmoxError) -> bool,
+) -> Result<T, ProxmoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProxmoxError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Err(err) if retryable(&err) && attempt < TRANSIENT_RETRY_ATTEMPTS => {
+                warn!(attempt, error = %err, "Proxmox call failed transiently; retrying");
+                attempt += 1;
+                sleep(TRANSIENT_RETRY_INTERVAL).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn vm_status_on(
+    client: &ProxmoxClient,
+    vmid: u64,
+    node: Option<&str>,
+) -> Result<VmStatus, ProxmoxError> {
+    match node {
+        Some(node) => client.vm_status_with_node_hint(vmid, node).await,
+        None => client.vm_status(vmid).await,
+    }
+}
+
 #[derive(Debug)]
 enum LaunchError {
     InProgress,
+    ShuttingDown,
+    NoMatch(String),
+    AmbiguousTag(String, Vec<u64>),
+    /// The running VM is listed in `PVE_PROTECTED_VMIDS`.
+    ProtectedVm(u64),
+    /// The running VM is left out of `PVE_ALLOWED_VMIDS`.
+    NotAllowed(u64),
     LaunchFailed(String),
     Proxmox(ProxmoxError),
 }
@@ -663,14 +3949,64 @@ impl From<ProxmoxError> for LaunchError {
 #[derive(Debug, Default)]
 struct ShutdownState {
     in_progress: bool,
+    action: Option<LaunchAction>,
+    started_at_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct ShutdownStateSummary {
+    in_progress: bool,
+    current_action: Option<LaunchAction>,
+    started_at_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShutdownConfig {
+    /// Log instead of running `shutdown -h now` once VMs are resolved.
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Default)]
 struct ShutdownManager {
     state: Mutex<ShutdownState>,
+    config: ShutdownConfig,
+    cancel: CancellationToken,
+    flows: TaskTracker,
+    protected_vmids: Arc<HashSet<u64>>,
+    allowed_vmids: Arc<Option<HashSet<u64>>>,
 }
 
 impl ShutdownManager {
+    fn new(
+        config: ShutdownConfig,
+        cancel: CancellationToken,
+        flows: TaskTracker,
+        protected_vmids: Arc<HashSet<u64>>,
+        allowed_vmids: Arc<Option<HashSet<u64>>>,
+    ) -> Self {
+        Self {
+            config,
+            cancel,
+            flows,
+            protected_vmids,
+            allowed_vmids,
+            ..Default::default()
+        }
+    }
+
+    pub async fn is_in_progress(&self) -> bool {
+        self.state.lock().await.in_progress
+    }
+
+    pub async fn current_state(&self) -> ShutdownStateSummary {
+        let state = self.state.lock().await;
+        ShutdownStateSummary {
+            in_progress: state.in_progress,
+            current_action: state.action,
+            started_at_ms: state.started_at_ms,
+        }
+    }
+
     async fn shutdown(
         self: Arc<Self>,
         client: ProxmoxClient,
@@ -684,11 +4020,23 @@ impl ShutdownManager {
             }
         }
 
+        if self.cancel.is_cancelled() {
+            warn!("Rejected host shutdown request during agent shutdown");
+            return Err(ShutdownError::ShuttingDown);
+        }
+
         info!(action = ?action, "Evaluating host shutdown preconditions");
         let vms = client.list_vms().await?;
         let running_vm = vms.into_iter().find(|vm| vm.status == VmStatus::Running);
 
         if let Some(ref running) = running_vm {
+            if !is_allowed_vmid(&self.allowed_vmids, running.vmid) {
+                warn!(
+                    running_vmid = running.vmid,
+                    "Host shutdown would stop a VM outside PVE_ALLOWED_VMIDS"
+                );
+                return Err(ShutdownError::NotAllowed(running.vmid));
+            }
             if action.is_none() {
                 info!(
                     running_vmid = running.vmid,
@@ -700,6 +4048,13 @@ impl ShutdownManager {
                 info!("Host shutdown cancelled by client");
                 return Ok(ShutdownResponse::cancelled());
             }
+            if self.protected_vmids.contains(&running.vmid) {
+                warn!(
+                    running_vmid = running.vmid,
+                    "Host shutdown would stop a protected VM"
+                );
+                return Err(ShutdownError::ProtectedVm(running.vmid));
+            }
         } else if matches!(action, Some(LaunchAction::Cancel)) {
             info!("Host shutdown cancelled before work started");
             return Ok(ShutdownResponse::cancelled());
@@ -708,11 +4063,13 @@ impl ShutdownManager {
         {
             let mut state = self.state.lock().await;
             state.in_progress = true;
+            state.action = action;
+            state.started_at_ms = Some(current_timestamp_ms());
             info!(action = ?action, "Host shutdown flow marked in progress");
         }
 
         let manager = Arc::clone(&self);
-        tokio::spawn(async move {
+        self.flows.spawn(async move {
             let outcome = manager.run_flow(&client, running_vm, action).await;
             match outcome {
                 Ok(()) => {
@@ -723,8 +4080,7 @@ impl ShutdownManager {
                 }
             }
 
-            let mut state = manager.state.lock().await;
-            state.in_progress = false;
+            *manager.state.lock().await = ShutdownState::default();
         });
 
         info!("Host shutdown flow detached from request lifecycle");
@@ -747,7 +4103,7 @@ impl ShutdownManager {
             for attempt in 1..=60 {
                 let status = client.vm_status(running.vmid).await?;
                 debug!(running_vmid = running.vmid, attempt, status = ?status, "Waiting for VM to stop before host shutdown");
-                if status == VmStatus::Stopped {
+                if selected_action.is_settled(status) {
                     info!(
                         running_vmid = running.vmid,
                         "VM stopped before host shutdown"
@@ -759,7 +4115,7 @@ impl ShutdownManager {
 
             let status = client.vm_status(running.vmid).await?;
             debug!(running_vmid = running.vmid, status = ?status, "Final VM status check before host shutdown");
-            if status != VmStatus::Stopped {
+            if !selected_action.is_settled(status) {
                 return Err(ShutdownError::ShutdownFailed(format!(
                     "Timed out waiting for VM {} to stop",
                     running.vmid
@@ -767,6 +4123,11 @@ impl ShutdownManager {
             }
         }
 
+        if self.config.dry_run {
+            info!("[dry-run] would call shutdown -h now");
+            return Ok(());
+        }
+
         info!("Initiating host shutdown command");
         tokio::task::spawn_blocking(|| {
             match Command::new("shutdown").arg("-h").arg("now").status() {
@@ -791,10 +4152,15 @@ impl ShutdownManager {
         vmid: u64,
         action: LaunchAction,
     ) -> Result<(), ShutdownError> {
+        if self.protected_vmids.contains(&vmid) {
+            warn!(vmid, action = ?action, "Refused host shutdown action on protected VM");
+            return Err(ShutdownError::ProtectedVm(vmid));
+        }
         info!(vmid, action = ?action, "Executing VM action");
         match action {
             LaunchAction::Shutdown => client.shutdown_vm(vmid).await?,
             LaunchAction::Hibernate => client.hibernate_vm(vmid).await?,
+            LaunchAction::Suspend => client.suspend_vm(vmid).await?,
             LaunchAction::Terminate => client.terminate_vm(vmid).await?,
             LaunchAction::Cancel => {}
         }
@@ -806,6 +4172,11 @@ impl ShutdownManager {
 #[derive(Debug)]
 enum ShutdownError {
     InProgress,
+    ShuttingDown,
+    /// The running VM is listed in `PVE_PROTECTED_VMIDS`.
+    ProtectedVm(u64),
+    /// The running VM is left out of `PVE_ALLOWED_VMIDS`.
+    NotAllowed(u64),
     Proxmox(ProxmoxError),
     ShutdownFailed(String),
 }
@@ -815,3 +4186,80 @@ impl From<ProxmoxError> for ShutdownError {
         Self::Proxmox(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(vmid: u64, tags: &[&str]) -> VmInfo {
+        VmInfo::builder(vmid, "busy")
+            .tags(tags)
+            .status(VmStatus::Running)
+            .build()
+    }
+
+    fn estimate(
+        running_vm: Option<&VmInfo>,
+        protected: &[u64],
+    ) -> Result<LaunchEstimate, LaunchError> {
+        let protected: HashSet<u64> = protected.iter().copied().collect();
+        estimate_launch(running_vm, 200, &protected, &LaunchTimeEstimates::default())
+    }
+
+    #[test]
+    fn estimate_is_zero_when_target_already_runs() {
+        let estimate = estimate(Some(&running(200, &[])), &[]).unwrap();
+        assert_eq!(estimate.duration, Duration::ZERO);
+        assert_eq!(estimate.reason, "VM 200 is already running");
+    }
+
+    #[test]
+    fn estimate_without_running_vm_covers_only_the_start() {
+        let estimate = estimate(None, &[]).unwrap();
+        assert_eq!(estimate.duration, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn estimate_for_easy_kill_vm_assumes_terminate() {
+        let estimate = estimate(Some(&running(100, &["Easy-Kill"])), &[]).unwrap();
+        assert_eq!(estimate.duration, Duration::from_secs(30));
+        assert!(estimate.reason.contains("easy-kill"));
+    }
+
+    #[test]
+    fn estimate_for_other_vm_assumes_graceful_shutdown() {
+        let estimate = estimate(Some(&running(100, &["dev"])), &[]).unwrap();
+        assert_eq!(estimate.duration, Duration::from_secs(120));
+        assert!(estimate.reason.contains("shut down gracefully"));
+    }
+
+    #[test]
+    fn estimate_refuses_when_running_vm_is_protected() {
+        assert!(matches!(
+            estimate(Some(&running(100, &["easy-kill"])), &[100]),
+            Err(LaunchError::ProtectedVm(100))
+        ));
+    }
+
+    #[test]
+    fn estimates_are_configurable() {
+        let estimates = LaunchTimeEstimates {
+            shutdown: Duration::from_secs(300),
+            ..Default::default()
+        };
+        let estimate =
+            estimate_launch(Some(&running(100, &[])), 200, &HashSet::new(), &estimates).unwrap();
+        assert_eq!(estimate.duration, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = "\"00ff\"";
+        assert!(if_none_match_matches("\"00ff\"", etag));
+        assert!(if_none_match_matches("W/\"00ff\"", etag));
+        assert!(if_none_match_matches("\"abcd\", \"00ff\"", etag));
+        assert!(if_none_match_matches("*", etag));
+        assert!(!if_none_match_matches("\"abcd\"", etag));
+        assert!(!if_none_match_matches("00ff", etag));
+    }
+}
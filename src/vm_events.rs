@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::proxmox::types::{VmInfo, VmStatus};
+use crate::proxmox::ProxmoxClient;
+
+pub const DEFAULT_VM_EVENTS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Undelivered batches kept per subscriber before it starts lagging.
+const CHANNEL_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmStatusChange {
+    pub vmid: u64,
+    pub status: VmStatus,
+}
+
+/// VM status changes shared by every subscriber. A single task polls
+/// `list_vms` once the first subscriber arrives and broadcasts each batch of
+/// changes; it skips the Proxmox call while nobody is subscribed.
+#[derive(Clone)]
+pub struct VmEvents {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    client: ProxmoxClient,
+    poll_interval: Duration,
+    sender: broadcast::Sender<Arc<[VmStatusChange]>>,
+    started: AtomicBool,
+    cancel: CancellationToken,
+}
+
+impl VmEvents {
+    /// The polling task stops once `cancel` fires, closing every subscription.
+    pub fn new(client: ProxmoxClient, poll_interval: Duration, cancel: CancellationToken) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            inner: Arc::new(Inner {
+                client,
+                poll_interval,
+                sender,
+                started: AtomicBool::new(false),
+                cancel,
+            }),
+        }
+    }
+
+    /// Receives every batch of changes detected from now on. Starts the
+    /// polling task on first use; must be called within a Tokio runtime.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<[VmStatusChange]>> {
+        let receiver = self.inner.sender.subscribe();
+        if !self.inner.started.swap(true, Ordering::SeqCst) {
+            info!(
+                poll_interval_secs = self.inner.poll_interval.as_secs_f64(),
+                "Starting VM status polling task"
+            );
+            tokio::spawn(poll_loop(self.inner.clone()));
+        }
+        receiver
+    }
+}
+
+async fn poll_loop(inner: Arc<Inner>) {
+    let mut ticker = interval(inner.poll_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Kept while nobody listens, so a returning subscriber at worst sees a
+    // change it already has in its snapshot.
+    let mut last: Option<HashMap<u64, VmStatus>> = None;
+    loop {
+        tokio::select! {
+            _ = inner.cancel.cancelled() => break,
+            _ = ticker.tick() => {}
+        }
+        if inner.sender.receiver_count() == 0 {
+            continue;
+        }
+        let vms = match inner.client.list_vms().await {
+            Ok(vms) => vms,
+            Err(err) => {
+                warn!(error = %err, "Failed to poll VM statuses");
+                continue;
+            }
+        };
+        if let Some(previous) = &last {
+            let changes = status_changes(previous, &vms);
+            if !changes.is_empty() {
+                debug!(changes = changes.len(), "Broadcasting VM status changes");
+                let _ = inner.sender.send(changes.into());
+            }
+        }
+        last = Some(vms.into_iter().map(|vm| (vm.vmid, vm.status)).collect());
+    }
+    info!("VM status polling task stopped");
+}
+
+/// VMs whose status differs from `previous`, including newly seen ones, in
+/// `current` order. VMs that disappeared are not reported.
+fn status_changes(previous: &HashMap<u64, VmStatus>, current: &[VmInfo]) -> Vec<VmStatusChange> {
+    current
+        .iter()
+        .filter(|vm| previous.get(&vm.vmid) != Some(&vm.status))
+        .map(|vm| VmStatusChange {
+            vmid: vm.vmid,
+            status: vm.status.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+//! Loading an env file sets process-wide variables, so this lives in its own
+//! test binary where no other test can observe them.
+
+use clap::Parser;
+use risky_proxmox_agent::config::{CliArgs, Config};
+
+#[test]
+fn env_file_is_loaded_from_custom_path() {
+    let path = std::env::temp_dir().join(format!("agent-config-{}.env", std::process::id()));
+    std::fs::write(
+        &path,
+        "PVE_HOST=https://env-file.example:8006\n\
+         PVE_TOKEN_ID=env-file@pam!token\n\
+         PVE_TOKEN_SECRET=env-file-secret\n\
+         COMPRESSION_MIN_SIZE_BYTES=2048\n",
+    )
+    .unwrap();
+
+    let args = CliArgs::parse_from([
+        "risky-proxmox-agent",
+        "--port",
+        "9090",
+        "--env-file",
+        path.to_str().unwrap(),
+    ]);
+    let config = Config::from_args(args);
+    let _ = std::fs::remove_file(&path);
+
+    let config = config.unwrap();
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.pve_host, "https://env-file.example:8006");
+    assert_eq!(config.pve_token_id, "env-file@pam!token");
+    assert_eq!(config.pve_token_secret, "env-file-secret");
+    assert_eq!(config.compression_min_size_bytes, 2048);
+}
@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use proxmox_dummy::{spawn_dummy_server, DummyHandle, InjectedError};
+use risky_proxmox_agent::proxmox::ProxmoxClient;
+use risky_proxmox_agent::server::{router, AppState};
+use tokio::net::TcpListener;
+
+const INVENTORY_PATH: &str = "/api2/json/cluster/resources";
+
+/// Agent backed by a dummy whose inventory route fails with `error`.
+async fn spawn_failing_agent(error: InjectedError) -> SocketAddr {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    handle.set_error_on_route(INVENTORY_PATH, error).await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle).await.unwrap();
+    let client = ProxmoxClient::builder(format!("http://{dummy_addr}"), "token-id", "token-secret")
+        .request_timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(AppState::new(client));
+    tokio::spawn(async move {
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        if let Err(err) = axum::serve(listener, service).await {
+            eprintln!("app server failed: {err}");
+        }
+    });
+    addr
+}
+
+async fn list_vms_status(error: InjectedError) -> reqwest::Response {
+    let addr = spawn_failing_agent(error).await;
+    reqwest::get(format!("http://{addr}/api/vms"))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn proxmox_not_found_maps_to_404() {
+    let response = list_vms_status(InjectedError::Status(StatusCode::NOT_FOUND)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn proxmox_unauthorized_maps_to_401() {
+    let response = list_vms_status(InjectedError::Status(StatusCode::UNAUTHORIZED)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn proxmox_rate_limit_maps_to_429_with_retry_after() {
+    let response = list_vms_status(InjectedError::Status(StatusCode::TOO_MANY_REQUESTS)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .expect("Retry-After header");
+    assert!(retry_after.to_str().unwrap().parse::<u64>().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn proxmox_server_error_maps_to_502() {
+    let response = list_vms_status(InjectedError::Status(StatusCode::INTERNAL_SERVER_ERROR)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("500"), "{body}");
+}
+
+#[tokio::test]
+async fn proxmox_network_timeout_maps_to_504() {
+    let response = list_vms_status(InjectedError::Delay(Duration::from_secs(2))).await;
+    assert_eq!(response.status(), reqwest::StatusCode::GATEWAY_TIMEOUT);
+}
@@ -0,0 +1,112 @@
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use proxmox_dummy::{spawn_dummy_server, DummyHandle};
+
+fn spawn_agent_process(pve_host: &str) -> Child {
+    agent_command(pve_host)
+        .spawn()
+        .expect("agent binary should start")
+}
+
+fn agent_command(pve_host: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_risky-proxmox-agent"));
+    command
+        .args(["--bind", "127.0.0.1", "--port", "0"])
+        .env("PVE_HOST", pve_host)
+        .env("PVE_TOKEN_ID", "token-id")
+        .env("PVE_TOKEN_SECRET", "token-secret")
+        .env("RUST_LOG", "info")
+        .env("NO_COLOR", "1")
+        .env_remove("REMOTE_LOG_UPLOAD_URL")
+        .env_remove("REMOTE_LOG_AUTHORIZATION_SECRET")
+        .env_remove("STARTUP_PROBE_DISABLED")
+        .env_remove("STARTUP_PROBE_TIMEOUT_SECS")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    command
+}
+
+fn wait_for_log_line(child: &mut Child, needle: &'static str, limit: Duration) {
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if line.contains(needle) {
+                let _ = tx.send(());
+            }
+        }
+    });
+    rx.recv_timeout(limit)
+        .unwrap_or_else(|_| panic!("agent did not log '{needle}' within {limit:?}"));
+}
+
+fn wait_for_exit(child: &mut Child, limit: Duration) -> ExitStatus {
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("agent did not exit within {limit:?}");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sigterm_triggers_clean_exit() {
+    let handle = DummyHandle::new("pve");
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle).await.unwrap();
+
+    let mut child = spawn_agent_process(&format!("http://{dummy_addr}"));
+    wait_for_log_line(
+        &mut child,
+        "TCP listener bound successfully",
+        Duration::from_secs(30),
+    );
+
+    let killed = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(10));
+    assert!(status.success(), "agent exited with {status}");
+}
+
+/// Nothing listens on port 1, so the probe is refused immediately.
+const UNREACHABLE_HOST: &str = "http://127.0.0.1:1";
+
+#[test]
+fn unreachable_proxmox_exits_with_status_1() {
+    let mut child = agent_command(UNREACHABLE_HOST)
+        .env("STARTUP_PROBE_TIMEOUT_SECS", "2")
+        .spawn()
+        .expect("agent binary should start");
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(10));
+    assert_eq!(status.code(), Some(1), "agent exited with {status}");
+}
+
+#[test]
+fn disabled_startup_probe_serves_without_proxmox() {
+    let mut child = agent_command(UNREACHABLE_HOST)
+        .env("STARTUP_PROBE_DISABLED", "true")
+        .spawn()
+        .expect("agent binary should start");
+
+    wait_for_log_line(
+        &mut child,
+        "TCP listener bound successfully",
+        Duration::from_secs(30),
+    );
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
@@ -0,0 +1,159 @@
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use risky_proxmox_agent::config::RemoteLogConfig;
+use risky_proxmox_agent::remote_log::{RemoteLogHandle, RemoteLogMakeWriter};
+use tracing_subscriber::fmt::MakeWriter;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const SECRET: &str = "log-upload-secret";
+const HOSTNAME: &str = "agent-test-host";
+const UPLOAD_DELAY: Duration = Duration::from_millis(500);
+
+async fn mock_log_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/ingest"))
+        .and(header("authorization", SECRET))
+        .and(header("content-type", "application/json"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn remote_log(server: &MockServer, immediate_on_error: bool) -> RemoteLogHandle {
+    // Read once by RemoteLogHandle::new; every test sets the same value.
+    std::env::set_var("HOSTNAME", HOSTNAME);
+    RemoteLogHandle::new(RemoteLogConfig {
+        upload_url: format!("{}/ingest", server.uri()),
+        authorization_secret: SECRET.to_string(),
+        max_pending_bytes: 64 * 1024,
+        max_upload_bytes: 64 * 1024,
+        upload_delay_secs: UPLOAD_DELAY.as_secs_f64(),
+        immediate_on_error,
+        dedup_window_secs: 0,
+    })
+}
+
+/// Writes `lines` the way the tracing layer does; each is enqueued by a
+/// spawned task once its writer is dropped.
+fn write_lines(handle: &RemoteLogHandle, lines: &[&str]) {
+    let make_writer = RemoteLogMakeWriter::new(handle.clone());
+    for line in lines {
+        let mut writer = make_writer.make_writer();
+        writeln!(writer, "{line}").unwrap();
+    }
+}
+
+async fn wait_for_pending(handle: &RemoteLogHandle, expected: usize) {
+    while handle.pending_count().await < expected {
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Waits up to five real seconds for the log server to receive `expected`
+/// uploads. Yields rather than sleeps: the tests run on a paused clock, which
+/// would otherwise jump ahead while an upload is still on the wire.
+async fn uploads(server: &MockServer, expected: usize) -> Vec<Request> {
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while std::time::Instant::now() < deadline {
+        let received = server.received_requests().await.unwrap_or_default();
+        if received.len() >= expected {
+            return received;
+        }
+        tokio::task::yield_now().await;
+    }
+    panic!("log server did not receive {expected} upload(s)");
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[tokio::test(start_paused = true)]
+async fn buffered_entries_upload_after_the_delay() {
+    let server = mock_log_server().await;
+    let handle = remote_log(&server, false);
+    handle.spawn_upload_loop();
+    let before_ms = now_ms();
+    write_lines(
+        &handle,
+        &[
+            r#"{"level":"INFO","fields":{"message":"VM 100 started"}}"#,
+            "plain text line",
+        ],
+    );
+    wait_for_pending(&handle, 2).await;
+
+    // Still inside the upload delay, so nothing has been sent yet.
+    tokio::time::advance(UPLOAD_DELAY / 2).await;
+    assert!(server.received_requests().await.unwrap().is_empty());
+    assert_eq!(handle.pending_count().await, 2);
+
+    tokio::time::advance(UPLOAD_DELAY / 2).await;
+    let received = uploads(&server, 1).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].headers["authorization"], SECRET);
+    let entries: Vec<serde_json::Value> = received[0].body_json().unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in &entries {
+        assert_eq!(entry["hostname"], HOSTNAME);
+        let timestamp_ms = entry["timestamp_ms"].as_u64().unwrap();
+        assert!((before_ms..=now_ms()).contains(&timestamp_ms), "{entry}");
+    }
+    assert_eq!(entries[0]["fields"]["message"], "VM 100 started");
+    assert_eq!(entries[1]["message"], "plain text line");
+    assert_eq!(handle.pending_count().await, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn errors_upload_without_waiting_for_the_delay() {
+    let server = mock_log_server().await;
+    let handle = remote_log(&server, true);
+    handle.spawn_upload_loop();
+    write_lines(
+        &handle,
+        &[r#"{"level":"ERROR","fields":{"message":"Proxmox unreachable"}}"#],
+    );
+    let received = tokio::time::timeout(UPLOAD_DELAY / 2, uploads(&server, 1))
+        .await
+        .expect("urgent entry should skip the upload delay");
+    let entries: Vec<serde_json::Value> = received[0].body_json().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["fields"]["message"], "Proxmox unreachable");
+    assert_eq!(entries[0]["hostname"], HOSTNAME);
+}
+
+#[tokio::test]
+async fn flush_uploads_in_batches_of_max_upload_bytes() {
+    let server = mock_log_server().await;
+    std::env::set_var("HOSTNAME", HOSTNAME);
+    let handle = RemoteLogHandle::new(RemoteLogConfig {
+        upload_url: format!("{}/ingest", server.uri()),
+        authorization_secret: SECRET.to_string(),
+        max_pending_bytes: 64 * 1024,
+        max_upload_bytes: 1,
+        upload_delay_secs: 60.0,
+        immediate_on_error: false,
+        dedup_window_secs: 0,
+    });
+    write_lines(&handle, &["first", "second", "third"]);
+    wait_for_pending(&handle, 3).await;
+
+    handle.flush().await;
+    let received = uploads(&server, 3).await;
+    let messages: Vec<String> = received
+        .iter()
+        .map(|request| {
+            let entries: Vec<serde_json::Value> = request.body_json().unwrap();
+            assert_eq!(entries.len(), 1);
+            entries[0]["message"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(messages, ["first", "second", "third"]);
+}
@@ -1,26 +1,104 @@
+use std::io::Write;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use axum::Router;
-use proxmox_dummy::{spawn_dummy_server, DummyHandle, VmEntry, VmStatus};
+use proxmox_dummy::{
+    spawn_dummy_server, DummyHandle, FirewallRuleEntry, InjectedError, StorageEntry, VmEntry,
+    VmResources, VmStatus,
+};
 use reqwest::Client;
+use risky_proxmox_agent::fallback::{spawn_fallback_task, FallbackHandle, FallbackSelector};
+use risky_proxmox_agent::proxmox::error::ProxmoxError;
+use risky_proxmox_agent::proxmox::types::ForkOptions;
 use risky_proxmox_agent::proxmox::ProxmoxClient;
-use risky_proxmox_agent::server::{router, AppState};
+use risky_proxmox_agent::rate_limit::RateLimitConfig;
+use risky_proxmox_agent::server::{router, AppState, LaunchStateSnapshot, ShutdownConfig};
 use serde::Deserialize;
 use tokio::net::TcpListener;
+use tokio::sync::watch;
 use tokio::time::{sleep, timeout};
 
 async fn spawn_app(router: Router) -> SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
     tokio::spawn(async move {
-        if let Err(err) = axum::serve(listener, router).await {
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
+        if let Err(err) = axum::serve(listener, service).await {
             eprintln!("app server failed: {err}");
         }
     });
     addr
 }
 
+async fn spawn_agent(handle: &DummyHandle) -> SocketAddr {
+    spawn_agent_with(handle, |state| state).await
+}
+
+async fn spawn_agent_with(
+    handle: &DummyHandle,
+    configure: impl FnOnce(AppState) -> AppState,
+) -> SocketAddr {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+    spawn_app(router(configure(AppState::new(client)))).await
+}
+
+/// Like [`spawn_agent_with`], also returning a receiver for launch state changes.
+async fn spawn_agent_watching_launches(
+    handle: &DummyHandle,
+    configure: impl FnOnce(AppState) -> AppState,
+) -> (SocketAddr, watch::Receiver<LaunchStateSnapshot>) {
+    let mut launches = None;
+    let app_addr = spawn_agent_with(handle, |state| {
+        let state = configure(state);
+        launches = Some(state.subscribe_launch_status());
+        state
+    })
+    .await;
+    (app_addr, launches.unwrap())
+}
+
+/// Waits for the launch flow a request just started to finish.
+async fn wait_for_launch(launches: &mut watch::Receiver<LaunchStateSnapshot>) {
+    timeout(
+        Duration::from_secs(10),
+        launches.wait_for(|launch| !launch.in_progress),
+    )
+    .await
+    .expect("launch flow did not finish")
+    .unwrap();
+}
+
+const ADMIN_KEY: &str = "admin-key";
+
+async fn spawn_admin_agent(
+    handle: &DummyHandle,
+    configure: impl FnOnce(AppState) -> AppState,
+) -> SocketAddr {
+    spawn_agent_with(handle, |state| {
+        configure(state.with_admin_api_key(ADMIN_KEY))
+    })
+    .await
+}
+
+async fn insert_stopped_vm(handle: &DummyHandle, vmid: u64, name: &str) {
+    handle.insert_vm(VmEntry::builder(vmid, name).build()).await;
+}
+
+async fn insert_running_vm(handle: &DummyHandle, vmid: u64, name: &str) {
+    insert_stopped_vm(handle, vmid, name).await;
+    handle.set_status(vmid, VmStatus::Running).await;
+}
+
 async fn wait_for_status(handle: &DummyHandle, vmid: u64, status: VmStatus) {
     let _ = timeout(Duration::from_secs(5), async {
         loop {
@@ -40,11 +118,41 @@ struct ApiVm {
     tags: Vec<String>,
     status: String,
     notes: Option<String>,
+    node: Option<String>,
+    maxmem: Option<u64>,
+    maxcpu: Option<f64>,
+    disk: Option<u64>,
+    uptime: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiSnapshot {
+    name: String,
+    description: Option<String>,
+    creation_time: Option<u64>,
+    parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SystemStatus {
+    vms: Vec<ApiVm>,
+    launch_in_progress: bool,
+    shutdown_in_progress: bool,
+    fallback_inhibited: bool,
+    agent_uptime_secs: u64,
 }
 
 #[derive(Debug, Deserialize)]
 struct LaunchResponse {
     status: String,
+    running_vm: Option<RunningVm>,
+    allowed_actions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunningVm {
+    vmid: u64,
+    name: String,
 }
 
 #[tokio::test]
@@ -52,22 +160,20 @@ async fn list_vms_returns_expected_data() {
     std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
     let handle = DummyHandle::new("pve");
     handle
-        .insert_vm(VmEntry {
-            vmid: 101,
-            name: "alpha".to_string(),
-            tags: vec!["easy-kill".to_string()],
-            status: VmStatus::Running,
-            notes: Some("alpha notes".to_string()),
-        })
+        .insert_vm(
+            VmEntry::builder(101, "alpha")
+                .tags(&["easy-kill"])
+                .status(VmStatus::Running)
+                .notes(Some("alpha notes"))
+                .build(),
+        )
         .await;
     handle
-        .insert_vm(VmEntry {
-            vmid: 202,
-            name: "beta".to_string(),
-            tags: vec!["tag1".to_string(), "tag2".to_string()],
-            status: VmStatus::Stopped,
-            notes: None,
-        })
+        .insert_vm(
+            VmEntry::builder(202, "beta")
+                .tags(&["tag1", "tag2"])
+                .build(),
+        )
         .await;
 
     let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
@@ -97,6 +203,7 @@ async fn list_vms_returns_expected_data() {
     assert_eq!(alpha.status, "running");
     assert_eq!(alpha.tags, vec!["easy-kill"]);
     assert_eq!(alpha.notes.as_deref(), Some("alpha notes"));
+    assert_eq!(alpha.node.as_deref(), Some("pve"));
 }
 
 #[tokio::test]
@@ -104,22 +211,15 @@ async fn launch_flow_terminates_easy_kill_and_starts_target() {
     std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
     let handle = DummyHandle::new("pve");
     handle
-        .insert_vm(VmEntry {
-            vmid: 100,
-            name: "easy".to_string(),
-            tags: vec!["easy-kill".to_string()],
-            status: VmStatus::Running,
-            notes: None,
-        })
+        .insert_vm(
+            VmEntry::builder(100, "easy")
+                .tags(&["easy-kill"])
+                .status(VmStatus::Running)
+                .build(),
+        )
         .await;
     handle
-        .insert_vm(VmEntry {
-            vmid: 200,
-            name: "target".to_string(),
-            tags: vec![],
-            status: VmStatus::Stopped,
-            notes: None,
-        })
+        .insert_vm(VmEntry::builder(200, "target").build())
         .await;
 
     let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
@@ -130,7 +230,9 @@ async fn launch_flow_terminates_easy_kill_and_starts_target() {
         false,
     )
     .unwrap();
-    let app_addr = spawn_app(router(AppState::new(client))).await;
+    let state = AppState::new(client);
+    let mut launches = state.subscribe_launch_status();
+    let app_addr = spawn_app(router(state)).await;
 
     let response = Client::new()
         .post(format!("http://{app_addr}/api/launch"))
@@ -146,8 +248,4270 @@ async fn launch_flow_terminates_easy_kill_and_starts_target() {
     let response = response.json::<LaunchResponse>().await.unwrap();
 
     assert_eq!(response.status, "started");
-    wait_for_status(&handle, 100, VmStatus::Stopped).await;
-    wait_for_status(&handle, 200, VmStatus::Running).await;
+    wait_for_launch(&mut launches).await;
     assert_eq!(handle.status(100).await, Some(VmStatus::Stopped));
     assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+    handle
+        .assert_action_sequence(&[(100, "stop"), (200, "start")])
+        .await;
+    // One inventory listing for the launch plus the target's node lookup; the
+    // running VM is reached through the node from that listing.
+    let node_lookups = handle
+        .requests()
+        .await
+        .iter()
+        .filter(|request| request.path.ends_with("/cluster/resources"))
+        .count();
+    assert_eq!(node_lookups, 2);
+}
+
+#[tokio::test]
+async fn api_version_is_negotiated_from_accept_header() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 100, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms");
+    let get = |accept: Option<&'static str>| {
+        let mut request = client.get(&url);
+        if let Some(accept) = accept {
+            request = request.header(reqwest::header::ACCEPT, accept);
+        }
+        request.send()
+    };
+
+    for accept in [None, Some("application/vnd.risky-agent.v1+json")] {
+        let response = get(accept).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK, "{accept:?}");
+        assert_eq!(response.headers()["x-api-version"], "1");
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/vnd.risky-agent.v1+json"
+        );
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap()[0]["vmid"],
+            100
+        );
+    }
+
+    let response = get(Some("application/vnd.risky-agent.v2+json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(response.headers()["x-api-version"], "1");
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()["error"],
+        "API version 2 is not supported; supported versions: 1"
+    );
+
+    let response = get(Some("application/vnd.risky-agent.vx+json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()["x-api-version"], "1");
+
+    let response = client
+        .get(format!("http://{app_addr}/health"))
+        .header(
+            reqwest::header::ACCEPT,
+            "application/vnd.risky-agent.v2+json",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.headers().get("x-api-version").is_none());
+}
+
+#[tokio::test]
+async fn metrics_record_latency_per_endpoint() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent_with(&handle, |state| state.with_connection_limit(100)).await;
+
+    let endpoints = [
+        "/api/vms",
+        "/api/vms/:vmid/uptime",
+        "/api/vms/:vmid/tasks",
+        "/api/nodes",
+        "/api/cluster/status",
+    ];
+    for endpoint in endpoints {
+        let path = endpoint.replace(":vmid", "101");
+        let response = reqwest::get(format!("http://{app_addr}{path}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK, "{path}");
+    }
+
+    let response = reqwest::get(format!("http://{app_addr}/metrics"))
+        .await
+        .unwrap();
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+    for endpoint in endpoints {
+        let histogram = format!(
+            r#"http_request_duration_seconds_count{{matched_path="{endpoint}",method="GET",status_code="200"}}"#
+        );
+        assert!(body.contains(&histogram), "missing {histogram}");
+        let quantile = format!(
+            r#"http_request_duration_p99_seconds{{matched_path="{endpoint}",method="GET",status_code="200",quantile="0.99"}}"#
+        );
+        assert!(body.contains(&quantile), "missing {quantile}");
+    }
+    assert!(
+        body.contains(r#"proxmox_api_call_duration_seconds_count{method="GET",status_code="200"}"#)
+    );
+    // The scrape itself holds a slot while the metrics are rendered.
+    let active: i64 = body
+        .lines()
+        .find_map(|line| line.strip_prefix("active_connections "))
+        .expect("missing active_connections")
+        .parse()
+        .unwrap();
+    assert!(active >= 1, "active_connections = {active}");
+}
+
+#[tokio::test]
+async fn firewall_lists_vm_rules_in_order() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "web").await;
+    for (action, enable, source) in [("ACCEPT", 1, Some("10.0.0.0/8")), ("DROP", 0, None)] {
+        handle
+            .add_firewall_rule(
+                101,
+                FirewallRuleEntry {
+                    action: action.to_string(),
+                    rule_type: "in".to_string(),
+                    enable,
+                    comment: None,
+                    source: source.map(str::to_string),
+                    dest: None,
+                },
+            )
+            .await;
+    }
+    let app_addr = spawn_agent(&handle).await;
+
+    let body = reqwest::get(format!("http://{app_addr}/api/vms/101/firewall"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"pos": 0, "action": "ACCEPT", "type": "in", "enabled": true,
+             "comment": null, "source": "10.0.0.0/8", "dest": null},
+            {"pos": 1, "action": "DROP", "type": "in", "enabled": false,
+             "comment": null, "source": null, "dest": null},
+        ])
+    );
+
+    let response = reqwest::get(format!("http://{app_addr}/api/vms/999/firewall"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn console_url_points_novnc_at_the_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "my desktop").await;
+    insert_stopped_vm(&handle, 102, "idle").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let body = reqwest::get(format!("http://{app_addr}/api/vms/101/console-url"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(body["expires_in_secs"], 30);
+    let url = reqwest::Url::parse(body["url"].as_str().unwrap()).unwrap();
+    assert_eq!(url.host_str(), Some("127.0.0.1"));
+    assert_eq!(url.path(), "/");
+    let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let keys: Vec<&str> = query.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(
+        keys,
+        ["console", "novnc", "vmid", "vmname", "node", "resize", "ticket"]
+    );
+    let value = |key: &str| {
+        query
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .unwrap()
+    };
+    assert_eq!(value("console"), "kvm");
+    assert_eq!(value("novnc"), "1");
+    assert_eq!(value("vmid"), "101");
+    assert_eq!(value("vmname"), "my desktop");
+    assert_eq!(value("node"), "pve");
+    assert_eq!(value("resize"), "off");
+    assert!(value("ticket").starts_with("PVEVNC:"));
+    assert!(value("ticket").ends_with("::dummy+ticket/101="));
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .any(|request| request.method == "POST"
+            && request.path == "/api2/json/nodes/pve/qemu/101/vncproxy"));
+
+    let response = reqwest::get(format!("http://{app_addr}/api/vms/102/console-url"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn launch_estimate_reflects_running_vm() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(100, "easy")
+                .tags(&["easy-kill"])
+                .status(VmStatus::Running)
+                .build(),
+        )
+        .await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let body = reqwest::get(format!("http://{app_addr}/api/launch/estimate?vmid=200"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(body["estimated_secs"], 30);
+    assert!(body["reason"].as_str().unwrap().contains("easy-kill"));
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+
+    let response = reqwest::get(format!("http://{app_addr}/api/launch/estimate"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn launch_without_vmid_uses_configured_target() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 200, "target").await;
+    let (app_addr, mut launches) =
+        spawn_agent_watching_launches(&handle, |state| state.with_launch_target_vmid(200)).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = response.json::<LaunchResponse>().await.unwrap();
+    assert_eq!(response.status, "started");
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn launch_without_vmid_or_configured_target_is_rejected() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 200, "target").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "vmid required; no default configured");
+    assert_eq!(handle.vm(200).await.unwrap().status, VmStatus::Stopped);
+}
+
+#[tokio::test]
+async fn list_vms_filters_by_tag_and_status() {
+    let handle = DummyHandle::new("pve");
+    for (vmid, name, tags, status) in [
+        (101, "alpha", vec!["gaming"], VmStatus::Running),
+        (102, "beta", vec!["gaming", "primary"], VmStatus::Stopped),
+        (103, "gamma", vec!["work"], VmStatus::Stopped),
+    ] {
+        handle
+            .insert_vm(
+                VmEntry::builder(vmid, name)
+                    .tags(&tags)
+                    .status(status)
+                    .build(),
+            )
+            .await;
+    }
+    let app_addr = spawn_agent(&handle).await;
+
+    let fetch = |query: &'static str| async move {
+        let mut vmids: Vec<u64> = Client::new()
+            .get(format!("http://{app_addr}/api/vms{query}"))
+            .send()
+            .await
+            .unwrap()
+            .json::<Vec<ApiVm>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|vm| vm.vmid)
+            .collect();
+        vmids.sort_unstable();
+        vmids
+    };
+
+    assert_eq!(fetch("?tag=gaming").await, vec![101, 102]);
+    assert_eq!(fetch("?status=stopped").await, vec![102, 103]);
+    assert_eq!(fetch("?tag=gaming&status=running").await, vec![101]);
+    assert_eq!(fetch("?tag=missing").await, Vec::<u64>::new());
+}
+
+const VMS_FIXTURE: &str = include_str!("fixtures/vms.json");
+
+async fn fetch_vmids(app_addr: SocketAddr, query: &str) -> Vec<u64> {
+    let mut vmids: Vec<u64> = Client::new()
+        .get(format!("http://{app_addr}/api/vms{query}"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|vm| vm.vmid)
+        .collect();
+    vmids.sort_unstable();
+    vmids
+}
+
+#[tokio::test]
+async fn fixture_scenario_lists_and_filters_ten_vms() {
+    let handle = DummyHandle::from_json("pve", VMS_FIXTURE).unwrap();
+    let app_addr = spawn_agent(&handle).await;
+
+    assert_eq!(
+        fetch_vmids(app_addr, "").await,
+        (100..110).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        fetch_vmids(app_addr, "?status=running").await,
+        vec![100, 101, 104, 109]
+    );
+    assert_eq!(
+        fetch_vmids(app_addr, "?tag=infra").await,
+        vec![100, 101, 107]
+    );
+    assert_eq!(
+        fetch_vmids(app_addr, "?tag=work&name_contains=build").await,
+        vec![104, 105]
+    );
+    assert_eq!(fetch_vmids(app_addr, "?status=paused").await, vec![106]);
+}
+
+#[tokio::test]
+async fn fixture_vms_can_be_cleared_and_reloaded() {
+    let handle = DummyHandle::new("pve");
+    handle.insert_vms_from_json(VMS_FIXTURE).await.unwrap();
+    let app_addr = spawn_agent(&handle).await;
+    assert_eq!(fetch_vmids(app_addr, "").await.len(), 10);
+
+    handle.clear_vms().await;
+    assert!(fetch_vmids(app_addr, "").await.is_empty());
+
+    // A malformed entry leaves the VM map untouched.
+    let broken = r#"[{ "vmid": 200, "name": "ok", "tags": [], "status": "running" },
+                     { "vmid": "oops" }]"#;
+    assert!(handle.insert_vms_from_json(broken).await.is_err());
+    assert!(fetch_vmids(app_addr, "").await.is_empty());
+
+    handle.insert_vms_from_json(VMS_FIXTURE).await.unwrap();
+    assert_eq!(fetch_vmids(app_addr, "?tag=lab").await, vec![108, 109]);
+}
+
+#[tokio::test]
+async fn list_vms_filters_by_name_and_rejects_unknown_status() {
+    let handle = DummyHandle::new("pve");
+    for (vmid, name, tags, status) in [
+        (101, "Win-Desktop", vec!["gaming"], VmStatus::Running),
+        (102, "linux-desktop", vec!["gaming"], VmStatus::Stopped),
+        (103, "build-server", vec!["work"], VmStatus::Stopped),
+    ] {
+        handle
+            .insert_vm(
+                VmEntry::builder(vmid, name)
+                    .tags(&tags)
+                    .status(status)
+                    .build(),
+            )
+            .await;
+    }
+    let app_addr = spawn_agent(&handle).await;
+    let get = |query: &'static str| async move {
+        Client::new()
+            .get(format!("http://{app_addr}/api/vms{query}"))
+            .send()
+            .await
+            .unwrap()
+    };
+    let fetch = |query: &'static str| async move {
+        let mut vmids: Vec<u64> = get(query)
+            .await
+            .json::<Vec<ApiVm>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|vm| vm.vmid)
+            .collect();
+        vmids.sort_unstable();
+        vmids
+    };
+
+    assert_eq!(fetch("?name_contains=DESKTOP").await, vec![101, 102]);
+    assert_eq!(fetch("?name_contains=server").await, vec![103]);
+    assert_eq!(
+        fetch("?name_contains=desktop&status=stopped").await,
+        vec![102]
+    );
+    assert_eq!(
+        fetch("?name_contains=desktop&status=running&tag=gaming").await,
+        vec![101]
+    );
+    assert_eq!(
+        fetch("?name_contains=desktop&tag=work").await,
+        Vec::<u64>::new()
+    );
+
+    let response = get("?status=sleeping").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(fetch("?status=unknown").await, Vec::<u64>::new());
+}
+
+#[tokio::test]
+async fn patch_tags_updates_vm_tags() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(VmEntry::builder(101, "alpha").tags(&["old"]).build())
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .patch(format!("http://{app_addr}/api/vms/101/tags"))
+        .json(&serde_json::json!({ "tags": ["a", "b"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let vms = Client::new()
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    assert_eq!(vms[0].tags, vec!["a", "b"]);
+}
+
+#[tokio::test]
+async fn patch_notes_updates_vm_notes() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(VmEntry::builder(101, "alpha").build())
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .patch(format!("http://{app_addr}/api/vms/101/notes"))
+        .json(&serde_json::json!({ "notes": "updated notes" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let vms = Client::new()
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    assert_eq!(vms[0].notes.as_deref(), Some("updated notes"));
+}
+
+#[tokio::test]
+async fn reboot_action_leaves_vm_running() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(101, "alpha")
+                .status(VmStatus::Running)
+                .build(),
+        )
+        .await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/101/action"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "action": "reboot" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn uptime_counts_from_start_and_is_zero_when_stopped() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "long-lived").await;
+    handle
+        .set_vm_resources(
+            100,
+            VmResources {
+                uptime: Some(3600),
+                ..VmResources::default()
+            },
+        )
+        .await;
+    insert_stopped_vm(&handle, 101, "fresh").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let uptime = |vmid: u64| {
+        let client = client.clone();
+        async move {
+            let body = client
+                .get(format!("http://{app_addr}/api/vms/{vmid}/uptime"))
+                .send()
+                .await
+                .unwrap()
+                .json::<serde_json::Value>()
+                .await
+                .unwrap();
+            body["uptime_secs"].as_u64().expect("uptime_secs")
+        }
+    };
+
+    assert_eq!(uptime(100).await, 3600);
+    assert_eq!(uptime(101).await, 0);
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/101/action"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "action": "start" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    assert!(uptime(101).await < 5);
+
+    handle.set_status(100, VmStatus::Stopped).await;
+    assert_eq!(uptime(100).await, 0);
+}
+
+#[tokio::test]
+async fn reset_action_leaves_vm_running() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(101, "alpha")
+                .status(VmStatus::Running)
+                .build(),
+        )
+        .await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/101/action"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "action": "reset" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn list_vms_answers_not_modified_for_current_etag() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 100, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let etag = response.headers()[reqwest::header::ETAG].clone();
+    assert!(etag.to_str().unwrap().starts_with('"'));
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()[0]["vmid"],
+        100
+    );
+
+    let response = client
+        .get(&url)
+        .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[reqwest::header::ETAG], etag);
+    assert!(response.bytes().await.unwrap().is_empty());
+
+    insert_stopped_vm(&handle, 101, "beta").await;
+    let response = client
+        .get(&url)
+        .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_ne!(response.headers()[reqwest::header::ETAG], etag);
+    assert_eq!(
+        response
+            .json::<serde_json::Value>()
+            .await
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn list_vms_etag_ignores_advancing_uptime() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms");
+    let set_uptime = |uptime| {
+        handle.set_vm_resources(
+            100,
+            VmResources {
+                uptime: Some(uptime),
+                disk: Some(uptime * 1024),
+                ..VmResources::default()
+            },
+        )
+    };
+
+    set_uptime(60).await;
+    let response = client.get(&url).send().await.unwrap();
+    let etag = response.headers()[reqwest::header::ETAG].clone();
+
+    set_uptime(120).await;
+    let response = client
+        .get(&url)
+        .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    handle.set_status(100, VmStatus::Stopped).await;
+    let response = client
+        .get(&url)
+        .header(reqwest::header::IF_NONE_MATCH, etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn list_vms_includes_resource_statistics() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(101, "alpha")
+                .status(VmStatus::Running)
+                .build(),
+        )
+        .await;
+    handle
+        .set_vm_resources(
+            101,
+            VmResources {
+                maxmem: Some(8 * 1024 * 1024 * 1024),
+                maxcpu: Some(4.0),
+                disk: Some(32 * 1024 * 1024 * 1024),
+                uptime: Some(3600),
+            },
+        )
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let vms = Client::new()
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    let alpha = &vms[0];
+    assert_eq!(alpha.maxmem, Some(8 * 1024 * 1024 * 1024));
+    assert_eq!(alpha.maxcpu, Some(4.0));
+    assert_eq!(alpha.disk, Some(32 * 1024 * 1024 * 1024));
+    assert_eq!(alpha.uptime, Some(3600));
+}
+
+#[tokio::test]
+async fn snapshot_routes_create_list_and_delete() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let snapshots_url = format!("http://{app_addr}/api/vms/101/snapshots");
+
+    for (name, description) in [("first", Some("before upgrade")), ("second", None)] {
+        let response = client
+            .post(&snapshots_url)
+            .json(&serde_json::json!({ "name": name, "description": description }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    }
+
+    let snapshots = client
+        .get(&snapshots_url)
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiSnapshot>>()
+        .await
+        .unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[0].name, "first");
+    assert_eq!(snapshots[0].description.as_deref(), Some("before upgrade"));
+    assert!(snapshots[0].creation_time.is_some());
+    assert_eq!(snapshots[1].parent.as_deref(), Some("first"));
+
+    let response = client
+        .delete(format!("{snapshots_url}/first"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .delete(format!("{snapshots_url}/first"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let remaining: Vec<String> = handle
+        .snapshots(101)
+        .await
+        .into_iter()
+        .map(|snapshot| snapshot.name)
+        .collect();
+    assert_eq!(remaining, vec!["second"]);
+
+    let response = client
+        .delete(format!("{snapshots_url}/first"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let response = client
+        .delete(format!("http://{app_addr}/api/vms/999/snapshots/second"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn snapshot_rollback_requires_a_stopped_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    insert_running_vm(&handle, 102, "beta").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    for vmid in [101, 102] {
+        let response = client
+            .post(format!("http://{app_addr}/api/vms/{vmid}/snapshots"))
+            .json(&serde_json::json!({ "name": "clean" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    }
+    let rollback = |vmid: u64, name: &str| {
+        client
+            .post(format!(
+                "http://{app_addr}/api/vms/{vmid}/snapshots/{name}/rollback"
+            ))
+            .bearer_auth(ADMIN_KEY)
+            .send()
+    };
+
+    let response = rollback(101, "clean").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    let task_id = body["task_id"].as_str().unwrap();
+    let tasks = handle.tasks().await;
+    let task = tasks.iter().find(|task| task.upid == task_id).unwrap();
+    assert_eq!((task.kind.as_str(), task.vmid), ("qmrollback", 101));
+
+    let response = rollback(101, "missing").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = rollback(102, "clean").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "VM 102 is running, not stopped");
+    assert!(!handle
+        .tasks()
+        .await
+        .iter()
+        .any(|task| task.kind == "qmrollback" && task.vmid == 102));
+
+    let response = client
+        .post(format!(
+            "http://{app_addr}/api/vms/101/snapshots/clean/rollback"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn snapshot_routes_return_not_found_for_unknown_vm() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/api/vms/999/snapshots"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn duplicate_snapshot_name_returns_conflict() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        let response = client
+            .post(format!("http://{app_addr}/api/vms/101/snapshots"))
+            .json(&serde_json::json!({ "name": "dup" }))
+            .send()
+            .await
+            .unwrap();
+        statuses.push(response.status());
+    }
+    assert_eq!(
+        statuses,
+        vec![reqwest::StatusCode::CREATED, reqwest::StatusCode::CONFLICT]
+    );
+}
+
+#[tokio::test]
+async fn status_endpoint_combines_system_state() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    insert_stopped_vm(&handle, 102, "beta").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let status = Client::new()
+        .get(format!("http://{app_addr}/api/status"))
+        .send()
+        .await
+        .unwrap()
+        .json::<SystemStatus>()
+        .await
+        .unwrap();
+
+    let mut vmids: Vec<u64> = status.vms.iter().map(|vm| vm.vmid).collect();
+    vmids.sort_unstable();
+    assert_eq!(vmids, vec![101, 102]);
+    assert!(!status.launch_in_progress);
+    assert!(!status.shutdown_in_progress);
+    assert!(!status.fallback_inhibited);
+    assert!(status.agent_uptime_secs < 60);
+}
+
+#[tokio::test]
+async fn fork_job_reports_failure_for_unknown_vm() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/999/fork"))
+        .json(&serde_json::json!({ "name": "copy" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let job_id = response.json::<serde_json::Value>().await.unwrap()["job_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let job = timeout(Duration::from_secs(5), async {
+        loop {
+            let job = client
+                .get(format!("http://{app_addr}/api/fork/jobs/{job_id}"))
+                .send()
+                .await
+                .unwrap()
+                .json::<serde_json::Value>()
+                .await
+                .unwrap();
+            if job["state"] == "failed" || job["state"] == "completed" {
+                break job;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("fork job should finish");
+    assert_eq!(job["state"], "failed");
+    assert_eq!(job["source_vmid"], 999);
+    assert!(job["error"].is_string());
+
+    let missing = client
+        .get(format!(
+            "http://{app_addr}/api/fork/jobs/00000000-0000-0000-0000-000000000000"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[derive(Debug, Deserialize)]
+struct ForkResponse {
+    status: String,
+    vmid: u64,
+    started: bool,
+    error: Option<String>,
+}
+
+#[tokio::test]
+async fn fork_snapshots_then_clones_source_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let fork = response.json::<ForkResponse>().await.unwrap();
+    assert_eq!(fork.status, "created");
+    assert_eq!(fork.vmid, 102);
+    assert!(!fork.started);
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+
+    let requests = handle.requests().await;
+    let position = |suffix: &str| {
+        requests
+            .iter()
+            .position(|request| request.method == "POST" && request.path.ends_with(suffix))
+            .unwrap_or_else(|| panic!("no POST to {suffix} in {requests:?}"))
+    };
+    assert!(position("/qemu/101/snapshot") < position("/qemu/101/clone"));
+    assert_eq!(handle.snapshots(101).await.len(), 1);
+
+    let vms = client
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    let forked = vms
+        .iter()
+        .find(|vm| vm.vmid == 102)
+        .expect("forked VM listed");
+    assert_eq!(forked.name, "alpha-copy");
+}
+
+#[tokio::test]
+async fn fork_with_start_starts_the_new_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy", "start": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let fork = response.json::<ForkResponse>().await.unwrap();
+    assert_eq!(fork.status, "created");
+    assert_eq!(fork.vmid, 102);
+    assert!(fork.started);
+    assert_eq!(handle.status(102).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn fork_with_failed_start_reports_multi_status() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle.set_fail_starts(true).await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy", "start": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::MULTI_STATUS);
+    let fork = response.json::<ForkResponse>().await.unwrap();
+    assert_eq!(fork.status, "created_but_start_failed");
+    assert_eq!(fork.vmid, 102);
+    assert!(!fork.started);
+    assert!(fork.error.is_some());
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn fork_of_unknown_vm_returns_not_found() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 999, "name": "ghost" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .all(|request| !request.path.ends_with("/clone")));
+}
+
+#[tokio::test]
+async fn fork_times_out_when_clone_never_appears() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle.set_drop_clones(true).await;
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state.with_fork_wait(3, Duration::from_millis(20))
+    })
+    .await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Timed out waiting for VM 102"));
+}
+
+/// Spawns an agent whose clones never appear in the inventory, so fork
+/// waits last until the agent shuts down. Returns its state for
+/// [`AppState::begin_shutdown`].
+async fn spawn_agent_with_stuck_forks(handle: &DummyHandle) -> (SocketAddr, AppState) {
+    insert_stopped_vm(handle, 101, "alpha").await;
+    handle.set_drop_clones(true).await;
+    let mut agent_state = None;
+    let app_addr = spawn_agent_with(handle, |state| {
+        let state = state.with_fork_wait(1000, Duration::from_millis(50));
+        agent_state = Some(state.clone());
+        state
+    })
+    .await;
+    (app_addr, agent_state.unwrap())
+}
+
+#[tokio::test]
+async fn fork_wait_ends_when_agent_shuts_down() {
+    let handle = DummyHandle::new("pve");
+    let (app_addr, state) = spawn_agent_with_stuck_forks(&handle).await;
+
+    let request = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy" }))
+        .send();
+    let shutdown = async {
+        sleep(Duration::from_millis(200)).await;
+        state.begin_shutdown();
+    };
+    let (response, ()) = timeout(Duration::from_secs(5), async {
+        tokio::join!(request, shutdown)
+    })
+    .await
+    .expect("fork request outlived the shutdown");
+    let response = response.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "Agent is shutting down" })
+    );
+}
+
+#[tokio::test]
+async fn fork_job_events_end_when_agent_shuts_down() {
+    let handle = DummyHandle::new("pve");
+    let (app_addr, state) = spawn_agent_with_stuck_forks(&handle).await;
+    let client = Client::new();
+    let created = client
+        .post(format!("http://{app_addr}/api/vms/101/fork"))
+        .json(&serde_json::json!({ "name": "alpha-copy" }))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let job_id = created["job_id"].as_str().unwrap();
+
+    let response = client
+        .get(format!("http://{app_addr}/api/fork/jobs/{job_id}/events"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    state.begin_shutdown();
+    let body = timeout(Duration::from_secs(5), response.text())
+        .await
+        .expect("event stream outlived the shutdown")
+        .unwrap();
+    assert!(body.contains("event: progress"), "{body}");
+}
+
+async fn post_launch(app_addr: SocketAddr, body: serde_json::Value) -> LaunchResponse {
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        panic!("unexpected status {status}: {body}");
+    }
+    response.json::<LaunchResponse>().await.unwrap()
+}
+
+#[tokio::test]
+async fn launch_needs_action_then_shutdown_starts_target() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(app_addr, serde_json::json!({ "vmid": 200 })).await;
+    assert_eq!(response.status, "needs_action");
+    let running = response.running_vm.expect("running VM reported");
+    assert_eq!((running.vmid, running.name.as_str()), (100, "busy"));
+    assert_eq!(
+        response.allowed_actions,
+        vec!["shutdown", "hibernate", "suspend", "terminate", "cancel"]
+    );
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+    assert!(response.running_vm.is_none());
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(100).await, Some(VmStatus::Stopped));
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn launch_waits_for_delayed_power_transitions() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    handle
+        .set_vm_power_transition_delay(100, Duration::from_millis(200))
+        .await;
+    handle
+        .set_vm_power_transition_delay(200, Duration::from_millis(200))
+        .await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+
+    wait_for_launch(&mut launches).await;
+    // The flow finishes once the start is issued; the guest reports running 200ms later.
+    wait_for_status(&handle, 200, VmStatus::Running).await;
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(100).await, Some(VmStatus::Stopped));
+    handle
+        .assert_action_sequence(&[(100, "shutdown"), (200, "start")])
+        .await;
+    // The flow saw VM 100 still running at least once before starting 200.
+    let status_polls = handle
+        .requests()
+        .await
+        .iter()
+        .filter(|request| request.path.ends_with("/qemu/100/status/current"))
+        .count();
+    assert!(status_polls >= 2, "{status_polls} status polls");
+    let kinds: Vec<_> = handle
+        .tasks()
+        .await
+        .into_iter()
+        .map(|task| task.kind)
+        .collect();
+    assert_eq!(kinds, ["qmshutdown", "qmstart"]);
+}
+
+#[tokio::test]
+async fn malformed_json_bodies_get_structured_422_errors() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state.with_shutdown_config(ShutdownConfig { dry_run: true })
+    })
+    .await;
+    let client = Client::new();
+
+    let cases = [
+        ("/api/launch", r#"{"vmid": 200"#, "EOF while parsing"),
+        (
+            "/api/launch",
+            r#"{"vmid": 200, "action": "explode"}"#,
+            "unknown variant",
+        ),
+        ("/api/launch", r#"{"vmid": "two hundred"}"#, "invalid type"),
+        ("/api/fork", "not json", "expected ident"),
+        ("/api/fork", r#"{"vmid": 100}"#, "missing field `name`"),
+        ("/api/fork", r#"{"vmid": 100, "name": 7}"#, "invalid type"),
+        ("/api/host-shutdown", "{", "EOF while parsing"),
+        ("/api/host-shutdown", r#"{"action": 5}"#, "action"),
+    ];
+    for (path, body, detail) in cases {
+        let response = client
+            .post(format!("http://{app_addr}{path}"))
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            "{path} {body}"
+        );
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/vnd.risky-agent.v1+json",
+            "{path} {body}"
+        );
+        let error = response.json::<serde_json::Value>().await.unwrap()["error"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("JSON parse error: "), "{error}");
+        assert!(error.contains(detail), "{path} {body}: {error}");
+    }
+
+    let response = client
+        .post(format!("http://{app_addr}/api/launch"))
+        .body(r#"{"vmid": 200}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .all(|request| request.method != "POST"));
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn launch_cancel_leaves_vms_untouched() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = post_launch(app_addr, serde_json::json!({ "vmid": 200 })).await;
+    assert_eq!(response.status, "needs_action");
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "cancel" }),
+    )
+    .await;
+    assert_eq!(response.status, "cancelled");
+
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(200).await, Some(VmStatus::Stopped));
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .all(|request| request.method != "POST"));
+}
+
+#[tokio::test]
+async fn concurrent_launch_is_rejected_with_conflict() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "first").await;
+    insert_stopped_vm(&handle, 300, "second").await;
+    handle.set_ignore_shutdown(true).await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&serde_json::json!({ "vmid": 300 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "Launch already in progress" })
+    );
+
+    let unblock = handle.clone();
+    tokio::spawn(async move {
+        unblock.set_status(100, VmStatus::Stopped).await;
+    });
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(300).await, Some(VmStatus::Stopped));
+}
+
+/// Captures formatted tracing output for the current (single-threaded) test runtime.
+#[derive(Clone, Default)]
+struct LogCapture(Arc<Mutex<Vec<u8>>>);
+
+impl LogCapture {
+    fn install(&self) -> tracing::subscriber::DefaultGuard {
+        let capture = self.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || capture.clone())
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    fn contains(&self, needle: &str) -> bool {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).contains(needle)
+    }
+
+    async fn wait_for(&self, needle: &str) -> bool {
+        timeout(Duration::from_secs(5), async {
+            while !self.contains(needle) {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+impl Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn spawn_dry_run_agent(handle: &DummyHandle) -> SocketAddr {
+    spawn_agent_with(handle, |state| {
+        state.with_shutdown_config(ShutdownConfig { dry_run: true })
+    })
+    .await
+}
+
+async fn post_host_shutdown(app_addr: SocketAddr, body: serde_json::Value) -> LaunchResponse {
+    Client::new()
+        .post(format!("http://{app_addr}/api/host-shutdown"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json::<LaunchResponse>()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn host_shutdown_dry_run_without_running_vms() {
+    let logs = LogCapture::default();
+    let _guard = logs.install();
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_dry_run_agent(&handle).await;
+
+    let response = post_host_shutdown(app_addr, serde_json::json!({})).await;
+    assert_eq!(response.status, "started");
+    assert!(logs.wait_for("[dry-run] would call shutdown -h now").await);
+}
+
+#[tokio::test]
+async fn host_shutdown_dry_run_stops_running_vm_first() {
+    let logs = LogCapture::default();
+    let _guard = logs.install();
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    let app_addr = spawn_dry_run_agent(&handle).await;
+
+    let response = post_host_shutdown(app_addr, serde_json::json!({})).await;
+    assert_eq!(response.status, "needs_action");
+    assert_eq!(response.running_vm.map(|vm| vm.vmid), Some(100));
+    assert!(!logs.contains("[dry-run]"));
+
+    let response = post_host_shutdown(app_addr, serde_json::json!({ "action": "shutdown" })).await;
+    assert_eq!(response.status, "started");
+    assert!(logs.wait_for("[dry-run] would call shutdown -h now").await);
+    assert_eq!(handle.status(100).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn health_reports_proxmox_reachability() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/health"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "status": "ok", "cluster_quorate": true })
+    );
+    // Token privileges are checked once at startup, not on every poll.
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .all(|request| !request.path.contains("/access/")));
+
+    let unreachable =
+        ProxmoxClient::new("http://127.0.0.1:9", "token-id", "token-secret", false).unwrap();
+    let app_addr = spawn_app(router(AppState::new(unreachable))).await;
+    let response = Client::new()
+        .get(format!("http://{app_addr}/health"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn list_storages_returns_node_storage_pools() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .add_storage(StorageEntry {
+            storage: "local-lvm".to_string(),
+            storage_type: "lvmthin".to_string(),
+            content: "images,rootdir".to_string(),
+            avail: 600,
+            total: 1000,
+            used: 400,
+            enabled: 1,
+            active: 1,
+            shared: 0,
+        })
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let storages = client
+        .get(format!("http://{app_addr}/api/nodes/pve/storages"))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        storages,
+        serde_json::json!([{
+            "storage": "local-lvm",
+            "type": "lvmthin",
+            "avail": 600,
+            "total": 1000,
+            "used": 400,
+            "enabled": true,
+        }])
+    );
+
+    let response = client
+        .get(format!("http://{app_addr}/api/nodes/missing/storages"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn fork_passes_target_storage_node_and_pool_to_clone() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle
+        .add_storage(StorageEntry {
+            storage: "fast".to_string(),
+            storage_type: "zfspool".to_string(),
+            content: "images".to_string(),
+            avail: 1,
+            total: 1,
+            used: 0,
+            enabled: 1,
+            active: 1,
+            shared: 0,
+        })
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({
+            "vmid": 101,
+            "name": "alpha-fast",
+            "target_storage": "fast",
+            "target_node": "pve",
+            "target_pool": "lab",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let clones = handle.clones().await;
+    assert_eq!(clones.len(), 1);
+    let clone = &clones[0];
+    assert_eq!(clone.name.as_deref(), Some("alpha-fast"));
+    assert_eq!(clone.full, Some(1));
+    assert_eq!(clone.storage.as_deref(), Some("fast"));
+    assert_eq!(clone.target.as_deref(), Some("pve"));
+    assert_eq!(clone.pool.as_deref(), Some("lab"));
+    assert!(clone
+        .snapname
+        .as_deref()
+        .is_some_and(|name| name.starts_with("fork-")));
+}
+
+async fn post_clone(app_addr: SocketAddr, vmid: u64, body: serde_json::Value) -> u64 {
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/{vmid}/clone"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    body["vmid"]
+        .as_u64()
+        .expect("clone response carries the vmid")
+}
+
+async fn create_agent_snapshot(app_addr: SocketAddr, vmid: u64, name: &str) {
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/{vmid}/snapshots"))
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn clone_defaults_to_linked_clone_of_current_state() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let vmid = post_clone(app_addr, 101, serde_json::json!({ "name": "alpha-linked" })).await;
+    assert_eq!(vmid, 102);
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+    let clones = handle.clones().await;
+    assert_eq!(clones.len(), 1);
+    assert_eq!(clones[0].full, Some(0));
+    assert_eq!(clones[0].snapname, None);
+    assert!(handle.snapshots(101).await.is_empty());
+}
+
+#[tokio::test]
+async fn linked_clone_uses_the_given_snapshot() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    create_agent_snapshot(app_addr, 101, "golden").await;
+
+    let body = serde_json::json!({ "name": "alpha-golden", "snapshot": "golden", "full": false });
+    assert_eq!(post_clone(app_addr, 101, body).await, 102);
+    let clones = handle.clones().await;
+    assert_eq!(clones[0].full, Some(0));
+    assert_eq!(clones[0].snapname.as_deref(), Some("golden"));
+    assert_eq!(handle.snapshots(101).await.len(), 1);
+}
+
+#[tokio::test]
+async fn full_clone_uses_the_given_snapshot_without_taking_another() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle
+        .add_storage(StorageEntry {
+            storage: "fast".to_string(),
+            storage_type: "zfspool".to_string(),
+            content: "images".to_string(),
+            avail: 1,
+            total: 1,
+            used: 0,
+            enabled: 1,
+            active: 1,
+            shared: 0,
+        })
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+    create_agent_snapshot(app_addr, 101, "golden").await;
+
+    let body = serde_json::json!({
+        "name": "alpha-full",
+        "snapshot": "golden",
+        "full": true,
+        "target_storage": "fast"
+    });
+    assert_eq!(post_clone(app_addr, 101, body).await, 102);
+    let clones = handle.clones().await;
+    assert_eq!(clones[0].full, Some(1));
+    assert_eq!(clones[0].snapname.as_deref(), Some("golden"));
+    assert_eq!(clones[0].storage.as_deref(), Some("fast"));
+    assert_eq!(handle.snapshots(101).await.len(), 1);
+}
+
+#[tokio::test]
+async fn full_clone_without_snapshot_takes_one_like_fork() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let body = serde_json::json!({ "name": "alpha-full", "full": true });
+    assert_eq!(post_clone(app_addr, 101, body).await, 102);
+    let snapshots = handle.snapshots(101).await;
+    assert_eq!(snapshots.len(), 1);
+    assert!(snapshots[0].name.starts_with("fork-"));
+    let clones = handle.clones().await;
+    assert_eq!(clones[0].full, Some(1));
+    assert_eq!(clones[0].snapname, Some(snapshots[0].name.clone()));
+}
+
+#[tokio::test]
+async fn clone_from_unknown_snapshot_fails() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/101/clone"))
+        .json(&serde_json::json!({ "name": "alpha-copy", "snapshot": "missing" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+    assert!(handle.vm(102).await.is_none());
+}
+
+#[tokio::test]
+async fn linked_fork_omits_unset_clone_targets() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-linked", "full_clone": false }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let clones = handle.clones().await;
+    assert_eq!(clones.len(), 1);
+    assert_eq!(clones[0].full, Some(0));
+    assert_eq!(clones[0].storage, None);
+    assert_eq!(clones[0].target, None);
+    assert_eq!(clones[0].pool, None);
+}
+
+#[tokio::test]
+async fn launch_with_suspend_pauses_running_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "suspend" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(100).await, Some(VmStatus::Paused));
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn dummy_rejects_clients_with_wrong_token() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle.require_auth("root@pam!agent", "s3cret").await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let base_url = format!("http://{dummy_addr}");
+
+    let client = ProxmoxClient::new(base_url.clone(), "root@pam!agent", "s3cret", false).unwrap();
+    let vms = client.list_vms().await.unwrap();
+    assert_eq!(vms.len(), 1);
+
+    let client = ProxmoxClient::new(base_url, "root@pam!agent", "wrong", false).unwrap();
+    let err = client.list_vms().await.unwrap_err();
+    assert!(
+        matches!(err, ProxmoxError::Unauthorized),
+        "unexpected error: {err:?}"
+    );
+
+    handle.set_auth_required(false).await;
+    assert!(client.list_vms().await.is_ok());
+}
+
+#[tokio::test]
+async fn node_routes_report_cluster_topology() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let nodes = client
+        .get(format!("http://{app_addr}/api/nodes"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0]["node"], "pve");
+    assert_eq!(nodes[0]["status"], "online");
+    assert!(nodes[0]["maxmem"].as_u64().unwrap() > nodes[0]["mem"].as_u64().unwrap());
+
+    let status = client
+        .get(format!("http://{app_addr}/api/nodes/pve/status"))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(status["node"], "pve");
+    assert_eq!(status["uptime"], 3600);
+    assert!(status["kernel_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("Linux"));
+
+    let response = client
+        .get(format!("http://{app_addr}/api/nodes/missing/status"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn failed_clone_task_reports_exit_status_and_log() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle
+        .fail_next_task("clone failed: not enough space on storage")
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 101, "name": "alpha-copy" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .ends_with("failed: clone failed: not enough space on storage"));
+    assert!(handle.vm(102).await.is_none());
+
+    let tasks = handle.tasks().await;
+    assert_eq!(tasks.len(), 1);
+    let log_path = format!("/nodes/pve/tasks/{}/log", tasks[0].upid);
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .any(|request| request.path.ends_with(&log_path)));
+}
+
+#[tokio::test]
+async fn get_task_log_returns_configured_lines() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+
+    let mut opts = ForkOptions::new("alpha-copy");
+    opts.full_clone = false;
+    client.fork_vm(101, opts).await.unwrap();
+    let upid = handle.tasks().await[0].upid.clone();
+    handle
+        .set_task_log(
+            &upid,
+            vec!["create linked clone".to_string(), "TASK OK".to_string()],
+        )
+        .await;
+
+    let lines = client.get_task_log("pve", &upid).await.unwrap();
+    assert_eq!(lines, vec!["create linked clone", "TASK OK"]);
+}
+
+async fn insert_tagged_vm(handle: &DummyHandle, vmid: u64, name: &str, tags: &[&str]) {
+    handle
+        .insert_vm(VmEntry::builder(vmid, name).tags(tags).build())
+        .await;
+}
+
+async fn post_launch_by_tag(app_addr: SocketAddr, tag: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!("http://{app_addr}/api/launch/by-tag"))
+        .json(&serde_json::json!({ "tag": tag, "action": null }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn launch_by_tag_without_match_returns_not_found() {
+    let handle = DummyHandle::new("pve");
+    insert_tagged_vm(&handle, 101, "alpha", &["work"]).await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = post_launch_by_tag(app_addr, "gaming").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn launch_by_tag_starts_single_match() {
+    let handle = DummyHandle::new("pve");
+    insert_tagged_vm(&handle, 101, "alpha", &["work"]).await;
+    insert_tagged_vm(&handle, 102, "beta", &["gaming", "primary"]).await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch_by_tag(app_addr, "gaming").await;
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.json::<LaunchResponse>().await.unwrap().status,
+        "started"
+    );
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(102).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn launch_by_tag_with_several_matches_returns_conflict() {
+    let handle = DummyHandle::new("pve");
+    insert_tagged_vm(&handle, 101, "alpha", &["gaming"]).await;
+    insert_tagged_vm(&handle, 102, "beta", &["gaming"]).await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = post_launch_by_tag(app_addr, "gaming").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn notes_endpoints_read_and_write_vm_notes() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let notes_url = format!("http://{app_addr}/api/vms/101/notes");
+
+    let notes = client
+        .get(&notes_url)
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(notes, serde_json::json!({ "notes": "" }));
+
+    let response = client
+        .post(&notes_url)
+        .json(&serde_json::json!({ "notes": "Game library lives on D:" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let notes = client
+        .get(&notes_url)
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(notes["notes"], "Game library lives on D:");
+
+    let vms = client
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    assert_eq!(vms[0].notes.as_deref(), Some("Game library lives on D:"));
+}
+
+#[tokio::test]
+async fn notes_endpoints_reject_unknown_vm_and_oversized_notes() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .get(format!("http://{app_addr}/api/vms/999/notes"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/101/notes"))
+        .json(&serde_json::json!({ "notes": "x".repeat(65536) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(handle.vm(101).await.unwrap().notes, None);
+}
+
+#[tokio::test]
+async fn reconfigured_client_uses_overridden_credentials() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    handle.require_auth("root@pam!agent", "rotated").await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+
+    let stale = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "root@pam!agent",
+        "old",
+        false,
+    )
+    .unwrap();
+    assert!(matches!(
+        stale.list_vms().await,
+        Err(ProxmoxError::Unauthorized)
+    ));
+
+    let rotated = stale.reconfigure().token_secret("rotated").build().unwrap();
+    assert_eq!(rotated.list_vms().await.unwrap().len(), 1);
+
+    let elsewhere = rotated
+        .reconfigure()
+        .base_url("http://127.0.0.1:9")
+        .build()
+        .unwrap();
+    assert!(elsewhere.list_vms().await.is_err());
+    assert!(rotated.list_vms().await.is_ok());
+}
+
+async fn insert_many_vms(handle: &DummyHandle, count: u64) {
+    for vmid in 100..100 + count {
+        insert_stopped_vm(handle, vmid, &format!("compressible-vm-{vmid}")).await;
+    }
+}
+
+#[tokio::test]
+async fn list_vms_is_gzip_compressed_when_requested() {
+    let handle = DummyHandle::new("pve");
+    insert_many_vms(&handle, 40).await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/api/vms"))
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .unwrap(),
+        "gzip"
+    );
+    let compressed = response.bytes().await.unwrap();
+    let mut json = String::new();
+    std::io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(compressed.as_ref()),
+        &mut json,
+    )
+    .unwrap();
+    assert!(json.len() > compressed.len());
+    let vms: Vec<ApiVm> = serde_json::from_str(&json).unwrap();
+    assert_eq!(vms.len(), 40);
+}
+
+#[tokio::test]
+async fn list_vms_is_uncompressed_without_accept_encoding() {
+    let handle = DummyHandle::new("pve");
+    insert_many_vms(&handle, 40).await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .is_none());
+    let vms = response.json::<Vec<ApiVm>>().await.unwrap();
+    assert_eq!(vms.len(), 40);
+}
+
+#[tokio::test]
+async fn rapid_requests_are_rate_limited_per_ip() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state.with_rate_limit(RateLimitConfig {
+            requests_per_second: 1.0,
+            burst: 5,
+        })
+    })
+    .await;
+    let client = Client::new();
+
+    let mut statuses = Vec::new();
+    for _ in 0..20 {
+        let response = client
+            .get(format!("http://{app_addr}/api/vms"))
+            .send()
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(headers.get("x-ratelimit-limit").unwrap(), "5");
+        assert!(headers.contains_key("x-ratelimit-remaining"));
+        assert!(headers.contains_key("x-ratelimit-reset"));
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            assert!(headers.contains_key(reqwest::header::RETRY_AFTER));
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["error"], "Too many requests");
+        }
+        statuses.push(status);
+    }
+    let limited = statuses
+        .iter()
+        .filter(|status| **status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        .count();
+    assert!(
+        limited >= 10,
+        "expected most requests to be limited: {statuses:?}"
+    );
+    assert!(statuses[..5].iter().all(|status| status.is_success()));
+
+    let health = client
+        .get(format!("http://{app_addr}/health"))
+        .send()
+        .await
+        .unwrap();
+    assert!(health.status().is_success());
+    assert!(!health.headers().contains_key("x-ratelimit-limit"));
+}
+
+#[tokio::test]
+async fn concurrent_request_burst_is_capped_with_503() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    // Slow inventory calls keep the first requests in flight during the burst.
+    handle
+        .set_error_on_route(
+            "/api2/json/cluster/resources",
+            InjectedError::Delay(Duration::from_millis(500)),
+        )
+        .await;
+    let app_addr = spawn_agent_with(&handle, |state| state.with_connection_limit(100)).await;
+    let client = Client::new();
+
+    let burst = futures_util::future::join_all(
+        (0..150).map(|_| client.get(format!("http://{app_addr}/api/vms")).send()),
+    );
+    let health = async {
+        sleep(Duration::from_millis(200)).await;
+        client
+            .get(format!("http://{app_addr}/health"))
+            .send()
+            .await
+            .unwrap()
+    };
+    let (responses, health) = tokio::join!(burst, health);
+    assert!(health.status().is_success());
+
+    let mut rejected = 0;
+    for response in responses {
+        let response = response.unwrap();
+        if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            assert_eq!(response.headers()[reqwest::header::RETRY_AFTER], "1");
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["error"], "Server busy");
+            rejected += 1;
+        } else {
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+    }
+    assert!(
+        (1..=50).contains(&rejected),
+        "{rejected} of 150 requests rejected"
+    );
+
+    handle.clear_route_errors().await;
+    let response = client
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+async fn select_fallback(selector: FallbackSelector) -> Option<u64> {
+    let handle = DummyHandle::new("pve");
+    insert_tagged_vm(&handle, 120, "desktop", &["fallback"]).await;
+    insert_tagged_vm(&handle, 110, "media", &["fallback", "media"]).await;
+    insert_tagged_vm(&handle, 105, "desktop", &["dev"]).await;
+    insert_stopped_vm(&handle, 130, "scratch").await;
+
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+    let vms = client.list_vms().await.unwrap();
+    selector.select(&vms).map(|vm| vm.vmid)
+}
+
+#[tokio::test]
+async fn fallback_selects_lowest_vmid_by_name() {
+    let selector = FallbackSelector::new(Some("desktop".to_string()), None).unwrap();
+    assert_eq!(select_fallback(selector).await, Some(105));
+}
+
+#[tokio::test]
+async fn fallback_selects_lowest_vmid_by_tag() {
+    let selector = FallbackSelector::new(None, Some("fallback".to_string())).unwrap();
+    assert_eq!(select_fallback(selector).await, Some(110));
+}
+
+#[tokio::test]
+async fn fallback_requires_name_and_tag_when_both_set() {
+    let selector =
+        FallbackSelector::new(Some("desktop".to_string()), Some("fallback".to_string())).unwrap();
+    assert_eq!(select_fallback(selector).await, Some(120));
+
+    let selector =
+        FallbackSelector::new(Some("scratch".to_string()), Some("fallback".to_string())).unwrap();
+    assert_eq!(select_fallback(selector).await, None);
+    assert!(FallbackSelector::new(None, None).is_none());
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkActionResponse {
+    results: Vec<BulkActionResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkActionResult {
+    vmid: u64,
+    ok: bool,
+    error: Option<String>,
+}
+
+async fn post_bulk_action(app_addr: SocketAddr, vmids: &[u64], action: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!("http://{app_addr}/api/vms/bulk-action"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "vmids": vmids, "action": action }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn bulk_action_starts_all_vms() {
+    let handle = DummyHandle::new("pve");
+    for vmid in [100, 101, 102] {
+        insert_stopped_vm(&handle, vmid, &format!("lab-{vmid}")).await;
+    }
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_bulk_action(app_addr, &[100, 101, 102], "start").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<BulkActionResponse>().await.unwrap();
+    assert_eq!(body.results.len(), 3);
+    assert!(body.results.iter().all(|result| result.ok));
+    for vmid in [100, 101, 102] {
+        assert_eq!(handle.status(vmid).await, Some(VmStatus::Running));
+    }
+}
+
+#[tokio::test]
+async fn bulk_action_reports_partial_failure_as_multi_status() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 100, "lab-100").await;
+    insert_stopped_vm(&handle, 101, "lab-101").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_bulk_action(app_addr, &[100, 999, 101], "start").await;
+    assert_eq!(response.status(), reqwest::StatusCode::MULTI_STATUS);
+    let body = response.json::<BulkActionResponse>().await.unwrap();
+    let failed: Vec<_> = body.results.iter().filter(|result| !result.ok).collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].vmid, 999);
+    assert!(failed[0].error.is_some());
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn bulk_action_rejects_oversized_requests() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let vmids: Vec<u64> = (100..121).collect();
+    let response = post_bulk_action(app_addr, &vmids, "stop").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn cluster_status_reports_dummy_cluster() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/api/cluster/status"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({
+            "name": "pve-cluster",
+            "quorate": true,
+            "nodes": [{ "name": "pve", "online": true, "local": true }],
+        })
+    );
+}
+
+async fn get_in_progress(app_addr: SocketAddr, flow: &str) -> serde_json::Value {
+    Client::new()
+        .get(format!("http://{app_addr}/api/{flow}/in-progress"))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn launch_in_progress_tracks_flow() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    handle.set_ignore_shutdown(true).await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+    assert_eq!(
+        *launches.borrow_and_update(),
+        LaunchStateSnapshot::default()
+    );
+
+    let idle = get_in_progress(app_addr, "launch").await;
+    assert_eq!(
+        idle,
+        serde_json::json!({
+            "in_progress": false,
+            "target_vmid": null,
+            "current_action": null,
+            "started_at_ms": null,
+        })
+    );
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+
+    let busy = get_in_progress(app_addr, "launch").await;
+    assert_eq!(busy["in_progress"], true);
+    assert_eq!(busy["target_vmid"], 200);
+    assert_eq!(busy["current_action"], "shutdown");
+    assert!(busy["started_at_ms"].as_u64().unwrap() > 0);
+
+    timeout(Duration::from_secs(5), launches.changed())
+        .await
+        .expect("launch start should be published")
+        .unwrap();
+    let started = *launches.borrow_and_update();
+    assert!(started.in_progress);
+    assert_eq!(started.target_vmid, Some(200));
+    assert!(started.started_at.is_some());
+
+    handle.set_status(100, VmStatus::Stopped).await;
+    timeout(Duration::from_secs(5), launches.changed())
+        .await
+        .expect("launch never returned to idle")
+        .unwrap();
+    assert_eq!(*launches.borrow(), LaunchStateSnapshot::default());
+    assert_eq!(get_in_progress(app_addr, "launch").await, idle);
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn launch_survives_a_proxmox_network_partition() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    handle.set_ignore_shutdown(true).await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+
+    // The flow is now polling VM 100 every 2 seconds; the guest stops while
+    // Proxmox is unreachable, so only a retry after the partition sees it.
+    let partition_ends = std::time::SystemTime::now() + Duration::from_secs(2);
+    handle
+        .simulate_network_partition(Duration::from_secs(2))
+        .await;
+    handle.set_status(100, VmStatus::Stopped).await;
+    let response = reqwest::get(format!("http://{app_addr}/api/vms"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_GATEWAY);
+
+    timeout(
+        Duration::from_secs(15),
+        launches.wait_for(|launch| !launch.in_progress),
+    )
+    .await
+    .expect("launch never finished")
+    .unwrap();
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+    let started_at = handle.transitions(200).await[0].transitioned_at;
+    let partition_ends_ms = partition_ends
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    assert!(
+        started_at >= partition_ends_ms,
+        "started during the partition"
+    );
+}
+
+#[tokio::test]
+async fn launch_does_not_resend_a_start_that_timed_out() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 200, "target").await;
+    // Proxmox receives the start but answers after the client gave up.
+    handle
+        .set_error_on_route(
+            "/api2/json/nodes/pve/qemu/200/status/start",
+            InjectedError::Delay(Duration::from_secs(1)),
+        )
+        .await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::builder(format!("http://{dummy_addr}"), "token-id", "token-secret")
+        .request_timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    let state = AppState::new(client);
+    let mut launches = state.subscribe_launch_status();
+    let app_addr = spawn_app(router(state)).await;
+
+    let response = post_launch(app_addr, serde_json::json!({ "vmid": 200 })).await;
+    assert_eq!(response.status, "started");
+    wait_for_launch(&mut launches).await;
+
+    handle.assert_action_sequence(&[(200, "start")]).await;
+}
+
+#[tokio::test]
+async fn host_shutdown_in_progress_tracks_flow() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    handle.set_ignore_shutdown(true).await;
+    let app_addr = spawn_dry_run_agent(&handle).await;
+
+    assert_eq!(
+        get_in_progress(app_addr, "host-shutdown").await["in_progress"],
+        false
+    );
+    let response = post_host_shutdown(app_addr, serde_json::json!({ "action": "shutdown" })).await;
+    assert_eq!(response.status, "started");
+
+    let busy = get_in_progress(app_addr, "host-shutdown").await;
+    assert_eq!(busy["in_progress"], true);
+    assert_eq!(busy["current_action"], "shutdown");
+    assert!(busy["started_at_ms"].as_u64().unwrap() > 0);
+
+    handle.set_status(100, VmStatus::Stopped).await;
+    for _ in 0..50 {
+        if get_in_progress(app_addr, "host-shutdown").await["in_progress"] == false {
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("host shutdown never returned to idle");
+}
+
+#[tokio::test]
+async fn second_terminate_launch_escalates_running_shutdown() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "stubborn").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    handle.set_ignore_shutdown(true).await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let first = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "shutdown" }),
+    )
+    .await;
+    assert_eq!(first.status, "started");
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+
+    let requests_before_escalation = handle.requests().await.len();
+    let second = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "terminate" }),
+    )
+    .await;
+    assert_eq!(second.status, "updated");
+
+    wait_for_launch(&mut launches).await;
+    assert_eq!(handle.status(200).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(100).await, Some(VmStatus::Stopped));
+
+    let requests = handle.requests().await;
+    let position = |suffix: &str| {
+        requests
+            .iter()
+            .position(|request| request.method == "POST" && request.path.ends_with(suffix))
+    };
+    let shutdown = position("/qemu/100/status/shutdown").expect("shutdown was not sent");
+    let stop = position("/qemu/100/status/stop").expect("escalated stop was not sent");
+    assert!(shutdown < requests_before_escalation);
+    assert!(stop >= requests_before_escalation);
+    assert!(stop < position("/qemu/200/status/start").expect("target was not started"));
+}
+
+#[tokio::test]
+async fn out_of_range_vmids_are_rejected_before_proxmox() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&serde_json::json!({ "vmid": 99 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "error": "VM ID 99 is below the minimum of 100" })
+    );
+
+    let response = client
+        .get(format!("http://{app_addr}/api/vms/1000000000/notes"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client
+        .post(format!("http://{app_addr}/api/fork"))
+        .json(&serde_json::json!({ "vmid": 42, "name": "copy" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert!(handle.requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn backup_starts_task_and_lists_archive() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 100, "alpha").await;
+    handle
+        .add_storage(StorageEntry {
+            storage: "backups".to_string(),
+            storage_type: "dir".to_string(),
+            content: "backup".to_string(),
+            avail: 600,
+            total: 1000,
+            used: 400,
+            enabled: 1,
+            active: 1,
+            shared: 0,
+        })
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/100/backup"))
+        .json(&serde_json::json!({
+            "storage": "backups",
+            "mode": "stop",
+            "compress": "gzip",
+            "notes": "before upgrade",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    let tasks = handle.tasks().await;
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].kind, "vzdump");
+    assert_eq!(body["upid"], tasks[0].upid);
+
+    let backups = client
+        .get(format!(
+            "http://{app_addr}/api/vms/100/backup?storage=backups"
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(backups.len(), 1);
+    assert!(backups[0]["volid"]
+        .as_str()
+        .unwrap()
+        .starts_with("backups:backup/vzdump-qemu-100-"));
+    assert_eq!(backups[0]["format"], "vma.gz");
+    assert_eq!(backups[0]["notes"], "before upgrade");
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/100/backup"))
+        .json(&serde_json::json!({ "storage": "missing" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(!response.status().is_success());
+}
+
+#[tokio::test]
+async fn security_headers_are_sent_by_default() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let response = client
+        .get(format!("http://{app_addr}/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers["x-frame-options"], "DENY");
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["referrer-policy"], "same-origin");
+    assert_eq!(headers["content-security-policy"], "default-src 'self'");
+    let page = response.text().await.unwrap();
+    assert!(
+        !page.contains("<style"),
+        "inline styles break the default CSP"
+    );
+
+    let response = client
+        .get(format!("http://{app_addr}/api/docs"))
+        .send()
+        .await
+        .unwrap();
+    let csp = response.headers()["content-security-policy"]
+        .to_str()
+        .unwrap();
+    assert!(csp.contains("https://unpkg.com"), "{csp}");
+
+    let app_addr = spawn_agent_with(&handle, |state| state.without_security_headers()).await;
+    let response = client
+        .get(format!("http://{app_addr}/"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get("x-frame-options").is_none());
+    assert!(response.headers().get("content-security-policy").is_none());
+}
+
+#[tokio::test]
+async fn openapi_spec_and_docs_are_served() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_agent(&handle).await;
+
+    let response = Client::new()
+        .get(format!("http://{app_addr}/api/openapi.json"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.headers()[reqwest::header::CONTENT_TYPE],
+        "application/json"
+    );
+    let spec = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(spec["openapi"], "3.0.3");
+    for path in ["/api/vms", "/api/launch", "/api/fork", "/api/host-shutdown"] {
+        assert!(spec["paths"].get(path).is_some(), "missing {path}");
+    }
+    for schema in [
+        "ApiVm",
+        "LaunchRequest",
+        "LaunchResponse",
+        "ForkRequest",
+        "ForkResponse",
+        "ShutdownRequest",
+        "ShutdownResponse",
+        "ApiError",
+    ] {
+        assert!(
+            spec["components"]["schemas"].get(schema).is_some(),
+            "missing {schema}"
+        );
+    }
+    assert!(spec["components"]["headers"].get("XApiVersion").is_some());
+    for response in ["MalformedApiVersion", "UnsupportedApiVersion"] {
+        assert!(
+            spec["components"]["responses"].get(response).is_some(),
+            "missing {response}"
+        );
+    }
+    assert_eq!(
+        spec["paths"]["/api/vms"]["get"]["responses"]["406"]["$ref"],
+        "#/components/responses/UnsupportedApiVersion"
+    );
+
+    let docs = Client::new()
+        .get(format!("http://{app_addr}/api/docs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(docs.status(), reqwest::StatusCode::OK);
+    assert!(docs.text().await.unwrap().contains("/api/openapi.json"));
+}
+
+#[tokio::test]
+async fn list_node_vms_returns_vms_on_that_node() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(101, "alpha")
+                .tags(&["dev"])
+                .status(VmStatus::Running)
+                .notes(Some("ignored by the node listing"))
+                .build(),
+        )
+        .await;
+    handle
+        .set_vm_resources(
+            101,
+            VmResources {
+                maxmem: Some(2048),
+                maxcpu: Some(2.0),
+                disk: Some(4096),
+                uptime: Some(60),
+            },
+        )
+        .await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+
+    let vms = client
+        .get(format!("http://{app_addr}/api/nodes/pve/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        vms,
+        serde_json::json!([{
+            "vmid": 101,
+            "name": "alpha",
+            "tags": ["dev"],
+            "status": "running",
+            "notes": null,
+            "node": "pve",
+            "maxmem": 2048,
+            "maxcpu": 2.0,
+            "disk": 4096,
+            "uptime": 60,
+        }])
+    );
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .any(|request| request.path == "/api2/json/nodes/pve/qemu"));
+
+    let response = client
+        .get(format!("http://{app_addr}/api/nodes/missing/vms"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn delete_vm_starts_a_destroy_task() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "throwaway").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+
+    let response = client
+        .delete(format!("http://{app_addr}/admin/vms/101?purge=true"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body["task_id"].as_str().unwrap().starts_with("UPID:pve:"));
+    assert_eq!(handle.status(101).await, None);
+
+    let response = client
+        .delete(format!("http://{app_addr}/admin/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn vm_tasks_list_the_start_task() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    insert_stopped_vm(&handle, 102, "other").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    assert_eq!(
+        post_power(app_addr, 101, "start").await.status(),
+        reqwest::StatusCode::OK
+    );
+    assert_eq!(
+        post_power(app_addr, 102, "start").await.status(),
+        reqwest::StatusCode::OK
+    );
+    assert_eq!(
+        post_power(app_addr, 101, "shutdown").await.status(),
+        reqwest::StatusCode::OK
+    );
+
+    let tasks = reqwest::get(format!("http://{app_addr}/api/vms/101/tasks"))
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    let types: Vec<_> = tasks
+        .iter()
+        .map(|task| task["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, ["qmshutdown", "qmstart"]);
+    assert_eq!(tasks[1]["status"], "OK");
+    assert_eq!(tasks[1]["user"], "root@pam");
+    assert!(tasks[1]["upid"].as_str().unwrap().contains(":qmstart:101:"));
+
+    let tasks = reqwest::get(format!("http://{app_addr}/api/vms/101/tasks?limit=1"))
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0]["type"], "qmshutdown");
+}
+
+/// Follows `next_cursor` from the first page of `path` (an empty `cursor`) to
+/// the last, returning every page's `data` and the cursors seen on the way.
+async fn fetch_all_pages(
+    app_addr: SocketAddr,
+    path: &str,
+    limit: u32,
+) -> (Vec<serde_json::Value>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut cursors = Vec::new();
+    loop {
+        let cursor = cursors.last().map_or("", String::as_str);
+        let url = format!("http://{app_addr}{path}?limit={limit}&cursor={cursor}");
+        let page = reqwest::get(url)
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        let data = page["data"].as_array().unwrap();
+        assert!(data.len() <= limit as usize, "{page}");
+        entries.extend(data.iter().cloned());
+        match page["next_cursor"].as_str() {
+            Some(cursor) => cursors.push(cursor.to_string()),
+            None => return (entries, cursors),
+        }
+    }
+}
+
+#[tokio::test]
+async fn vm_tasks_are_paged_by_cursor() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    for action in ["start", "stop", "start", "stop", "start"] {
+        assert_eq!(
+            post_power(app_addr, 101, action).await.status(),
+            reqwest::StatusCode::OK
+        );
+    }
+    let all = reqwest::get(format!("http://{app_addr}/api/vms/101/tasks"))
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 5);
+    let upid = |index: usize| all[index]["upid"].as_str().unwrap().to_string();
+
+    let (tasks, cursors) = fetch_all_pages(app_addr, "/api/vms/101/tasks", 2).await;
+    assert_eq!(cursors, [upid(1), upid(3)]);
+    assert_eq!(tasks, all);
+
+    let (tasks, cursors) = fetch_all_pages(app_addr, "/api/vms/101/tasks", 5).await;
+    assert!(cursors.is_empty());
+    assert_eq!(tasks, all);
+
+    for query in ["cursor=&limit=0", "cursor=&limit=501", "cursor=not-a-upid"] {
+        let response = reqwest::get(format!("http://{app_addr}/api/vms/101/tasks?{query}"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{query}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn vm_list_is_paged_by_vmid() {
+    let handle = DummyHandle::new("pve");
+    for vmid in [104, 101, 105, 103, 102] {
+        insert_stopped_vm(&handle, vmid, &format!("vm-{vmid}")).await;
+    }
+    let app_addr = spawn_agent(&handle).await;
+
+    let page = reqwest::get(format!("http://{app_addr}/api/vms?limit=2&cursor="))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 5);
+    assert_eq!(page["next_cursor"], "102");
+
+    let (vms, cursors) = fetch_all_pages(app_addr, "/api/vms", 2).await;
+    assert_eq!(cursors, ["102", "104"]);
+    let vmids: Vec<u64> = vms.iter().map(|vm| vm["vmid"].as_u64().unwrap()).collect();
+    assert_eq!(vmids, [101, 102, 103, 104, 105]);
+
+    let unpaged = reqwest::get(format!("http://{app_addr}/api/vms"))
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(unpaged.len(), 5);
+    let first = reqwest::get(format!("http://{app_addr}/api/vms?limit=2"))
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    let vmids: Vec<u64> = first
+        .iter()
+        .map(|vm| vm["vmid"].as_u64().unwrap())
+        .collect();
+    assert_eq!(vmids, [101, 102]);
+
+    for query in ["cursor=abc", "limit=501", "cursor=&limit=501"] {
+        let response = reqwest::get(format!("http://{app_addr}/api/vms?{query}"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{query}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn vm_metrics_return_rrd_series_for_timeframe() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "alpha").await;
+    let app_addr = spawn_agent(&handle).await;
+
+    let metrics = reqwest::get(format!("http://{app_addr}/api/vms/101/metrics"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(metrics["timeframe"], "hour");
+    let timestamps = metrics["timestamps"].as_array().unwrap();
+    assert_eq!(timestamps.len(), 70);
+    assert_eq!(
+        timestamps[1].as_u64().unwrap() - timestamps[0].as_u64().unwrap(),
+        60
+    );
+    for series in ["cpu", "mem", "netin", "netout"] {
+        let values = metrics[series].as_array().unwrap();
+        assert_eq!(values.len(), timestamps.len());
+        assert!(values[0].is_f64());
+        assert!(values.last().unwrap().is_null());
+    }
+
+    let metrics = reqwest::get(format!(
+        "http://{app_addr}/api/vms/101/metrics?timeframe=week"
+    ))
+    .await
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(metrics["timeframe"], "week");
+    let timestamps = metrics["timestamps"].as_array().unwrap();
+    assert_eq!(
+        timestamps[1].as_u64().unwrap() - timestamps[0].as_u64().unwrap(),
+        10800
+    );
+    assert!(handle
+        .requests()
+        .await
+        .iter()
+        .any(|request| request.path == "/api2/json/nodes/pve/qemu/101/rrddata"));
+
+    let response = reqwest::get(format!(
+        "http://{app_addr}/api/vms/101/metrics?timeframe=decade"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+async fn post_power(app_addr: SocketAddr, vmid: u64, action: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!("http://{app_addr}/api/vms/{vmid}/{action}"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn provision_sets_cloudinit_config_for_admins() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 150, "clone").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms/150/provision");
+    let body = serde_json::json!({
+        "cloudinit_storage": "local-lvm",
+        "user": {
+            "username": "deploy",
+            "password_hash": "$6$salt$hash",
+            "ssh_keys": ["ssh-ed25519 AAAAC3Nza deploy@laptop"]
+        },
+        "ipconfigs": [{ "interface": 0, "ip": "dhcp" }]
+    });
+
+    let response = client.post(&url).json(&body).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({
+            "ipconfigs": [{ "interface": 0, "ip": "10.0.0.5", "gw": "10.0.0.1" }]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(handle.cloudinit(150).await.is_empty());
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let config = handle.cloudinit(150).await;
+    assert_eq!(config["ide2"], "local-lvm:cloudinit");
+    assert_eq!(config["ciuser"], "deploy");
+    assert_eq!(config["cipassword"], "$6$salt$hash");
+    assert_eq!(config["ipconfig0"], "ip=dhcp");
+}
+
+#[tokio::test]
+async fn import_disk_downloads_and_attaches_for_admins() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 150, "clone").await;
+    handle
+        .add_storage(StorageEntry {
+            storage: "local".to_string(),
+            storage_type: "dir".to_string(),
+            content: "images,import".to_string(),
+            avail: 1,
+            total: 1,
+            used: 0,
+            enabled: 1,
+            active: 1,
+            shared: 0,
+        })
+        .await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms/150/import-disk");
+    let body = serde_json::json!({
+        "storage": "local",
+        "url": "https://cloud-images.example.com/noble-server-cloudimg-amd64.qcow2"
+    });
+
+    let response = client.post(&url).json(&body).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "storage": "local", "url": "file:///etc/shadow" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(handle.downloads().await.is_empty());
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let imported: serde_json::Value = response.json().await.unwrap();
+    assert!(imported["upid"].as_str().unwrap().starts_with("UPID:pve:"));
+    assert_eq!(handle.downloads().await.len(), 1);
+    let disk = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(disk) = handle.disks(150).await.remove("scsi0") {
+                return disk;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("imported disk was not attached");
+    assert_eq!(
+        disk,
+        "local:0,import-from=local:import/noble-server-cloudimg-amd64.qcow2"
+    );
+}
+
+#[tokio::test]
+async fn sendkey_requires_admin_key_and_an_allowed_key() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "windows").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms/101/sendkey");
+
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "key": "ctrl-alt-delete" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "key": "shutdown -h now" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(handle.sent_keys().await.is_empty());
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "key": "ctrl-alt-delete" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let sent = handle.sent_keys().await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(
+        (sent[0].vmid, sent[0].key.as_str()),
+        (101, "ctrl-alt-delete")
+    );
+}
+
+#[tokio::test]
+async fn start_endpoint_starts_a_stopped_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_power(app_addr, 101, "start").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body, serde_json::json!({ "ok": true }));
+    wait_for_status(&handle, 101, VmStatus::Running).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+
+    let response = post_power(app_addr, 101, "start").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn stop_endpoint_stops_a_running_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_power(app_addr, 101, "stop").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap()["ok"],
+        true
+    );
+    wait_for_status(&handle, 101, VmStatus::Stopped).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+
+    let response = post_power(app_addr, 101, "stop").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn shutdown_endpoint_shuts_down_a_running_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "desktop").await;
+    insert_stopped_vm(&handle, 102, "idle").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_power(app_addr, 101, "shutdown").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    wait_for_status(&handle, 101, VmStatus::Stopped).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+
+    let response = post_power(app_addr, 102, "shutdown").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "VM 102 is already stopped");
+}
+
+#[tokio::test]
+async fn suspend_and_resume_endpoints_pause_and_continue_a_vm() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "desktop").await;
+    insert_stopped_vm(&handle, 102, "idle").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = post_power(app_addr, 101, "resume").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "VM 101 is running, not paused");
+
+    let response = post_power(app_addr, 101, "suspend").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body, serde_json::json!({ "status": "suspending" }));
+    wait_for_status(&handle, 101, VmStatus::Paused).await;
+
+    let response = post_power(app_addr, 101, "suspend").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"], "VM 101 is paused, not running");
+
+    let response = post_power(app_addr, 101, "resume").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body, serde_json::json!({ "status": "resuming" }));
+    wait_for_status(&handle, 101, VmStatus::Running).await;
+
+    let response = post_power(app_addr, 102, "suspend").await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+
+    let response = Client::new()
+        .post(format!("http://{app_addr}/api/vms/101/suspend"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn power_endpoints_require_the_admin_key() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/vms/101/start"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    for action in ["start", "shutdown", "hibernate", "terminate", "reset"] {
+        let response = client
+            .post(format!("http://{app_addr}/api/vms/101/action"))
+            .json(&serde_json::json!({ "action": action }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNAUTHORIZED,
+            "{action}"
+        );
+    }
+    for action in ["start", "stop", "terminate"] {
+        let response = client
+            .post(format!("http://{app_addr}/api/vms/bulk-action"))
+            .json(&serde_json::json!({ "vmids": [101], "action": action }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNAUTHORIZED,
+            "{action}"
+        );
+    }
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+    handle.assert_action_sequence(&[]).await;
+}
+
+async fn next_ws_json(
+    socket: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> serde_json::Value {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    loop {
+        let message = timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("timed out waiting for a WebSocket message")
+            .expect("WebSocket closed")
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn vm_websocket_pushes_snapshot_then_status_changes() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    insert_running_vm(&handle, 102, "server").await;
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state.with_vm_events_poll_interval(Duration::from_millis(50))
+    })
+    .await;
+    let url = format!("ws://{app_addr}/api/ws/vms");
+
+    let (mut first, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let (mut second, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    for socket in [&mut first, &mut second] {
+        let snapshot = next_ws_json(socket).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        let mut statuses: Vec<_> = snapshot["vms"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|vm| (vm["vmid"].as_u64().unwrap(), vm["status"].clone()))
+            .collect();
+        statuses.sort_by_key(|(vmid, _)| *vmid);
+        assert_eq!(statuses, [(101, "stopped".into()), (102, "running".into())]);
+    }
+
+    // Let the poller record a baseline before changing anything.
+    sleep(Duration::from_millis(200)).await;
+    handle.set_status(101, VmStatus::Running).await;
+    for socket in [&mut first, &mut second] {
+        let delta = next_ws_json(socket).await;
+        assert_eq!(
+            delta,
+            serde_json::json!({
+                "type": "delta",
+                "changes": [{ "vmid": 101, "status": "running" }],
+            })
+        );
+    }
+
+    first.close(None).await.unwrap();
+    drop(first);
+    handle.set_status(102, VmStatus::Stopped).await;
+    let delta = next_ws_json(&mut second).await;
+    assert_eq!(delta["changes"][0]["vmid"], 102);
+    assert_eq!(delta["changes"][0]["status"], "stopped");
+    second.send(Message::Close(None)).await.unwrap();
+}
+
+#[tokio::test]
+async fn cpu_and_memory_can_be_changed() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "worker").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let patch = |resource: &str, body: serde_json::Value| {
+        client
+            .patch(format!("http://{app_addr}/api/vms/101/{resource}"))
+            .json(&body)
+            .send()
+    };
+
+    let response = patch("cpu", serde_json::json!({ "sockets": 2, "cores": 8 }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let response = patch("memory", serde_json::json!({ "memory_mib": 16384 }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    for (resource, body) in [
+        ("cpu", serde_json::json!({ "sockets": 0, "cores": 8 })),
+        ("cpu", serde_json::json!({ "sockets": 32, "cores": 32 })),
+        ("memory", serde_json::json!({ "memory_mib": 8 })),
+    ] {
+        let response = patch(resource, body.clone()).await.unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            "{resource} {body}"
+        );
+    }
+
+    let hardware = handle.hardware(101).await.unwrap();
+    assert_eq!(
+        (hardware.sockets, hardware.cores, hardware.memory),
+        (2, 8, 16384)
+    );
+}
+
+#[tokio::test]
+async fn resize_disk_validates_size_before_calling_proxmox() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "clone").await;
+    let app_addr = spawn_agent(&handle).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/api/vms/101/disk/resize");
+
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "disk": "scsi0", "size": "+10G" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    for (disk, size) in [("scsi0", "-10G"), ("scsi0", "512M"), ("../scsi0", "+1G")] {
+        let response = client
+            .post(&url)
+            .json(&serde_json::json!({ "disk": disk, "size": size }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            "{disk} {size}"
+        );
+    }
+
+    let resizes = handle.disk_resizes().await;
+    assert_eq!(resizes.len(), 1);
+    assert_eq!(resizes[0].vmid, 101);
+    assert_eq!(resizes[0].size, "+10G");
+}
+
+#[tokio::test]
+async fn agent_exec_runs_command_in_guest() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let client = Client::new();
+    let url = format!("http://{app_addr}/admin/vms/101/agent/exec");
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "command": "echo", "args": ["hello", "world"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "exit_code": 0, "stdout": "hello world\n", "stderr": "" })
+    );
+    let execs = handle.agent_execs().await;
+    assert_eq!(execs.len(), 1);
+    assert_eq!(execs[0].command, ["echo", "hello", "world"]);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "command": "x".repeat(4097) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(handle.agent_execs().await.len(), 1);
+}
+
+#[tokio::test]
+async fn delete_running_vm_is_a_conflict() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 101, "busy").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+
+    let response = Client::new()
+        .delete(format!("http://{app_addr}/admin/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn delete_refuses_protected_and_fallback_vms() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(VmEntry::builder(101, "keeper").tags(&["Protected"]).build())
+        .await;
+    insert_stopped_vm(&handle, 102, "desktop").await;
+    let fallback = FallbackHandle::new(FallbackSelector {
+        name: Some("desktop".to_string()),
+        tag: None,
+    });
+    let app_addr = spawn_admin_agent(&handle, |state| state.with_fallback(fallback)).await;
+    let client = Client::new();
+
+    for vmid in [101, 102] {
+        let response = client
+            .delete(format!("http://{app_addr}/admin/vms/{vmid}"))
+            .bearer_auth(ADMIN_KEY)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        assert_eq!(handle.status(vmid).await, Some(VmStatus::Stopped));
+    }
+}
+
+#[tokio::test]
+async fn delete_checks_the_current_status_despite_the_vm_list_cache() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "scratch").await;
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let proxmox =
+        ProxmoxClient::builder(format!("http://{dummy_addr}"), "token-id", "token-secret")
+            .vm_list_cache_ttl(Duration::from_secs(60))
+            .build()
+            .unwrap();
+    let app_addr = spawn_app(router(AppState::new(proxmox).with_admin_api_key(ADMIN_KEY))).await;
+    let client = Client::new();
+
+    let response = reqwest::get(format!("http://{app_addr}/api/vms"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // Started behind the agent's back, so only Proxmox knows.
+    handle.set_status(101, VmStatus::Running).await;
+
+    let response = client
+        .delete(format!("http://{app_addr}/admin/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn protected_vmids_block_launch_and_host_shutdown() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "infra").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state
+            .with_shutdown_config(ShutdownConfig { dry_run: true })
+            .with_protected_vmids([100])
+    })
+    .await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("http://{app_addr}/api/launch"))
+        .json(&serde_json::json!({ "vmid": 200, "action": "terminate" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let response = client
+        .post(format!("http://{app_addr}/api/host-shutdown"))
+        .json(&serde_json::json!({ "action": "shutdown" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(200).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn running_vm_outside_allowed_vmids_blocks_launch_and_host_shutdown() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 102, "other-team").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let app_addr = spawn_agent_with(&handle, |state| {
+        state
+            .with_shutdown_config(ShutdownConfig { dry_run: true })
+            .with_allowed_vmids([200])
+    })
+    .await;
+    let client = Client::new();
+
+    for body in [
+        serde_json::json!({ "vmid": 200 }),
+        serde_json::json!({ "vmid": 200, "action": "terminate" }),
+    ] {
+        let response = client
+            .post(format!("http://{app_addr}/api/launch"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN, "{body}");
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"], "VM 102 is not managed by this agent");
+    }
+
+    let response = client
+        .post(format!("http://{app_addr}/api/host-shutdown"))
+        .json(&serde_json::json!({ "action": "shutdown" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    assert_eq!(handle.status(102).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(200).await, Some(VmStatus::Stopped));
+    handle.assert_action_sequence(&[]).await;
+}
+
+#[tokio::test]
+async fn protected_vmids_cannot_be_deleted_terminated_or_bulk_stopped() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "infra").await;
+    insert_stopped_vm(&handle, 101, "archive").await;
+    insert_running_vm(&handle, 102, "lab").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state.with_protected_vmids([100, 101])).await;
+    let client = Client::new();
+
+    let response = client
+        .delete(format!("http://{app_addr}/admin/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+
+    for action in ["terminate", "reset", "reboot"] {
+        let response = client
+            .post(format!("http://{app_addr}/api/vms/100/action"))
+            .bearer_auth(ADMIN_KEY)
+            .json(&serde_json::json!({ "action": action }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::FORBIDDEN,
+            "{action}"
+        );
+    }
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+    handle.assert_action_sequence(&[]).await;
+
+    let response = post_bulk_action(app_addr, &[100, 102], "stop").await;
+    assert_eq!(response.status(), reqwest::StatusCode::MULTI_STATUS);
+    let body = response.json::<BulkActionResponse>().await.unwrap();
+    let failed: Vec<u64> = body
+        .results
+        .iter()
+        .filter(|result| !result.ok)
+        .map(|result| result.vmid)
+        .collect();
+    assert_eq!(failed, vec![100]);
+    assert_eq!(handle.status(100).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(102).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn allowed_vmids_hide_and_refuse_other_vms() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 100, "alpha").await;
+    insert_stopped_vm(&handle, 101, "beta").await;
+    insert_running_vm(&handle, 102, "other-team").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state.with_allowed_vmids([100, 101])).await;
+    let client = Client::new();
+
+    let vms = client
+        .get(format!("http://{app_addr}/api/vms"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<ApiVm>>()
+        .await
+        .unwrap();
+    let mut vmids: Vec<u64> = vms.iter().map(|vm| vm.vmid).collect();
+    vmids.sort();
+    assert_eq!(vmids, [100, 101]);
+
+    let fork = serde_json::json!({ "name": "copy" });
+    let requests = [
+        (
+            reqwest::Method::POST,
+            "/api/launch",
+            serde_json::json!({ "vmid": 102 }),
+        ),
+        (
+            reqwest::Method::POST,
+            "/api/fork",
+            serde_json::json!({ "vmid": 102, "name": "copy" }),
+        ),
+        (reqwest::Method::POST, "/api/vms/102/fork", fork.clone()),
+        (reqwest::Method::POST, "/api/vms/102/clone", fork),
+        (
+            reqwest::Method::POST,
+            "/api/vms/102/action",
+            serde_json::json!({ "action": "terminate" }),
+        ),
+        (
+            reqwest::Method::POST,
+            "/api/vms/102/stop",
+            serde_json::Value::Null,
+        ),
+        (
+            reqwest::Method::DELETE,
+            "/admin/vms/102",
+            serde_json::Value::Null,
+        ),
+        (
+            reqwest::Method::GET,
+            "/api/vms/102/uptime",
+            serde_json::Value::Null,
+        ),
+    ];
+    for (method, path, body) in requests {
+        let mut request = client
+            .request(method.clone(), format!("http://{app_addr}{path}"))
+            .bearer_auth(ADMIN_KEY);
+        if !body.is_null() {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::FORBIDDEN,
+            "{method} {path}"
+        );
+    }
+    assert_eq!(handle.status(102).await, Some(VmStatus::Running));
+    assert!(handle.clones().await.is_empty());
+}
+
+#[tokio::test]
+async fn fallback_trigger_starts_the_fallback_vm_without_waiting() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+    let fallback = spawn_fallback_task(
+        client.clone(),
+        FallbackSelector {
+            name: Some("desktop".to_string()),
+            tag: None,
+        },
+    );
+    let app_addr = spawn_app(router(
+        AppState::new(client)
+            .with_fallback(fallback)
+            .with_admin_api_key(ADMIN_KEY),
+    ))
+    .await;
+    let http = Client::new();
+    let post = |path: &str| {
+        http.post(format!("http://{app_addr}/admin/fallback/{path}"))
+            .bearer_auth(ADMIN_KEY)
+            .send()
+    };
+
+    post("inhibit").await.unwrap();
+    let response = post("trigger").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "error": "fallback inhibited" })
+    );
+
+    post("enable").await.unwrap();
+    let response = post("trigger").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "triggered": true })
+    );
+    wait_for_status(&handle, 101, VmStatus::Running).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+/// Agent with a running fallback task selecting the VM named "desktop".
+async fn spawn_fallback_agent(handle: &DummyHandle) -> SocketAddr {
+    spawn_fallback_agent_with(
+        handle,
+        FallbackSelector {
+            name: Some("desktop".to_string()),
+            tag: None,
+        },
+    )
+    .await
+}
+
+async fn spawn_fallback_agent_with(handle: &DummyHandle, selector: FallbackSelector) -> SocketAddr {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+    let fallback = spawn_fallback_task(client.clone(), selector);
+    spawn_app(router(
+        AppState::new(client)
+            .with_fallback(fallback)
+            .with_admin_api_key(ADMIN_KEY),
+    ))
+    .await
+}
+
+async fn put_fallback_name(app_addr: SocketAddr, name: Option<&str>) -> reqwest::Response {
+    Client::new()
+        .put(format!("http://{app_addr}/api/config/fallback"))
+        .bearer_auth(ADMIN_KEY)
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn trigger_fallback(app_addr: SocketAddr) {
+    let response = Client::new()
+        .post(format!("http://{app_addr}/admin/fallback/trigger"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn fallback_name_can_be_changed_at_runtime() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    insert_stopped_vm(&handle, 102, "laptop").await;
+    let app_addr = spawn_fallback_agent(&handle).await;
+    let config_url = format!("http://{app_addr}/api/config/fallback");
+
+    let current = reqwest::get(&config_url)
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(current, serde_json::json!({ "name": "desktop" }));
+
+    let response = put_fallback_name(app_addr, Some("laptop")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "name": "laptop" })
+    );
+    let current = reqwest::get(&config_url)
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(current["name"], "laptop");
+
+    trigger_fallback(app_addr).await;
+    wait_for_status(&handle, 102, VmStatus::Running).await;
+    assert_eq!(handle.status(102).await, Some(VmStatus::Running));
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+}
+
+#[tokio::test]
+async fn null_fallback_name_disables_auto_start() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_fallback_agent(&handle).await;
+
+    let response = put_fallback_name(app_addr, None).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        serde_json::json!({ "name": null })
+    );
+
+    trigger_fallback(app_addr).await;
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+
+    put_fallback_name(app_addr, Some("desktop")).await;
+    trigger_fallback(app_addr).await;
+    wait_for_status(&handle, 101, VmStatus::Running).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn fallback_can_be_enabled_without_startup_config() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_fallback_agent_with(&handle, FallbackSelector::default()).await;
+
+    let current = reqwest::get(format!("http://{app_addr}/api/config/fallback"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(current, serde_json::json!({ "name": null }));
+
+    let response = put_fallback_name(app_addr, Some("desktop")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    trigger_fallback(app_addr).await;
+    wait_for_status(&handle, 101, VmStatus::Running).await;
+    assert_eq!(handle.status(101).await, Some(VmStatus::Running));
+}
+
+#[tokio::test]
+async fn fallback_config_changes_require_the_admin_key() {
+    let handle = DummyHandle::new("pve");
+    let app_addr = spawn_fallback_agent(&handle).await;
+
+    let response = Client::new()
+        .put(format!("http://{app_addr}/api/config/fallback"))
+        .json(&serde_json::json!({ "name": "laptop" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let current = reqwest::get(format!("http://{app_addr}/api/config/fallback"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(current["name"], "desktop");
+}
+
+#[tokio::test]
+async fn fork_with_node_hint_skips_every_node_lookup() {
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    let handle = DummyHandle::new("pve");
+    for vmid in 101..=150 {
+        insert_stopped_vm(&handle, vmid, &format!("vm-{vmid}")).await;
+    }
+    let (dummy_addr, _dummy_task) = spawn_dummy_server(handle.clone()).await.unwrap();
+    let client = ProxmoxClient::new(
+        format!("http://{dummy_addr}"),
+        "token-id",
+        "token-secret",
+        false,
+    )
+    .unwrap();
+    let hinted = client.clone().with_node_hint("pve".to_string());
+
+    // Distinct sources, as fork snapshots are named after the current second.
+    let mut counts = Vec::new();
+    for (client, source) in [(&client, 101), (&hinted, 102)] {
+        let before = handle.requests().await.len();
+        client
+            .fork_vm(source, ForkOptions::new(format!("copy-of-{source}")))
+            .await
+            .unwrap();
+        let requests = handle.requests().await;
+        let lookups = requests[before..]
+            .iter()
+            .filter(|request| request.path.ends_with("/cluster/resources"))
+            .count();
+        counts.push((requests.len() - before, lookups));
+    }
+    let [(uncached, uncached_lookups), (cached, cached_lookups)] = counts[..] else {
+        unreachable!()
+    };
+    // Each VM-scoped step (snapshot, clone) resolves the node once without a
+    // hint; with one, only the Proxmox calls themselves remain.
+    assert_eq!(uncached_lookups, 2);
+    assert_eq!(cached_lookups, 0);
+    assert_eq!(
+        uncached - cached,
+        uncached_lookups,
+        "{uncached} requests without the hint, {cached} with"
+    );
+}
+
+#[tokio::test]
+async fn admin_routes_require_the_admin_key() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "alpha").await;
+    let client = Client::new();
+
+    let disabled = spawn_agent(&handle).await;
+    let response = client
+        .delete(format!("http://{disabled}/admin/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let url = format!("http://{app_addr}/admin/vms/101");
+    let response = client.delete(&url).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = client
+        .delete(&url)
+        .bearer_auth("not-the-admin-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(handle.status(101).await, Some(VmStatus::Stopped));
+
+    let response = client
+        .delete(format!("http://{app_addr}/api/vms/101"))
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = client
+        .delete(&url)
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn history_lists_power_transitions_in_order() {
+    let handle = DummyHandle::new("pve");
+    insert_stopped_vm(&handle, 101, "desktop").await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let history_url = format!("http://{app_addr}/api/vms/101/history");
+
+    let history = reqwest::get(&history_url)
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert!(history.is_empty());
+
+    for _ in 0..2 {
+        let response = post_power(app_addr, 101, "start").await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        wait_for_status(&handle, 101, VmStatus::Running).await;
+        let response = post_power(app_addr, 101, "stop").await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        wait_for_status(&handle, 101, VmStatus::Stopped).await;
+    }
+
+    let history = reqwest::get(&history_url)
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    let steps: Vec<(&str, &str)> = history
+        .iter()
+        .map(|transition| {
+            (
+                transition["from"].as_str().unwrap(),
+                transition["to"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            ("stopped", "running"),
+            ("running", "stopped"),
+            ("stopped", "running"),
+            ("running", "stopped"),
+        ]
+    );
+    let times: Vec<u64> = history
+        .iter()
+        .map(|transition| transition["transitioned_at"].as_u64().unwrap())
+        .collect();
+    assert!(times.windows(2).all(|pair| pair[0] <= pair[1]), "{times:?}");
+
+    let dummy_steps: Vec<(VmStatus, VmStatus)> = handle
+        .transitions(101)
+        .await
+        .into_iter()
+        .map(|transition| (transition.from, transition.to))
+        .collect();
+    assert_eq!(
+        dummy_steps,
+        [
+            (VmStatus::Stopped, VmStatus::Running),
+            (VmStatus::Running, VmStatus::Stopped),
+            (VmStatus::Stopped, VmStatus::Running),
+            (VmStatus::Running, VmStatus::Stopped),
+        ]
+    );
+
+    let response = reqwest::get(format!("http://{app_addr}/api/vms/999/history"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn history_includes_transitions_made_by_a_launch() {
+    let handle = DummyHandle::new("pve");
+    insert_running_vm(&handle, 100, "busy").await;
+    insert_stopped_vm(&handle, 200, "target").await;
+    let (app_addr, mut launches) = spawn_agent_watching_launches(&handle, |state| state).await;
+
+    let response = post_launch(
+        app_addr,
+        serde_json::json!({ "vmid": 200, "action": "suspend" }),
+    )
+    .await;
+    assert_eq!(response.status, "started");
+    wait_for_launch(&mut launches).await;
+
+    for (vmid, expected) in [(100, ("running", "paused")), (200, ("stopped", "running"))] {
+        let history = reqwest::get(format!("http://{app_addr}/api/vms/{vmid}/history"))
+            .await
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap();
+        let steps: Vec<(&str, &str)> = history
+            .iter()
+            .map(|transition| {
+                (
+                    transition["from"].as_str().unwrap(),
+                    transition["to"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(steps, [expected], "VM {vmid}");
+    }
+}
+
+#[tokio::test]
+async fn token_permissions_are_listed_for_admins() {
+    let handle = DummyHandle::new("pve");
+    handle
+        .set_permissions("/vms", &["VM.PowerMgmt", "VM.Audit"])
+        .await;
+    let app_addr = spawn_admin_agent(&handle, |state| state).await;
+    let url = format!("http://{app_addr}/api/access/permissions");
+
+    let response = Client::new().get(&url).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = Client::new()
+        .get(&url)
+        .bearer_auth(ADMIN_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "/vms": ["VM.Audit", "VM.PowerMgmt"] })
+    );
 }
@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+use proxmox_dummy::{spawn_dummy_server, DummyHandle, VmEntry, VmStatus};
+use risky_proxmox_agent::config::{CliArgs, Config};
+use risky_proxmox_agent::proxmox::ProxmoxClient;
+use risky_proxmox_agent::server::{router, AppState};
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::TestWriter;
+
+/// Collects formatted tracing output while also echoing it to the test harness.
+#[derive(Clone, Default)]
+struct LogCapture(Arc<Mutex<Vec<u8>>>);
+
+impl LogCapture {
+    fn install(&self) -> tracing::subscriber::DefaultGuard {
+        let capture = self.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer((move || capture.clone()).and(TestWriter::new()))
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Mirrors the wiring in `main.rs`: config from the environment, client,
+/// state and router, served on the configured address.
+async fn start_from_env() -> SocketAddr {
+    // Parsed from fixed args rather than the test binary's own argv, which
+    // carries libtest flags and filters.
+    let args = CliArgs::parse_from(["risky-proxmox-agent", "--bind", "127.0.0.1", "--port", "0"]);
+    let config = Config::from_args(args).expect("config should load from env");
+
+    let client = ProxmoxClient::builder(
+        config.pve_host.clone(),
+        &config.pve_token_id,
+        &config.pve_token_secret,
+    )
+    .insecure_ssl(config.pve_insecure_ssl)
+    .cert_fingerprint(config.pve_cert_fingerprint.clone())
+    .vm_list_cache_ttl(Duration::from_millis(config.pve_vm_list_cache_ttl_ms))
+    .build()
+    .expect("client should build");
+    client
+        .probe_and_version()
+        .await
+        .expect("dummy should answer the startup probe");
+
+    let state =
+        AppState::new(client).with_protected_vmids(config.pve_protected_vmids.iter().copied());
+    let app = router(state);
+    let listener = tokio::net::TcpListener::bind((config.bind, config.port))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    addr
+}
+
+#[tokio::test]
+async fn agent_starts_from_environment_against_dummy_proxmox() {
+    let logs = LogCapture::default();
+    let _guard = logs.install();
+
+    let handle = DummyHandle::new("pve");
+    handle
+        .insert_vm(
+            VmEntry::builder(100, "alpha")
+                .status(VmStatus::Running)
+                .build(),
+        )
+        .await;
+    handle
+        .insert_vm(VmEntry::builder(101, "beta").tags(&["gaming"]).build())
+        .await;
+    let (dummy_addr, _task) = spawn_dummy_server(handle.clone()).await.unwrap();
+
+    // This is the only test in the binary, so the process environment is ours.
+    std::env::set_var("NO_PROXY", "127.0.0.1,localhost");
+    std::env::set_var("PVE_HOST", format!("http://{dummy_addr}"));
+    std::env::set_var("PVE_TOKEN_ID", "token-id");
+    std::env::set_var("PVE_TOKEN_SECRET", "token-secret");
+    std::env::set_var("PVE_PROTECTED_VMIDS", "100");
+    let app_addr = start_from_env().await;
+
+    let health = reqwest::get(format!("http://{app_addr}/health"))
+        .await
+        .unwrap();
+    assert_eq!(health.status(), reqwest::StatusCode::OK);
+
+    let vms = reqwest::get(format!("http://{app_addr}/api/vms"))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let mut names: Vec<&str> = vms
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|vm| vm["name"].as_str().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["alpha", "beta"]);
+
+    let logs = logs.contents();
+    assert!(logs.contains("Proxmox API reachable"), "{logs}");
+    assert!(
+        logs.contains("Incoming HTTP request method=GET path=/health"),
+        "{logs}"
+    );
+    assert!(logs.contains("VM list retrieved"), "{logs}");
+}